@@ -0,0 +1,758 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use serde::{Serialize, Serializer};
+
+use crate::token::{OpType, Span};
+use crate::types::Type;
+use crate::value::Value;
+
+/// The address of a node, which tells it apart from the other nodes of the
+/// tree for as long as the tree lives and is not moved
+pub fn node_address<T>(node: &T) -> usize {
+    node as *const T as usize
+}
+
+/// An expression, which is evaluated to a value
+#[derive(Debug, Serialize)]
+pub enum Expr {
+    BinOp(BinOp),
+    Literal(Literal),
+    UnaryOp(UnaryOp),
+    Var(Var),
+    Index(Index),
+    Field(Field),
+    Deref(Deref),
+    SetConstructor(SetConstructor),
+    FunctionCall(FunctionCall),
+    /// only found among the arguments of WRITE, WRITELN and STR
+    FormattedParam(FormattedParam),
+}
+
+impl Expr {
+    /// The source the expression was read from
+    pub fn span(&self) -> &Span {
+        match self {
+            Expr::BinOp(node) => &node.span,
+            Expr::Literal(node) => &node.span,
+            Expr::UnaryOp(node) => &node.span,
+            Expr::Var(node) => &node.span,
+            Expr::Index(node) => &node.span,
+            Expr::Field(node) => &node.span,
+            Expr::Deref(node) => &node.span,
+            Expr::SetConstructor(node) => &node.span,
+            Expr::FunctionCall(node) => &node.span,
+            Expr::FormattedParam(node) => &node.span,
+        }
+    }
+}
+
+/// A statement, which is run for what it does
+#[derive(Debug, Serialize)]
+pub enum Stmt {
+    Compound(Compound),
+    Assign(Assign),
+    ProcedureCall(ProcedureCall),
+    If(If),
+    While(While),
+    For(For),
+    Repeat(Repeat),
+    Case(Case),
+    With(With),
+    /// `BREAK`, leaving the innermost loop
+    Break(Span),
+    /// `CONTINUE`, going on with the next iteration of the innermost loop
+    Continue(Span),
+    Exit(Exit),
+    Goto(Goto),
+    Labeled(Labeled),
+    TryExcept(TryExcept),
+    TryFinally(TryFinally),
+    Raise(Raise),
+    /// the empty statement
+    NoOp(Span),
+}
+
+impl Stmt {
+    /// The source the statement was read from
+    pub fn span(&self) -> &Span {
+        match self {
+            Stmt::Compound(node) => &node.span,
+            Stmt::Assign(node) => &node.span,
+            Stmt::ProcedureCall(node) => &node.span,
+            Stmt::If(node) => &node.span,
+            Stmt::While(node) => &node.span,
+            Stmt::For(node) => &node.span,
+            Stmt::Repeat(node) => &node.span,
+            Stmt::Case(node) => &node.span,
+            Stmt::With(node) => &node.span,
+            Stmt::Exit(node) => &node.span,
+            Stmt::Goto(node) => &node.span,
+            Stmt::Labeled(node) => &node.span,
+            Stmt::TryExcept(node) => &node.span,
+            Stmt::TryFinally(node) => &node.span,
+            Stmt::Raise(node) => &node.span,
+            Stmt::Break(span) | Stmt::Continue(span) | Stmt::NoOp(span) => span,
+        }
+    }
+}
+
+/// A declaration in a block
+#[derive(Debug, Serialize)]
+pub enum Decl {
+    Label(LabelDecl),
+    Const(ConstDecl),
+    TypedConst(TypedConstDecl),
+    Type(TypeDecl),
+    Var(VarDecl),
+    Forward(ForwardDecl),
+    Procedure(Rc<ProcedureDecl>),
+    Function(Rc<FunctionDecl>),
+}
+
+impl Decl {
+    /// The source the declaration was read from
+    pub fn span(&self) -> &Span {
+        match self {
+            Decl::Label(node) => &node.span,
+            Decl::Const(node) => &node.span,
+            Decl::TypedConst(node) => &node.span,
+            Decl::Type(node) => &node.span,
+            Decl::Var(node) => &node.var_node.span,
+            Decl::Forward(node) => &node.span,
+            Decl::Procedure(node) => &node.span,
+            Decl::Function(node) => &node.span,
+        }
+    }
+}
+
+/// A procedure or function as the frame of the block declaring it keeps it
+#[derive(Clone, Debug)]
+pub enum Routine {
+    Procedure(Rc<ProcedureDecl>),
+    Function(Rc<FunctionDecl>),
+}
+
+#[derive(Debug, Serialize)]
+pub struct BinOp {
+    pub op_type: OpType,
+    pub left: Box<Expr>,
+    pub right: Box<Expr>,
+    pub span: Span,
+}
+impl BinOp {
+    pub fn new(op_type: OpType, left: Expr, right: Expr, span: Span) -> BinOp {
+        BinOp { op_type, left: Box::new(left), right: Box::new(right), span }
+    }
+}
+
+/// A literal constant such as `3`, `2.5` or `TRUE`, or the value of LOW,
+/// HIGH or SIZEOF of a type, which the parser computes
+#[derive(Debug, Serialize)]
+pub struct Literal {
+    pub value: Value,
+    /// the function and the type as written, e.g. `HIGH` and `Digits` for
+    /// `HIGH(Digits)`, when the value is that of a type function
+    pub call: Option<(String, Type)>,
+    pub span: Span,
+}
+impl Literal {
+    pub fn new(value: Value, span: Span) -> Literal {
+        Literal { value, call: None, span }
+    }
+    pub fn of_call(value: Value, function: &str, type_node: Type, span: Span) -> Literal {
+        Literal { value, call: Some((String::from(function), type_node)), span }
+    }
+}
+
+/// A constant expression, e.g. `N * 2`, along with the value the parser
+/// folds it into
+#[derive(Clone, Debug, Serialize)]
+pub struct Constant {
+    pub value: Value,
+    /// the expression as written, which is only printed back
+    pub source: Rc<Expr>,
+}
+impl Constant {
+    pub fn new(value: Value, source: Expr) -> Constant {
+        Constant { value, source: Rc::new(source) }
+    }
+}
+/// Constants are the same when their values are, so that `1..N` and `1..10`
+/// are the same type when `N` is 10
+impl PartialEq for Constant {
+    fn eq(&self, other: &Constant) -> bool {
+        self.value == other.value
+    }
+}
+
+/// `-x`, `+x` or `NOT x`
+#[derive(Debug, Serialize)]
+pub struct UnaryOp {
+    pub op_type: OpType,
+    pub expr: Box<Expr>,
+    pub span: Span,
+}
+impl UnaryOp {
+    pub fn new(op_type: OpType, expr: Expr, span: Span) -> UnaryOp {
+        UnaryOp { op_type, expr: Box::new(expr), span }
+    }
+}
+
+/// A variable reference, e.g. the `x` in `x := x + 1`
+#[derive(Debug, Serialize)]
+pub struct Var {
+    pub name: String,
+    pub span: Span,
+}
+impl Var {
+    pub fn new(name: &str, span: Span) -> Var {
+        Var { name: String::from(name), span }
+    }
+}
+
+/// `array[index]`, where `a[i, j]` is parsed as `a[i][j]`
+#[derive(Debug, Serialize)]
+pub struct Index {
+    pub array: Box<Expr>,
+    pub index: Box<Expr>,
+    pub span: Span,
+}
+impl Index {
+    pub fn new(array: Expr, index: Expr, span: Span) -> Index {
+        Index { array: Box::new(array), index: Box::new(index), span }
+    }
+}
+
+/// `record.name`
+#[derive(Debug, Serialize)]
+pub struct Field {
+    pub record: Box<Expr>,
+    pub name: String,
+    pub span: Span,
+}
+impl Field {
+    pub fn new(record: Expr, name: &str, span: Span) -> Field {
+        Field { record: Box::new(record), name: String::from(name), span }
+    }
+}
+
+/// `pointer^`, the heap cell a pointer refers to
+#[derive(Debug, Serialize)]
+pub struct Deref {
+    pub pointer: Box<Expr>,
+    pub span: Span,
+}
+impl Deref {
+    pub fn new(pointer: Expr, span: Span) -> Deref {
+        Deref { pointer: Box::new(pointer), span }
+    }
+}
+
+/// `[1, 3..5, n]`
+#[derive(Debug, Serialize)]
+pub struct SetConstructor {
+    pub elements: Vec<ValueRange>,
+    pub span: Span,
+}
+impl SetConstructor {
+    pub fn new(elements: Vec<ValueRange>, span: Span) -> SetConstructor {
+        SetConstructor { elements, span }
+    }
+}
+
+/// `left := right`, where `left` is a `Var`, `Index`, `Field` or `Deref`
+#[derive(Debug, Serialize)]
+pub struct Assign {
+    pub left: Expr,
+    pub right: Expr,
+    pub span: Span,
+}
+impl Assign {
+    pub fn new(left: Expr, right: Expr, span: Span) -> Assign {
+        Assign { left, right, span }
+    }
+}
+
+/// `BEGIN ... END`
+#[derive(Debug, Serialize)]
+pub struct Compound {
+    pub children: Vec<Stmt>,
+    pub span: Span,
+}
+impl Compound {
+    pub fn new(children: Vec<Stmt>, span: Span) -> Compound {
+        Compound { children, span }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct VarDecl {
+    pub var_node: Var,
+    pub type_node: Type,
+    /// for a parameter declared CONST, which the routine may not assign to
+    pub is_const: bool,
+}
+impl VarDecl {
+    pub fn new(var_node: Var, type_node: Type) -> VarDecl {
+        VarDecl { var_node, type_node, is_const: false }
+    }
+    pub fn new_const(var_node: Var, type_node: Type) -> VarDecl {
+        VarDecl { var_node, type_node, is_const: true }
+    }
+}
+
+/// `LABEL label;`
+#[derive(Debug, Serialize)]
+pub struct LabelDecl {
+    pub label: String,
+    pub span: Span,
+}
+impl LabelDecl {
+    pub fn new(label: &str, span: Span) -> LabelDecl {
+        LabelDecl { label: String::from(label), span }
+    }
+}
+
+/// `CONST name = constant;`
+#[derive(Debug, Serialize)]
+pub struct ConstDecl {
+    pub name: String,
+    pub constant: Constant,
+    pub span: Span,
+}
+impl ConstDecl {
+    pub fn new(name: &str, constant: Constant, span: Span) -> ConstDecl {
+        ConstDecl { name: String::from(name), constant, span }
+    }
+}
+
+/// The value of a typed constant, folded by the parser but converted to the
+/// declared type only once that type is resolved
+#[derive(Debug, Serialize)]
+pub enum Initializer {
+    /// a constant, including a set of constants
+    Value(Constant),
+    /// `(first, second, ...)`, the elements of an array
+    List(Vec<Initializer>),
+    /// `(name: value; ...)`, the fields of a record in the order they are declared
+    Fields(Vec<(String, Initializer)>),
+}
+
+/// `CONST name: type_node = initializer;`, a variable starting out with the
+/// initializer which keeps its value between the calls of its routine
+#[derive(Debug, Serialize)]
+pub struct TypedConstDecl {
+    pub name: String,
+    pub type_node: Type,
+    pub initializer: Initializer,
+    pub span: Span,
+}
+impl TypedConstDecl {
+    pub fn new(
+        name: &str,
+        type_node: Type,
+        initializer: Initializer,
+        span: Span,
+    ) -> TypedConstDecl {
+        TypedConstDecl { name: String::from(name), type_node, initializer, span }
+    }
+}
+
+/// `TYPE name = type_node;`
+#[derive(Debug, Serialize)]
+pub struct TypeDecl {
+    pub name: String,
+    pub type_node: Type,
+    pub span: Span,
+}
+impl TypeDecl {
+    pub fn new(name: &str, type_node: Type, span: Span) -> TypeDecl {
+        TypeDecl { name: String::from(name), type_node, span }
+    }
+}
+
+/// `IF condition THEN then_branch ELSE else_branch`
+#[derive(Debug, Serialize)]
+pub struct If {
+    pub condition: Expr,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+    pub span: Span,
+}
+impl If {
+    pub fn new(condition: Expr, then_branch: Stmt, else_branch: Option<Stmt>, span: Span) -> If {
+        let (then_branch, else_branch) = (Box::new(then_branch), else_branch.map(Box::new));
+        If { condition, then_branch, else_branch, span }
+    }
+}
+
+/// `WHILE condition DO body`
+#[derive(Debug, Serialize)]
+pub struct While {
+    pub condition: Expr,
+    pub body: Box<Stmt>,
+    pub span: Span,
+}
+impl While {
+    pub fn new(condition: Expr, body: Stmt, span: Span) -> While {
+        While { condition, body: Box::new(body), span }
+    }
+}
+
+/// `WITH record DO body`, where the fields of the record can be named on their own
+#[derive(Debug, Serialize)]
+pub struct With {
+    pub record: Expr,
+    pub body: Box<Stmt>,
+    pub span: Span,
+}
+impl With {
+    pub fn new(record: Expr, body: Stmt, span: Span) -> With {
+        With { record, body: Box::new(body), span }
+    }
+}
+
+/// `FOR variable := start TO end DO body`, or `DOWNTO` when `downto` is set
+#[derive(Debug, Serialize)]
+pub struct For {
+    pub variable: Var,
+    pub start: Expr,
+    pub end: Expr,
+    pub downto: bool,
+    pub body: Box<Stmt>,
+    pub span: Span,
+}
+impl For {
+    pub fn new(
+        variable: Var,
+        start: Expr,
+        end: Expr,
+        downto: bool,
+        body: Stmt,
+        span: Span,
+    ) -> For {
+        For { variable, start, end, downto, body: Box::new(body), span }
+    }
+}
+
+/// `REPEAT children UNTIL condition`
+#[derive(Debug, Serialize)]
+pub struct Repeat {
+    pub children: Vec<Stmt>,
+    pub condition: Expr,
+    pub span: Span,
+}
+impl Repeat {
+    pub fn new(children: Vec<Stmt>, condition: Expr, span: Span) -> Repeat {
+        Repeat { children, condition, span }
+    }
+}
+
+/// A single value or a `low..high` range, as a CASE label or an element of a set constructor
+#[derive(Debug, Serialize)]
+pub struct ValueRange {
+    pub low: Expr,
+    pub high: Option<Expr>,
+}
+impl ValueRange {
+    pub fn new(low: Expr, high: Option<Expr>) -> ValueRange {
+        ValueRange { low, high }
+    }
+}
+
+/// `1, 3..5: body`
+#[derive(Debug, Serialize)]
+pub struct CaseArm {
+    pub labels: Vec<ValueRange>,
+    pub body: Stmt,
+}
+impl CaseArm {
+    pub fn new(labels: Vec<ValueRange>, body: Stmt) -> CaseArm {
+        CaseArm { labels, body }
+    }
+}
+
+/// `CASE selector OF arms ELSE else_branch END`
+#[derive(Debug, Serialize)]
+pub struct Case {
+    pub selector: Expr,
+    pub arms: Vec<CaseArm>,
+    pub else_branch: Option<Vec<Stmt>>,
+    pub span: Span,
+}
+impl Case {
+    pub fn new(
+        selector: Expr,
+        arms: Vec<CaseArm>,
+        else_branch: Option<Vec<Stmt>>,
+        span: Span,
+    ) -> Case {
+        Case { selector, arms, else_branch, span }
+    }
+}
+
+/// `EXIT` or `EXIT(result)`, leaving the running procedure or function, or
+/// the program itself, a function returning `result` when given
+#[derive(Debug, Serialize)]
+pub struct Exit {
+    pub result: Option<Expr>,
+    pub span: Span,
+}
+impl Exit {
+    pub fn new(result: Option<Expr>, span: Span) -> Exit {
+        Exit { result, span }
+    }
+}
+
+/// `ON variable: class DO body`, handling the exceptions of the class, the
+/// exception being bound to `variable` when given
+#[derive(Debug, Serialize)]
+pub struct ExceptionHandler {
+    pub variable: Option<String>,
+    pub class: String,
+    pub body: Stmt,
+}
+impl ExceptionHandler {
+    pub fn new(variable: Option<String>, class: &str, body: Stmt) -> ExceptionHandler {
+        ExceptionHandler { variable, class: String::from(class), body }
+    }
+}
+
+/// `TRY body EXCEPT handlers ELSE else_branch END`, where an EXCEPT part of
+/// plain statements is an `else_branch` without handlers
+#[derive(Debug, Serialize)]
+pub struct TryExcept {
+    pub body: Vec<Stmt>,
+    pub handlers: Vec<ExceptionHandler>,
+    pub else_branch: Option<Vec<Stmt>>,
+    pub span: Span,
+}
+impl TryExcept {
+    pub fn new(
+        body: Vec<Stmt>,
+        handlers: Vec<ExceptionHandler>,
+        else_branch: Option<Vec<Stmt>>,
+        span: Span,
+    ) -> TryExcept {
+        TryExcept { body, handlers, else_branch, span }
+    }
+}
+
+/// `TRY body FINALLY finally END`, running `finally` however `body` is left
+#[derive(Debug, Serialize)]
+pub struct TryFinally {
+    pub body: Vec<Stmt>,
+    pub finally: Vec<Stmt>,
+    pub span: Span,
+}
+impl TryFinally {
+    pub fn new(body: Vec<Stmt>, finally: Vec<Stmt>, span: Span) -> TryFinally {
+        TryFinally { body, finally, span }
+    }
+}
+
+/// `RAISE message`, or a bare `RAISE` raising again the exception being handled
+#[derive(Debug, Serialize)]
+pub struct Raise {
+    pub message: Option<Expr>,
+    pub span: Span,
+}
+impl Raise {
+    pub fn new(message: Option<Expr>, span: Span) -> Raise {
+        Raise { message, span }
+    }
+}
+
+/// `label: statement`, a statement GOTO can jump to
+#[derive(Debug, Serialize)]
+pub struct Labeled {
+    pub label: String,
+    pub statement: Box<Stmt>,
+    pub span: Span,
+}
+impl Labeled {
+    pub fn new(label: &str, statement: Stmt, span: Span) -> Labeled {
+        Labeled { label: String::from(label), statement: Box::new(statement), span }
+    }
+}
+
+/// `GOTO label`
+#[derive(Debug, Serialize)]
+pub struct Goto {
+    pub label: String,
+    pub span: Span,
+}
+impl Goto {
+    pub fn new(label: &str, span: Span) -> Goto {
+        Goto { label: String::from(label), span }
+    }
+}
+
+/// `PROCEDURE name(params); block;`
+///
+/// A formal parameter is declared just like a variable local to the procedure.
+#[derive(Debug, Serialize)]
+pub struct ProcedureDecl {
+    pub name: String,
+    pub params: Vec<VarDecl>,
+    pub block: Block,
+    pub span: Span,
+}
+impl ProcedureDecl {
+    pub fn new(name: &str, params: Vec<VarDecl>, block: Block, span: Span) -> ProcedureDecl {
+        ProcedureDecl { name: String::from(name), params, block, span }
+    }
+}
+
+/// `PROCEDURE name(params); FORWARD;` or the same for a function, announcing
+/// a routine defined further down the same block
+#[derive(Debug, Serialize)]
+pub struct ForwardDecl {
+    pub name: String,
+    pub params: Vec<VarDecl>,
+    /// the result type of a function, `None` for a procedure
+    pub return_type: Option<Type>,
+    pub span: Span,
+}
+impl ForwardDecl {
+    pub fn new(
+        name: &str,
+        params: Vec<VarDecl>,
+        return_type: Option<Type>,
+        span: Span,
+    ) -> ForwardDecl {
+        ForwardDecl { name: String::from(name), params, return_type, span }
+    }
+}
+
+/// `name(actual_params)`
+#[derive(Debug, Serialize)]
+pub struct ProcedureCall {
+    pub name: String,
+    pub actual_params: Vec<Expr>,
+    pub span: Span,
+}
+impl ProcedureCall {
+    pub fn new(name: &str, actual_params: Vec<Expr>, span: Span) -> ProcedureCall {
+        ProcedureCall { name: String::from(name), actual_params, span }
+    }
+}
+
+/// `FUNCTION name(params): return_type; block;`
+///
+/// Assigning to `name` inside the block sets the result of the call.
+#[derive(Debug, Serialize)]
+pub struct FunctionDecl {
+    pub name: String,
+    pub params: Vec<VarDecl>,
+    pub return_type: Type,
+    pub block: Block,
+    pub span: Span,
+}
+impl FunctionDecl {
+    pub fn new(
+        name: &str,
+        params: Vec<VarDecl>,
+        return_type: Type,
+        block: Block,
+        span: Span,
+    ) -> FunctionDecl {
+        FunctionDecl { name: String::from(name), params, return_type, block, span }
+    }
+}
+
+/// `name(actual_params)` inside an expression
+#[derive(Debug, Serialize)]
+pub struct FunctionCall {
+    pub name: String,
+    pub actual_params: Vec<Expr>,
+    pub span: Span,
+}
+impl FunctionCall {
+    pub fn new(name: &str, actual_params: Vec<Expr>, span: Span) -> FunctionCall {
+        FunctionCall { name: String::from(name), actual_params, span }
+    }
+}
+
+/// `expr:width:precision`, an argument of WRITE or WRITELN
+#[derive(Debug, Serialize)]
+pub struct FormattedParam {
+    pub expr: Box<Expr>,
+    pub width: Box<Expr>,
+    pub precision: Option<Box<Expr>>,
+    pub span: Span,
+}
+impl FormattedParam {
+    pub fn new(expr: Expr, width: Expr, precision: Option<Expr>, span: Span) -> FormattedParam {
+        let precision = precision.map(Box::new);
+        FormattedParam { expr: Box::new(expr), width: Box::new(width), precision, span }
+    }
+}
+
+/// The declarations followed by the compound statement
+#[derive(Debug, Serialize)]
+pub struct Block {
+    pub declarations: Vec<Decl>,
+    pub compound_statement: Compound,
+    pub span: Span,
+}
+impl Block {
+    pub fn new(declarations: Vec<Decl>, compound_statement: Compound, span: Span) -> Block {
+        Block { declarations, compound_statement, span }
+    }
+}
+
+/// The root of the tree: `PROGRAM name; block .`
+#[derive(Debug, Serialize)]
+pub struct Program {
+    /// the name in the heading, `None` for a program without one
+    pub name: Option<String>,
+    /// the units the program names in its USES clause
+    pub uses: Vec<String>,
+    /// every unit the program needs, each after the units it uses, which
+    /// is the order their initialization runs in
+    pub units: Vec<Unit>,
+    pub block: Block,
+    pub span: Span,
+}
+impl Program {
+    pub fn new(
+        name: Option<String>,
+        uses: Vec<String>,
+        units: Vec<Unit>,
+        block: Block,
+        span: Span,
+    ) -> Program {
+        Program { name, uses, units, block, span }
+    }
+}
+
+/// `UNIT name; INTERFACE ... IMPLEMENTATION ... END.`, read from the file
+/// named after it when a USES clause names it
+#[derive(Debug, Serialize)]
+pub struct Unit {
+    pub name: String,
+    /// the units named by the USES clauses of its interface and implementation
+    pub uses: Vec<String>,
+    /// the names declared by its interface, which are all the programs and
+    /// units using it see of it
+    #[serde(serialize_with = "sorted")]
+    pub interface: HashSet<String>,
+    /// the declarations of the interface and the implementation together,
+    /// the routines of the interface having only their headings there as
+    /// `ForwardDecl`, along with the statements of its initialization
+    pub block: Block,
+}
+impl Unit {
+    pub fn new(name: &str, uses: Vec<String>, interface: HashSet<String>, block: Block) -> Unit {
+        Unit { name: String::from(name), uses, interface, block }
+    }
+}
+
+/// The names of a set in order, so that the same tree is always written the same
+fn sorted<S: Serializer>(names: &HashSet<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut names: Vec<&String> = names.iter().collect();
+    names.sort();
+    names.serialize(serializer)
+}
@@ -1 +0,0 @@
-mod ast;
\ No newline at end of file
@@ -0,0 +1,342 @@
+use std::ops::{Range, RangeInclusive};
+
+use crate::error::RuntimeError;
+use crate::types::Type;
+use crate::value::Value;
+
+/// The procedures every program can call without declaring them, along with
+/// the positions of their arguments which are variables passed by reference;
+/// the interpreter runs them itself as they work on variables rather than values
+const STANDARD_PROCEDURES: [(&str, Range<usize>); 23] = [
+    ("NEW", 0..1),
+    ("DISPOSE", 0..1),
+    ("READ", 0..usize::MAX),
+    ("READLN", 0..usize::MAX),
+    ("WRITE", 0..0),
+    ("WRITELN", 0..0),
+    ("INC", 0..1),
+    ("DEC", 0..1),
+    ("HALT", 0..0),
+    ("SETLENGTH", 0..1),
+    ("ASSERT", 0..0),
+    ("INSERT", 1..2),
+    ("DELETE", 0..1),
+    ("STR", 1..2),
+    ("VAL", 1..3),
+    ("RANDOMIZE", 0..0),
+    ("ASSIGN", 0..1),
+    ("RESET", 0..0),
+    ("REWRITE", 0..0),
+    ("CLOSE", 0..0),
+    ("SEEK", 0..0),
+    ("GETDATE", 0..4),
+    ("GETTIME", 0..4),
+];
+
+fn standard_procedure(name: &str) -> Option<&'static (&'static str, Range<usize>)> {
+    STANDARD_PROCEDURES.iter().find(|(procedure, _)| procedure.eq_ignore_ascii_case(name))
+}
+pub fn is_standard_procedure(name: &str) -> bool {
+    standard_procedure(name).is_some()
+}
+/// The positions of the arguments of a standard procedure which must be variables
+pub fn variable_params(name: &str) -> Range<usize> {
+    standard_procedure(name).map_or(0..0, |(_, positions)| positions.clone())
+}
+
+/// The functions every program can call without declaring them which the
+/// interpreter runs itself, as they depend on its state or on the types of
+/// their arguments rather than on the values alone, along with the numbers
+/// of arguments they take
+const STANDARD_FUNCTIONS: [(&str, RangeInclusive<usize>); 11] = [
+    ("RANDOM", 0..=1),
+    ("EOF", 0..=1),
+    ("FILEPOS", 1..=1),
+    ("FILESIZE", 1..=1),
+    ("PARAMCOUNT", 0..=0),
+    ("PARAMSTR", 1..=1),
+    ("GETENV", 1..=1),
+    ("NOW", 0..=0),
+    ("LOW", 1..=1),
+    ("HIGH", 1..=1),
+    ("SIZEOF", 1..=1),
+];
+
+/// The numbers of arguments a standard function takes, if there is one of that name
+pub fn standard_function(name: &str) -> Option<RangeInclusive<usize>> {
+    STANDARD_FUNCTIONS
+        .iter()
+        .find(|(function, _)| function.eq_ignore_ascii_case(name))
+        .map(|(_, param_counts)| param_counts.clone())
+}
+
+/// The type of what a built-in or standard function gives for arguments of
+/// the given types, or `None` when it depends on an argument whose type is
+/// not known, and for LOW and HIGH, which depend on the type they are given
+pub fn result_type(name: &str, args: &[Option<Type>]) -> Option<Type> {
+    match name.to_uppercase().as_str() {
+        "SQRT" | "SIN" | "COS" | "EXP" | "LN" | "INT" | "FRAC" | "NOW" => Some(Type::Real),
+        "TRUNC" | "ROUND" | "ORD" | "LENGTH" | "POS" | "FILEPOS" | "FILESIZE" | "PARAMCOUNT"
+        | "SIZEOF" => Some(Type::Integer),
+        // RANDOM gives a real below 1, or an integer below its argument
+        "RANDOM" if args.is_empty() => Some(Type::Real),
+        "RANDOM" => Some(Type::Integer),
+        "ABS" | "SQR" | "SUCC" | "PRED" => args.first()?.as_ref().map(Type::host_type),
+        "CHR" => Some(Type::Char),
+        // UPCASE gives a character for a character and a string for a string
+        "UPCASE" => match args.first()?.as_ref()?.host_type() {
+            Type::Char => Some(Type::Char),
+            _ => Some(Type::String),
+        },
+        "COPY" | "CONCAT" | "PARAMSTR" | "GETENV" => Some(Type::String),
+        "EOF" => Some(Type::Boolean),
+        _ => None,
+    }
+}
+
+/// Whether a standard function takes a type, or a value standing for its
+/// type, which a constant may give it
+pub fn is_type_function(name: &str) -> bool {
+    ["LOW", "HIGH", "SIZEOF"].iter().any(|function| function.eq_ignore_ascii_case(name))
+}
+/// `LOW(t)`, `HIGH(t)` or `SIZEOF(t)` of an ordinal or array type, given
+/// the value of the argument for a dynamic array, whose bounds are set by its
+/// length
+pub fn type_function(
+    name: &str,
+    type_node: &Type,
+    value: Option<&Value>,
+) -> Result<Value, RuntimeError> {
+    let name = name.to_uppercase();
+    if name == "SIZEOF" {
+        return Ok(Value::Integer(type_node.size() as i32));
+    }
+    let (low, high) = match type_node {
+        Type::Array { index, .. } => index.bounds(),
+        Type::DynamicArray(_) => match value {
+            Some(Value::Array { elements, .. }) => {
+                (Value::Integer(0), Value::Integer(elements.len() as i32 - 1))
+            }
+            _ => {
+                return Err(RuntimeError::new(format!(
+                    "{} of a dynamic array type depends on the array",
+                    name
+                )));
+            }
+        },
+        type_node if type_node.is_ordinal() => type_node.bounds(),
+        _ => {
+            return Err(RuntimeError::new(format!(
+                "{} expects an ordinal or array type",
+                name
+            )));
+        }
+    };
+    Ok(if name == "LOW" { low } else { high })
+}
+
+/// A function every program can call without declaring it
+pub struct BuiltinFunction {
+    pub name: &'static str,
+    /// the number of arguments, `usize::MAX` for any number but none
+    pub param_count: usize,
+    pub apply: fn(&[Value]) -> Result<Value, RuntimeError>,
+}
+
+const BUILTIN_FUNCTIONS: [BuiltinFunction; 20] = [
+    BuiltinFunction { name: "ABS", param_count: 1, apply: abs },
+    BuiltinFunction { name: "SQR", param_count: 1, apply: sqr },
+    BuiltinFunction { name: "SQRT", param_count: 1, apply: sqrt },
+    BuiltinFunction { name: "SIN", param_count: 1, apply: |args| real(args, f64::sin) },
+    BuiltinFunction { name: "COS", param_count: 1, apply: |args| real(args, f64::cos) },
+    BuiltinFunction { name: "EXP", param_count: 1, apply: |args| real(args, f64::exp) },
+    BuiltinFunction { name: "LN", param_count: 1, apply: ln },
+    BuiltinFunction { name: "INT", param_count: 1, apply: |args| real(args, f64::trunc) },
+    BuiltinFunction { name: "FRAC", param_count: 1, apply: |args| real(args, f64::fract) },
+    BuiltinFunction { name: "TRUNC", param_count: 1, apply: trunc },
+    BuiltinFunction { name: "ROUND", param_count: 1, apply: round },
+    BuiltinFunction { name: "ORD", param_count: 1, apply: ord },
+    BuiltinFunction { name: "CHR", param_count: 1, apply: chr },
+    BuiltinFunction { name: "SUCC", param_count: 1, apply: |args| step(args, 1, "SUCC") },
+    BuiltinFunction { name: "PRED", param_count: 1, apply: |args| step(args, -1, "PRED") },
+    BuiltinFunction { name: "LENGTH", param_count: 1, apply: length },
+    BuiltinFunction { name: "COPY", param_count: 3, apply: copy },
+    BuiltinFunction { name: "POS", param_count: 2, apply: pos },
+    BuiltinFunction { name: "CONCAT", param_count: usize::MAX, apply: concat },
+    BuiltinFunction { name: "UPCASE", param_count: 1, apply: upcase },
+];
+
+/// The names of the standard procedures and functions and of the built-in
+/// functions, in upper case
+pub fn routine_names() -> impl Iterator<Item = &'static str> {
+    let procedures = STANDARD_PROCEDURES.iter().map(|(name, _)| *name);
+    let functions = STANDARD_FUNCTIONS.iter().map(|(name, _)| *name);
+    procedures.chain(functions).chain(BUILTIN_FUNCTIONS.iter().map(|function| function.name))
+}
+
+/// Find a built-in function; like the standard procedures, their names are
+/// matched ignoring case and may be redeclared
+pub fn builtin_function(name: &str) -> Option<&'static BuiltinFunction> {
+    BUILTIN_FUNCTIONS.iter().find(|function| function.name.eq_ignore_ascii_case(name))
+}
+
+/// ABS and SQR keep the type of their argument
+fn abs(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(match &args[0] {
+        Value::Integer(i) => Value::Integer(i.checked_abs().ok_or(RuntimeError::IntegerOverflow)?),
+        value => Value::Real(value.as_real()?.abs()),
+    })
+}
+fn sqr(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(match &args[0] {
+        Value::Integer(i) => {
+            Value::Integer(i.checked_mul(*i).ok_or(RuntimeError::IntegerOverflow)?)
+        }
+        value => Value::Real(value.as_real()?.powi(2)),
+    })
+}
+/// The other functions always give a real
+fn real(args: &[Value], function: fn(f64) -> f64) -> Result<Value, RuntimeError> {
+    Ok(Value::Real(function(args[0].as_real()?)))
+}
+fn sqrt(args: &[Value]) -> Result<Value, RuntimeError> {
+    let x = args[0].as_real()?;
+    if x < 0.0 {
+        return Err(RuntimeError::new(format!("SQRT of negative number {}", args[0])));
+    }
+    Ok(Value::Real(x.sqrt()))
+}
+fn ln(args: &[Value]) -> Result<Value, RuntimeError> {
+    let x = args[0].as_real()?;
+    if x <= 0.0 {
+        return Err(RuntimeError::new(format!("LN of non-positive number {}", args[0])));
+    }
+    Ok(Value::Real(x.ln()))
+}
+/// TRUNC and ROUND turn a real into the integer `function` rounds it to,
+/// ROUND rounding halves away from zero as ISO Pascal asks
+fn integer(args: &[Value], function: fn(f64) -> f64, name: &str) -> Result<Value, RuntimeError> {
+    let result = function(args[0].as_real()?);
+    // NaN is in no range
+    if !(i32::MIN as f64..=i32::MAX as f64).contains(&result) {
+        return Err(RuntimeError::new(format!(
+            "{} of {} does not fit in an INTEGER",
+            name, args[0]
+        )));
+    }
+    Ok(Value::Integer(result as i32))
+}
+fn trunc(args: &[Value]) -> Result<Value, RuntimeError> {
+    integer(args, f64::trunc, "TRUNC")
+}
+fn round(args: &[Value]) -> Result<Value, RuntimeError> {
+    integer(args, f64::round, "ROUND")
+}
+fn ord(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Integer(args[0].ordinal()?))
+}
+fn chr(args: &[Value]) -> Result<Value, RuntimeError> {
+    Value::Char('\0').with_ordinal(args[0].as_integer()?)
+}
+/// The number of elements of an array or of characters of a string
+fn length(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(match &args[0] {
+        Value::Array { elements, .. } => Value::Integer(elements.len() as i32),
+        value => Value::Integer(value.as_string()?.chars().count() as i32),
+    })
+}
+/// The value `delta` positions further along the type of the argument
+fn step(args: &[Value], delta: i32, name: &str) -> Result<Value, RuntimeError> {
+    let value = &args[0];
+    match value.ordinal()?.checked_add(delta).and_then(|ordinal| value.try_with_ordinal(ordinal)) {
+        Some(value) => Ok(value),
+        None => Err(RuntimeError::new(format!("{} of {} is out of range", name, value))),
+    }
+}
+/// `COPY(s, index, count)`, the `count` characters of a string starting at
+/// the one at `index`, counted from 1, or as many of them as there are; an
+/// index before the first character stands for the first one
+fn copy(args: &[Value]) -> Result<Value, RuntimeError> {
+    let text = args[0].as_string()?;
+    let start = args[1].as_integer()?.max(1) as usize - 1;
+    let count = args[2].as_integer()?.max(0) as usize;
+    Ok(Value::String(text.chars().skip(start).take(count).collect()))
+}
+/// `POS(part, s)`, where the first occurrence of a string in another one
+/// starts, counted from 1, or 0 when there is none
+fn pos(args: &[Value]) -> Result<Value, RuntimeError> {
+    let (part, text) = (args[0].as_string()?, args[1].as_string()?);
+    Ok(match text.find(&part) {
+        Some(offset) if !part.is_empty() => {
+            Value::Integer(text[..offset].chars().count() as i32 + 1)
+        }
+        _ => Value::Integer(0),
+    })
+}
+/// The strings given one after the other
+fn concat(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::String(args.iter().map(Value::as_string).collect::<Result<_, _>>()?))
+}
+/// A character, or the characters of a string, with the letters in upper case
+fn upcase(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(match &args[0] {
+        Value::Char(c) => Value::Char(c.to_ascii_uppercase()),
+        value => Value::String(value.as_string()?.to_ascii_uppercase()),
+    })
+}
+/// The INTEGER or REAL number written by a text, as VAL reads it, or the
+/// position, counted from 1, of the first character which cannot be part of
+/// it. Blanks may lead the number but not follow it.
+pub fn parse_number(text: &str, real: bool) -> Result<Value, usize> {
+    let chars: Vec<char> = text.chars().collect();
+    let start = chars.iter().take_while(|&&ch| ch == ' ').count();
+    let mut i = start;
+    if matches!(chars.get(i), Some('+' | '-')) {
+        i += 1;
+    }
+    if !skip_digits(&chars, &mut i) {
+        return Err(i + 1);
+    }
+    if real {
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            skip_digits(&chars, &mut i);
+        }
+        if matches!(chars.get(i), Some('e' | 'E')) {
+            i += 1;
+            if matches!(chars.get(i), Some('+' | '-')) {
+                i += 1;
+            }
+            if !skip_digits(&chars, &mut i) {
+                return Err(i + 1);
+            }
+        }
+    }
+    if i < chars.len() {
+        return Err(i + 1);
+    }
+    let number: String = chars[start..].iter().collect();
+    if real {
+        return Ok(Value::Real(number.parse().unwrap()));
+    }
+    // a number too large for an INTEGER fails at the digit making it so
+    let sign = if chars[start] == '-' { -1 } else { 1 };
+    let mut value: i64 = 0;
+    for (position, ch) in chars.iter().enumerate().skip(start) {
+        if let Some(digit) = ch.to_digit(10) {
+            value = value * 10 + sign * digit as i64;
+            if i32::try_from(value).is_err() {
+                return Err(position + 1);
+            }
+        }
+    }
+    Ok(Value::Integer(value as i32))
+}
+/// Move past the digits starting at `i`, telling whether there were any
+fn skip_digits(chars: &[char], i: &mut usize) -> bool {
+    let start = *i;
+    while chars.get(*i).is_some_and(|ch| ch.is_ascii_digit()) {
+        *i += 1;
+    }
+    *i > start
+}
@@ -0,0 +1,234 @@
+use crate::ast::Routine;
+use crate::error::RuntimeError;
+use crate::names::{Names, same_name};
+use crate::symbol::RESULT;
+use crate::types::Type;
+use crate::value::Value;
+
+/// A routine along with the index of the frame declaring it
+type FramedRoutine = (Routine, usize);
+
+/// The frame of one running program, unit, procedure or function
+#[derive(Clone)]
+pub struct ActivationRecord {
+    pub name: String,
+    /// 1 for the program and the units, one more for every routine we are nested in
+    pub nesting_level: usize,
+    /// index in the call stack of the frame of the lexically enclosing routine,
+    /// which may have returned when this one was called through a routine value
+    pub access_link: Option<usize>,
+    pub members: Names<Value>,
+    /// the declared types of the members, with named types resolved
+    pub member_types: Names<Type>,
+    /// the heap cells of the typed constants declared in this frame's block,
+    /// which outlive the frame so that the next call sees their values
+    pub statics: Names<usize>,
+    /// the constants declared in this frame's block
+    pub constants: Names<Value>,
+    /// the types declared in this frame's block, resolved when declared
+    pub types: Names<Type>,
+    /// the routines declared in this frame's block
+    pub routines: Names<Routine>,
+    /// the result of a function call, set by assigning to the function's name
+    pub return_value: Option<Value>,
+    pub return_type: Option<Type>,
+    /// for the frame of a unit, the names declared by its interface, which
+    /// are all the program and the other units see of it
+    pub interface: Option<Names<()>>,
+    /// the units the program or unit of this frame uses
+    pub uses: Vec<String>,
+    /// whether a routine value refers to this frame, which then outlives its call
+    captured: bool,
+}
+
+impl ActivationRecord {
+    pub fn new(name: &str, nesting_level: usize, access_link: Option<usize>) -> ActivationRecord {
+        ActivationRecord {
+            name: String::from(name),
+            nesting_level,
+            access_link,
+            members: Names::new(),
+            member_types: Names::new(),
+            statics: Names::new(),
+            constants: Names::new(),
+            types: Names::new(),
+            routines: Names::new(),
+            return_value: None,
+            return_type: None,
+            interface: None,
+            uses: Vec::new(),
+            captured: false,
+        }
+    }
+}
+
+/// The frames of the running routines, along with those of the routines
+/// which returned while a routine nested in them was captured as a value.
+///
+/// A captured routine keeps reading and writing the locals of the routines
+/// it is nested in, so their frames have to stay where its value refers to.
+#[derive(Default)]
+pub struct CallStack {
+    /// the frames by index, `None` for a slot free to reuse
+    records: Vec<Option<ActivationRecord>>,
+    /// the indices of the frames of the running routines, the innermost last
+    running: Vec<usize>,
+    /// the indices of the free slots of `records`
+    free: Vec<usize>,
+}
+
+impl CallStack {
+    pub fn new() -> CallStack {
+        CallStack { records: Vec::new(), running: Vec::new(), free: Vec::new() }
+    }
+    /// Run a frame, returning its index
+    pub fn push(&mut self, record: ActivationRecord) -> usize {
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.records[index] = Some(record);
+                index
+            }
+            None => {
+                self.records.push(Some(record));
+                self.records.len() - 1
+            }
+        };
+        self.running.push(index);
+        index
+    }
+    /// Leave the running frame, which is dropped unless a routine value refers to it
+    pub fn pop(&mut self) -> ActivationRecord {
+        let index = self.running.pop().unwrap();
+        if self.get(index).captured {
+            return self.get(index).clone();
+        }
+        self.free.push(index);
+        self.records[index].take().unwrap()
+    }
+    pub fn peek(&mut self) -> &mut ActivationRecord {
+        let index = *self.running.last().unwrap();
+        self.records[index].as_mut().unwrap()
+    }
+    pub fn get(&self, index: usize) -> &ActivationRecord {
+        self.records[index].as_ref().unwrap()
+    }
+    /// The number of running routines, the program included
+    pub fn depth(&self) -> usize {
+        self.running.len()
+    }
+    /// Keep a frame, and the frames of the routines it is nested in, once
+    /// their routines return, as a routine value refers to it
+    pub fn capture(&mut self, index: usize) {
+        let mut frame = Some(index);
+        while let Some(i) = frame {
+            let record = self.records[i].as_mut().unwrap();
+            record.captured = true;
+            frame = record.access_link;
+        }
+    }
+
+    /// The indices of the frames where the running routine looks `name`
+    /// up, innermost first.
+    ///
+    /// These are the frames of the routine itself and of the routines it is
+    /// lexically nested in, not those of its callers, then those of the units
+    /// loaded before the program or unit of the routine. A unit shows only
+    /// its interface, and only to the programs and units using it.
+    pub fn visible_frames(&self, name: &str) -> Vec<usize> {
+        let mut frames = Vec::new();
+        let mut index = self.running.last().copied();
+        // the program or unit of the running routine, once we get to its frame
+        let mut module: Option<&ActivationRecord> = None;
+        // an overloaded routine is known by its name followed by its number
+        let declared = name.split('#').next().unwrap();
+        while let Some(i) = index {
+            let record = self.get(i);
+            let shown = match (module, &record.interface) {
+                (Some(module), Some(interface)) => {
+                    module.uses.iter().any(|unit| same_name(unit, &record.name))
+                        && interface.contains_key(declared)
+                }
+                _ => true,
+            };
+            if shown {
+                frames.push(i);
+            }
+            if record.nesting_level == 1 && module.is_none() {
+                module = Some(record);
+            }
+            index = record.access_link;
+        }
+        frames
+    }
+
+    /// The heap cell of a visible typed constant, unless a name declared in
+    /// a nearer frame hides it
+    pub fn lookup_static(&self, name: &str) -> Option<usize> {
+        for i in self.visible_frames(name) {
+            let record = self.get(i);
+            if let Some(address) = record.statics.get(name) {
+                return Some(*address);
+            }
+            if record.members.contains_key(name)
+                || record.constants.contains_key(name)
+                || record.routines.contains_key(name)
+            {
+                return None;
+            }
+        }
+        None
+    }
+    /// The storage and declared type of a visible variable, or those of the
+    /// result of a running function when `name` is the function's own name,
+    /// or is Result in the dialect where `result_variable` says functions have one
+    pub fn variable_mut(&mut self, name: &str, result_variable: bool) -> (&mut Value, &Type) {
+        let is_result = result_variable && same_name(name, RESULT);
+        let found = self.visible_frames(name).into_iter().find(|&i| {
+            let record = self.get(i);
+            record.members.contains_key(name)
+                || ((same_name(&record.name, name) || is_result) && record.return_value.is_some())
+        });
+        let Some(i) = found else {
+            panic!("undeclared variable {}", name)
+        };
+        let record = self.records[i].as_mut().unwrap();
+        match record.members.get_mut(name) {
+            Some(value) => (value, &record.member_types[name]),
+            None => (record.return_value.as_mut().unwrap(), record.return_type.as_ref().unwrap()),
+        }
+    }
+    /// Find a visible type declared in a TYPE section
+    pub fn lookup_type(&self, name: &str) -> Option<&Type> {
+        self.visible_frames(name)
+            .into_iter()
+            .find_map(|i| self.get(i).types.get(name))
+    }
+    /// The routine a visible name stands for, being either the name of a
+    /// routine or of a procedural parameter holding one
+    pub fn routine_value(&self, name: &str) -> Option<Value> {
+        self.visible_frames(name).into_iter().find_map(|i| {
+            let record = self.get(i);
+            match record.members.get(name) {
+                Some(value @ Value::Routine(_)) => Some(value.clone()),
+                _ if record.routines.contains_key(name) => {
+                    Some(Value::Routine(Some((String::from(name), i))))
+                }
+                _ => None,
+            }
+        })
+    }
+    /// Find a visible routine along with the index of the frame declaring it,
+    /// failing for a procedural variable which has not been given one
+    pub fn lookup_routine(
+        &self,
+        name: &str,
+    ) -> Result<Option<FramedRoutine>, RuntimeError> {
+        match self.routine_value(name) {
+            None => Ok(None),
+            Some(Value::Routine(Some((routine, i)))) => {
+                Ok(Some((self.get(i).routines[&routine].clone(), i)))
+            }
+            Some(_) => Err(RuntimeError::new(format!("{} holds no routine", name))),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Where GETDATE, GETTIME, NOW and RANDOMIZE take the time from, which an
+/// embedder can replace to have every run see the same time
+pub trait Clock {
+    /// The time elapsed since the start of 1970, in UTC
+    fn now(&self) -> Duration;
+}
+
+/// The time of day of the machine the interpreter runs on
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+    }
+}
+
+/// A clock stopped at a given time
+pub struct FixedClock(pub Duration);
+
+impl Clock for FixedClock {
+    fn now(&self) -> Duration {
+        self.0
+    }
+}
+
+/// A point in time split up the way GETDATE and GETTIME return it
+pub struct DateTime {
+    pub year: i32,
+    pub month: i32,
+    pub day: i32,
+    /// counting from 0 for Sunday
+    pub day_of_week: i32,
+    pub hour: i32,
+    pub minute: i32,
+    pub second: i32,
+    pub hundredths: i32,
+}
+
+/// The number of days from the day Delphi counts its dates from, the 30th of
+/// December 1899, to the start of 1970
+const DELPHI_EPOCH_OFFSET: f64 = 25569.0;
+
+impl DateTime {
+    pub fn new(time: Duration) -> DateTime {
+        let days = (time.as_secs() / 86400) as i64;
+        let seconds = (time.as_secs() % 86400) as i32;
+        // the civil date of a day number, counting years from March so that
+        // the leap day comes last
+        let z = days + 719468;
+        let era = z / 146097;
+        let day_of_era = z - era * 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+            - day_of_era / 146096)
+            / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        DateTime {
+            year: year as i32,
+            month: month as i32,
+            day: day as i32,
+            // the 1st of January 1970 was a Thursday
+            day_of_week: ((days + 4) % 7) as i32,
+            hour: seconds / 3600,
+            minute: seconds / 60 % 60,
+            second: seconds % 60,
+            hundredths: (time.subsec_millis() / 10) as i32,
+        }
+    }
+}
+
+/// The time as NOW returns it, the number of days since the 30th of December
+/// 1899 with the time of day as the fraction
+pub fn delphi_time(time: Duration) -> f64 {
+    time.as_secs_f64() / 86400.0 + DELPHI_EPOCH_OFFSET
+}
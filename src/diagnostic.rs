@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::token::{Position, Span};
+
+/// The text of a program and of the files it includes, which the errors
+/// found in them are shown along with
+#[derive(Clone, Debug, Default)]
+pub struct Sources {
+    program: String,
+    /// the included files, the units used and the program itself when it
+    /// was read from a file, by the names their positions tell
+    files: HashMap<Rc<str>, String>,
+}
+
+impl Sources {
+    pub fn new(program: &str) -> Sources {
+        Sources { program: String::from(program), files: HashMap::new() }
+    }
+    pub fn add_file(&mut self, name: Rc<str>, text: &str) {
+        self.files.insert(name, String::from(text));
+    }
+    /// The text of the file a position lies in, `None` for a file never read
+    fn text(&self, file: Option<&Rc<str>>) -> Option<&str> {
+        match file {
+            Some(file) => self.files.get(file).map(String::as_str),
+            None => Some(&self.program),
+        }
+    }
+    /// The source from where `from` starts to where `to` starts, `None` when
+    /// they start in different files
+    pub fn between(&self, from: &Span, to: &Span) -> Option<&str> {
+        if from.start.file != to.start.file {
+            return None;
+        }
+        self.text(from.start.file.as_ref())?.get(from.bytes.start..to.bytes.start)
+    }
+    /// The message of an error followed by the line of source it was found
+    /// in, the part of the line it is about being underlined, like
+    ///
+    /// ```text
+    /// semantic error: undeclared identifier y at line 2, column 8
+    ///   |
+    /// 2 |   x := y + 1
+    ///   |        ^
+    /// ```
+    ///
+    /// Each syntax error is shown with a line of its own.
+    pub fn render(&self, error: &Error) -> String {
+        if let Error::Parse(errors) = error {
+            let rendered = errors.iter().map(|error| {
+                let text = format!("syntax error: {}", error);
+                with_snippet(text, self.snippet(error.position(), None))
+            });
+            return rendered.collect::<Vec<_>>().join("\n");
+        }
+        let snippet = match error {
+            Error::Parse(_) => None,
+            Error::Semantic(error) => {
+                error.span.as_ref().and_then(|span| self.snippet(&span.start, Some(span.bytes.end)))
+            }
+            Error::Runtime { error, span } => match (error.position(), span) {
+                // the range errors and ASSERT tell the statement they failed in
+                (Some(position), _) => self.snippet(position, None),
+                (None, Some(span)) => self.snippet(&span.start, Some(span.bytes.end)),
+                (None, None) => None,
+            },
+        };
+        with_snippet(error.to_string(), snippet)
+    }
+    /// The line a position lies in with a caret under the character at the
+    /// position, or carets up to the byte `end` when the line goes on that far
+    fn snippet(&self, position: &Position, end: Option<usize>) -> Option<String> {
+        let text = self.text(position.file.as_ref())?;
+        let line_start = match position.line {
+            1 => 0,
+            line => text.match_indices('\n').nth(line - 2)?.0 + 1,
+        };
+        let line = text[line_start..].split('\n').next()?.trim_end_matches('\r');
+        let prefix: String = line.chars().take(position.column - 1).collect();
+        let start = line_start + prefix.len();
+        let underlined = match end {
+            Some(end) if end > start => {
+                text[start..end.min(line_start + line.len())].chars().count()
+            }
+            _ => 0,
+        };
+        // a tab in front of the carets keeps them under the characters they mark
+        let indent: String =
+            prefix.chars().map(|ch| if ch == '\t' { '\t' } else { ' ' }).collect();
+        let number = position.line.to_string();
+        let gutter = " ".repeat(number.len());
+        Some(format!(
+            "{} |\n{} | {}\n{} | {}{}",
+            gutter, number, line, gutter, indent, "^".repeat(underlined.max(1))
+        ))
+    }
+}
+
+fn with_snippet(mut text: String, snippet: Option<String>) -> String {
+    if let Some(snippet) = snippet {
+        text.push('\n');
+        text.push_str(&snippet);
+    }
+    text
+}
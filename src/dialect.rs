@@ -0,0 +1,129 @@
+use std::fmt;
+
+use crate::token::OpType;
+
+/// The flavour of Pascal a program is written in, deciding which extensions
+/// to ISO 7185 Pascal it may use. Each dialect accepts what the ones before
+/// it do.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Dialect {
+    /// ISO 7185 Pascal, with none of the extensions, where MOD never gives a
+    /// negative result
+    Iso,
+    /// Turbo Pascal, adding strings, BREAK, CONTINUE and EXIT, typed constants,
+    /// `$` hexadecimal literals, units, positioning typed files, CONST
+    /// parameters and procedural types, and making MOD the remainder of DIV,
+    /// which has the sign of the dividend
+    #[default]
+    Turbo,
+    /// Delphi, adding overloaded routines, writing enumerated values as
+    /// their names, the Result variable of functions, exceptions and
+    /// dynamic arrays
+    Delphi,
+}
+
+/// An extension to ISO 7185 Pascal, which only some dialects accept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    StringType,
+    LoopControl,
+    Exit,
+    TypedConstants,
+    RadixLiterals,
+    Units,
+    Overloading,
+    FilePositioning,
+    EnumNames,
+    ResultVariable,
+    Exceptions,
+    ConstParameters,
+    ProceduralTypes,
+    DynamicArrays,
+}
+
+impl Feature {
+    /// The first dialect accepting the feature
+    pub fn dialect(self) -> Dialect {
+        match self {
+            Feature::Overloading
+            | Feature::EnumNames
+            | Feature::ResultVariable
+            | Feature::Exceptions
+            | Feature::DynamicArrays => Dialect::Delphi,
+            _ => Dialect::Turbo,
+        }
+    }
+    /// The extension a keyword belongs to, which the dialects lacking it
+    /// read as an identifier instead
+    pub fn of_keyword(op_type: OpType) -> Option<Feature> {
+        match op_type {
+            OpType::BREAK | OpType::CONTINUE => Some(Feature::LoopControl),
+            OpType::EXIT => Some(Feature::Exit),
+            OpType::TRY | OpType::EXCEPT | OpType::FINALLY | OpType::RAISE => {
+                Some(Feature::Exceptions)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Feature::StringType => "the STRING type",
+            Feature::LoopControl => "a BREAK or CONTINUE statement",
+            Feature::Exit => "an EXIT statement",
+            Feature::TypedConstants => "a typed constant",
+            Feature::RadixLiterals => "a $ hexadecimal or % binary literal",
+            Feature::Units => "a USES clause",
+            Feature::Overloading => "overloading a routine",
+            Feature::FilePositioning => "SEEK, FILEPOS or FILESIZE",
+            Feature::EnumNames => "writing an enumerated value",
+            Feature::ResultVariable => "the Result variable of a function",
+            Feature::Exceptions => "a TRY or RAISE statement",
+            Feature::ConstParameters => "a CONST parameter",
+            Feature::ProceduralTypes => "a procedural type",
+            Feature::DynamicArrays => "a dynamic array",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl Dialect {
+    /// The dialect called `name` by `--dialect`, ignoring case
+    pub fn from_name(name: &str) -> Option<Dialect> {
+        [Dialect::Iso, Dialect::Turbo, Dialect::Delphi]
+            .into_iter()
+            .find(|dialect| dialect.name().eq_ignore_ascii_case(name))
+    }
+    /// The name `--dialect` calls the dialect by
+    pub fn name(self) -> &'static str {
+        match self {
+            Dialect::Iso => "iso",
+            Dialect::Turbo => "turbo",
+            Dialect::Delphi => "delphi",
+        }
+    }
+    pub fn allows(self, feature: Feature) -> bool {
+        self >= feature.dialect()
+    }
+    /// The end of the message rejecting a feature, telling which dialect accepts it
+    pub fn rejection(self, feature: Feature) -> String {
+        let accepting = feature.dialect();
+        format!(
+            "is not part of {}, but {} accepts it with --dialect={}",
+            self, accepting, accepting.name()
+        )
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Dialect::Iso => "ISO 7185 Pascal",
+            Dialect::Turbo => "Turbo Pascal",
+            Dialect::Delphi => "Delphi",
+        };
+        write!(f, "{}", text)
+    }
+}
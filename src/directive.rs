@@ -0,0 +1,162 @@
+use std::collections::HashSet;
+
+use crate::token::Position;
+
+/// A directive to the compiler, written as a comment starting with `{$`
+#[derive(Debug, PartialEq)]
+pub enum Directive {
+    /// `{$R+}` or `{$RANGECHECKS ON}`, turning switches on or off, several
+    /// of them at once for `{$R+,B-}`
+    Switches(Vec<(char, bool)>),
+    /// a directive taking an argument, like `{$I utils.inc}`
+    Parameter { name: String, argument: String },
+}
+
+/// The long names of the switches, as in `{$RANGECHECKS ON}`
+const SWITCH_NAMES: [(&str, char); 3] =
+    [("ASSERTIONS", 'C'), ("BOOLEVAL", 'B'), ("RANGECHECKS", 'R')];
+
+impl Directive {
+    /// Read the text between `{$` and `}`; the names of directives and
+    /// switches are matched ignoring case, like keywords
+    pub fn parse(text: &str) -> Result<Directive, String> {
+        let text = text.trim();
+        let (name, argument) = match text.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (text, ""),
+        };
+        let long_switch = SWITCH_NAMES.iter().find(|(long, _)| long.eq_ignore_ascii_case(name));
+        if let Some((_, switch)) = long_switch {
+            let on = match argument.to_uppercase().as_str() {
+                "ON" => true,
+                "OFF" => false,
+                _ => {
+                    return Err(format!(
+                        "directive {} expects ON or OFF but got {}",
+                        name, argument
+                    ));
+                }
+            };
+            return Ok(Directive::Switches(vec![(*switch, on)]));
+        }
+        if argument.is_empty()
+            && let Some(switches) = name.split(',').map(switch).collect()
+        {
+            return Ok(Directive::Switches(switches));
+        }
+        Ok(Directive::Parameter { name: name.to_uppercase(), argument: String::from(argument) })
+    }
+}
+
+/// `R+` or `R-`, a letter naming a switch followed by its new state
+fn switch(text: &str) -> Option<(char, bool)> {
+    let mut chars = text.trim().chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(switch), Some(state @ ('+' | '-')), None) if switch.is_ascii_alphabetic() => {
+            Some((switch.to_ascii_uppercase(), state == '+'))
+        }
+        _ => None,
+    }
+}
+
+/// A region of conditional compilation opened by `{$IFDEF}` or `{$IFNDEF}`
+struct Condition {
+    /// whether the source of the branch we are in is skipped
+    skipping: bool,
+    /// whether the region lies inside a skipped one, skipping both its branches
+    inside_skipped: bool,
+    /// whether the `{$ELSE}` of the region has been met
+    in_else: bool,
+}
+
+/// What the directives the lexer has met so far ask for
+#[derive(Default)]
+pub struct Directives {
+    /// the switches set, by the offset of their directives in the token
+    /// stream, each staying in effect up to the next directive setting it
+    switches: Vec<(usize, char, bool)>,
+    /// the symbols defined for conditional compilation, in upper case
+    defines: HashSet<String>,
+    /// the regions of conditional compilation we are inside, innermost last
+    conditions: Vec<Condition>,
+}
+
+impl Directives {
+    pub fn new() -> Directives {
+        Directives { switches: Vec::new(), defines: HashSet::new(), conditions: Vec::new() }
+    }
+    /// Define a symbol for `{$IFDEF}`, whose name is matched ignoring case
+    pub fn define(&mut self, symbol: &str) {
+        self.defines.insert(symbol.to_uppercase());
+    }
+    /// Whether the source met now lies in a region turned off by
+    /// `{$IFDEF}` or its kin, which the lexer skips
+    pub fn skipping(&self) -> bool {
+        self.conditions.last().is_some_and(|condition| condition.skipping)
+    }
+    /// Take in a directive met at the given position, or tell why it is
+    /// out of place
+    pub fn handle(&mut self, directive: Directive, position: &Position) -> Result<(), String> {
+        let skipping = self.skipping();
+        match directive {
+            // a skipped region only has its nested regions tracked
+            Directive::Switches(_) if skipping => {}
+            Directive::Switches(switches) => {
+                for (switch, on) in switches {
+                    self.switches.push((position.offset, switch, on));
+                }
+            }
+            Directive::Parameter { name, argument } => match name.as_str() {
+                "IFDEF" | "IFNDEF" => {
+                    let defined = self.defines.contains(&argument.to_uppercase());
+                    self.conditions.push(Condition {
+                        skipping: skipping || defined != (name == "IFDEF"),
+                        inside_skipped: skipping,
+                        in_else: false,
+                    });
+                }
+                "ELSE" => match self.conditions.last_mut() {
+                    Some(condition) if !condition.in_else => {
+                        condition.in_else = true;
+                        condition.skipping = condition.inside_skipped || !condition.skipping;
+                    }
+                    Some(_) => return Err(String::from("second {$ELSE} in a {$IFDEF}")),
+                    None => return Err(String::from("{$ELSE} without {$IFDEF}")),
+                },
+                "ENDIF" if self.conditions.is_empty() => {
+                    return Err(String::from("{$ENDIF} without {$IFDEF}"));
+                }
+                "ENDIF" => {
+                    self.conditions.pop();
+                }
+                _ if skipping => {}
+                "DEFINE" => self.define(&argument),
+                "UNDEF" => {
+                    self.defines.remove(&argument.to_uppercase());
+                }
+                // the directives of other compilers are skipped, as they skip ours
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+    /// Check that every region of conditional compilation has been closed
+    /// once the end of the source is reached, the regions left open being
+    /// reported only once
+    pub fn finish(&mut self) -> Result<(), String> {
+        if !self.conditions.is_empty() {
+            self.conditions.clear();
+            return Err(String::from("missing {$ENDIF}"));
+        }
+        Ok(())
+    }
+    /// Whether the last directive for a switch before a position turned it
+    /// on, or `None` when no directive before it sets the switch
+    pub fn switch_at(&self, switch: char, position: &Position) -> Option<bool> {
+        self.switches
+            .iter()
+            .rev()
+            .find(|(at, name, _)| *name == switch && *at <= position.offset)
+            .map(|(_, _, on)| *on)
+    }
+}
@@ -0,0 +1,23 @@
+use std::collections::HashMap;
+
+/// The environment variables GETENV looks up, which an embedder can replace
+/// to hide the ones of the process or to make up others
+pub trait Environment {
+    fn get(&self, name: &str) -> Option<String>;
+}
+
+/// The environment variables of the process running the interpreter
+pub struct ProcessEnvironment;
+
+impl Environment for ProcessEnvironment {
+    fn get(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+}
+
+/// A fixed set of variables, none at all when empty
+impl Environment for HashMap<String, String> {
+    fn get(&self, name: &str) -> Option<String> {
+        HashMap::get(self, name).cloned()
+    }
+}
@@ -0,0 +1,275 @@
+use std::fmt;
+
+use crate::dialect::{Dialect, Feature};
+use crate::token::{OpType, Position, Span};
+use crate::types::RangeError;
+
+/// Source text the lexer cannot make a token of
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexError {
+    UnknownCharacter { ch: char, position: Position },
+    UnterminatedComment(Position),
+    UnterminatedString(Position),
+    /// a `$` or `%` not followed by digits
+    MissingDigits(Position),
+    /// a number too large for its type
+    LiteralOutOfRange { literal: String, position: Position },
+    /// a malformed or misplaced directive
+    Directive { message: String, position: Position },
+    /// a file named by `{$I}` or by a USES clause which cannot be read
+    Include { message: String, position: Position },
+    /// an extension the dialect of the program lacks
+    Unsupported { feature: Feature, dialect: Dialect, position: Position },
+}
+
+impl LexError {
+    pub fn position(&self) -> &Position {
+        match self {
+            LexError::UnterminatedComment(position)
+            | LexError::UnterminatedString(position)
+            | LexError::MissingDigits(position)
+            | LexError::UnknownCharacter { position, .. }
+            | LexError::LiteralOutOfRange { position, .. }
+            | LexError::Directive { position, .. }
+            | LexError::Include { position, .. }
+            | LexError::Unsupported { position, .. } => position,
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexError::UnknownCharacter { ch, position } => {
+                write!(f, "unknown character {:?} at {}", ch, position)
+            }
+            LexError::UnterminatedComment(position) => {
+                write!(f, "unterminated comment starting at {}", position)
+            }
+            LexError::UnterminatedString(position) => {
+                write!(f, "unterminated string literal starting at {}", position)
+            }
+            LexError::MissingDigits(position) => {
+                write!(f, "expected digits in the integer literal at {}", position)
+            }
+            LexError::LiteralOutOfRange { literal, position } => {
+                write!(f, "literal {} at {} is out of range", literal, position)
+            }
+            LexError::Directive { message, position } | LexError::Include { message, position } => {
+                write!(f, "{} at {}", message, position)
+            }
+            LexError::Unsupported { feature, dialect, position } => {
+                write!(f, "{} at {} {}", feature, position, dialect.rejection(*feature))
+            }
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// A token stream which is not a program
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    Lex(LexError),
+    UnexpectedToken { expected: OpType, found: String, position: Position },
+    /// an extension the dialect of the program lacks
+    Unsupported { feature: Feature, dialect: Dialect, position: Position },
+    /// a construct which is well formed but not allowed where it stands, like
+    /// a variable in a constant or BREAK outside of a loop
+    Invalid { message: String, position: Position },
+}
+
+impl ParseError {
+    pub fn position(&self) -> &Position {
+        match self {
+            ParseError::Lex(error) => error.position(),
+            ParseError::UnexpectedToken { position, .. }
+            | ParseError::Unsupported { position, .. }
+            | ParseError::Invalid { position, .. } => position,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Lex(error) => write!(f, "{}", error),
+            ParseError::UnexpectedToken { expected, found, position } if found.is_empty() => {
+                write!(f, "expected {:?} at {} but the source ends", expected, position)
+            }
+            ParseError::UnexpectedToken { expected, found, position } => {
+                write!(f, "expected {:?} at {} but found {}", expected, position, found)
+            }
+            ParseError::Unsupported { feature, dialect, position } => {
+                write!(f, "{} at {} {}", feature, position, dialect.rejection(*feature))
+            }
+            ParseError::Invalid { message, position } => write!(f, "{} at {}", message, position),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<LexError> for ParseError {
+    fn from(error: LexError) -> ParseError {
+        ParseError::Lex(error)
+    }
+}
+
+/// A program breaking a rule the semantic analyzer checks, like using an
+/// undeclared name
+#[derive(Clone, Debug, PartialEq)]
+pub struct SemanticError {
+    pub message: String,
+    /// the source of the innermost node the error was found in
+    pub span: Option<Span>,
+}
+
+impl SemanticError {
+    pub fn new(message: impl Into<String>) -> SemanticError {
+        SemanticError { message: message.into(), span: None }
+    }
+    /// The error found in a node, unless a node inside it already told where
+    pub fn at(mut self, span: &Span) -> SemanticError {
+        if self.span.is_none() {
+            self.span = Some(span.clone());
+        }
+        self
+    }
+}
+
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(span) = &self.span {
+            write!(f, " at {}", span.start)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SemanticError {}
+
+/// An error stopping a running program, unless a TRY handles it
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuntimeError {
+    DivisionByZero,
+    /// an integer operation whose result does not fit in an INTEGER
+    IntegerOverflow,
+    /// a value outside of its subrange or an index outside of the bounds of
+    /// its array, found by the range checks
+    Range(Box<RangeError>),
+    /// a value of another type than an operation expects, like `an INTEGER
+    /// value`, which the semantic analyzer does not always tell
+    TypeMismatch { expected: &'static str, found: &'static str },
+    /// READ or READLN past the end of what they read
+    EndOfInput,
+    /// a file which cannot be opened, read or written, `message` telling
+    /// what went wrong, like `cannot be opened: ...`
+    File { name: String, message: String },
+    AssertionFailed { position: Position, message: Option<String> },
+    /// an exception raised by RAISE, which unwinds to the TRY handling it
+    Exception(String),
+    Other(String),
+}
+
+impl RuntimeError {
+    pub fn new(message: impl Into<String>) -> RuntimeError {
+        RuntimeError::Other(message.into())
+    }
+    /// Where the error happened, for the errors telling it themselves
+    pub fn position(&self) -> Option<&Position> {
+        match self {
+            RuntimeError::Range(error) => error.position.as_ref(),
+            RuntimeError::AssertionFailed { position, .. } => Some(position),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::DivisionByZero => write!(f, "division by zero"),
+            RuntimeError::IntegerOverflow => write!(f, "integer overflow"),
+            RuntimeError::Range(error) => write!(f, "{}", error),
+            RuntimeError::TypeMismatch { expected, found } => {
+                write!(f, "expected {} but found {}", expected, found)
+            }
+            RuntimeError::EndOfInput => write!(f, "unexpected end of input"),
+            RuntimeError::File { name, message } => write!(f, "file {} {}", name, message),
+            RuntimeError::AssertionFailed { position, message: None } => {
+                write!(f, "assertion failed at {}", position)
+            }
+            RuntimeError::AssertionFailed { position, message: Some(message) } => {
+                write!(f, "assertion failed at {}: {}", position, message)
+            }
+            RuntimeError::Exception(message) => write!(f, "unhandled exception: {}", message),
+            RuntimeError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+impl From<RangeError> for RuntimeError {
+    fn from(error: RangeError) -> RuntimeError {
+        RuntimeError::Range(Box::new(error))
+    }
+}
+
+/// Why a program could not be run to its end, by the stage which gave up on it
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// every syntax error found, the parser going on after each one from the
+    /// end of the statement it was found in
+    Parse(Vec<ParseError>),
+    Semantic(SemanticError),
+    /// an error stopping the program, with the source of the innermost node
+    /// it was raised in, when it was raised in one
+    Runtime { error: RuntimeError, span: Option<Box<Span>> },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(errors) => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "syntax error: {}", error)?;
+                }
+                Ok(())
+            }
+            Error::Semantic(error) => write!(f, "semantic error: {}", error),
+            Error::Runtime { error, span } => {
+                write!(f, "runtime error: {}", error)?;
+                match span {
+                    Some(span) if error.position().is_none() => write!(f, " at {}", span.start),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Vec<ParseError>> for Error {
+    fn from(errors: Vec<ParseError>) -> Error {
+        Error::Parse(errors)
+    }
+}
+
+impl From<SemanticError> for Error {
+    fn from(error: SemanticError) -> Error {
+        Error::Semantic(error)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Error {
+        Error::Runtime { error, span: None }
+    }
+}
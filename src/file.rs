@@ -0,0 +1,272 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+
+use crate::error::RuntimeError;
+use crate::input::Input;
+use crate::types::Type;
+use crate::value::Value;
+
+/// Where the files named by ASSIGN are opened, which an embedder can replace
+/// to keep the programs it runs away from the real files
+pub trait FileSystem {
+    /// Open an existing file for reading, as RESET does
+    fn open(&mut self, name: &str) -> io::Result<Box<dyn BufRead>>;
+    /// Create a file, or empty an existing one, for writing, as REWRITE does
+    fn create(&mut self, name: &str) -> io::Result<Box<dyn Write>>;
+}
+
+/// The files of the machine the interpreter runs on, named relative to its
+/// working directory
+pub struct HostFileSystem;
+
+impl FileSystem for HostFileSystem {
+    fn open(&mut self, name: &str) -> io::Result<Box<dyn BufRead>> {
+        Ok(Box::new(BufReader::new(fs::File::open(name)?)))
+    }
+    fn create(&mut self, name: &str) -> io::Result<Box<dyn Write>> {
+        Ok(Box::new(BufWriter::new(fs::File::create(name)?)))
+    }
+}
+
+/// A filesystem refusing to open any file
+pub struct NoFileSystem;
+
+impl FileSystem for NoFileSystem {
+    fn open(&mut self, _: &str) -> io::Result<Box<dyn BufRead>> {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "file access is disabled"))
+    }
+    fn create(&mut self, _: &str) -> io::Result<Box<dyn Write>> {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "file access is disabled"))
+    }
+}
+
+/// What a file is open for
+enum Mode {
+    Closed,
+    Reading(Input),
+    Writing(Box<dyn Write>),
+    /// a typed file, which is held in memory from RESET or REWRITE until
+    /// CLOSE writes it back, if it was written to, with `writer` when
+    /// REWRITE created it
+    Records { bytes: Vec<u8>, position: usize, writer: Option<Box<dyn Write>>, modified: bool },
+}
+
+/// The file a file variable was assigned
+struct AssignedFile {
+    name: String,
+    /// the type of the components of a typed file, `None` for a TEXT one
+    component: Option<Type>,
+    mode: Mode,
+}
+
+/// The files assigned to the file variables of a program, which refer to
+/// them by their handles
+pub struct Files {
+    file_system: Box<dyn FileSystem>,
+    files: Vec<AssignedFile>,
+}
+
+impl Files {
+    pub fn new(file_system: Box<dyn FileSystem>) -> Files {
+        Files { file_system, files: Vec::new() }
+    }
+    pub fn set_file_system(&mut self, file_system: Box<dyn FileSystem>) {
+        self.file_system = file_system;
+    }
+    /// Give the file variable holding `handle` the file called `name`, of
+    /// components of the given type unless it is a TEXT file, closing the
+    /// one it had, returning the handle it holds from now on
+    pub fn assign(
+        &mut self,
+        handle: Option<usize>,
+        name: &str,
+        component: Option<Type>,
+    ) -> Result<usize, RuntimeError> {
+        let file = AssignedFile { name: String::from(name), component, mode: Mode::Closed };
+        match handle {
+            Some(handle) => {
+                self.close(handle)?;
+                self.files[handle] = file;
+                Ok(handle)
+            }
+            None => {
+                self.files.push(file);
+                Ok(self.files.len() - 1)
+            }
+        }
+    }
+    /// Open the file for reading from its start, a typed file for writing too
+    pub fn reset(&mut self, handle: usize) -> Result<(), RuntimeError> {
+        self.close(handle)?;
+        let file = &mut self.files[handle];
+        let mut reader = match self.file_system.open(&file.name) {
+            Ok(reader) => reader,
+            Err(error) => return Err(file.error(format!("cannot be opened: {}", error))),
+        };
+        if file.component.is_none() {
+            file.mode = Mode::Reading(Input::new(reader));
+            return Ok(());
+        }
+        let mut bytes = Vec::new();
+        if let Err(error) = reader.read_to_end(&mut bytes) {
+            return Err(file.error(format!("cannot be read: {}", error)));
+        }
+        file.mode = Mode::Records { bytes, position: 0, writer: None, modified: false };
+        Ok(())
+    }
+    /// Open the file for writing, emptying it first, a typed file for reading too
+    pub fn rewrite(&mut self, handle: usize) -> Result<(), RuntimeError> {
+        self.close(handle)?;
+        let file = &mut self.files[handle];
+        let writer = match self.file_system.create(&file.name) {
+            Ok(writer) => writer,
+            Err(error) => return Err(file.error(format!("cannot be created: {}", error))),
+        };
+        file.mode = match file.component {
+            Some(_) => {
+                let writer = Some(writer);
+                Mode::Records { bytes: Vec::new(), position: 0, writer, modified: true }
+            }
+            None => Mode::Writing(writer),
+        };
+        Ok(())
+    }
+    /// Close the file, writing out what was written to it
+    pub fn close(&mut self, handle: usize) -> Result<(), RuntimeError> {
+        let file = &mut self.files[handle];
+        let result = match &mut file.mode {
+            Mode::Writing(writer) => writer.flush(),
+            Mode::Records { bytes, writer, modified: true, .. } => {
+                match writer.take().map_or_else(|| self.file_system.create(&file.name), Ok) {
+                    Ok(mut writer) => writer.write_all(bytes).and_then(|_| writer.flush()),
+                    Err(error) => Err(error),
+                }
+            }
+            _ => Ok(()),
+        };
+        file.mode = Mode::Closed;
+        result.map_err(|error| file.error(format!("cannot be written: {}", error)))
+    }
+    /// Whether a TEXT file has been read to its end, or a typed file is
+    /// positioned after its last component
+    pub fn eof(&mut self, handle: usize) -> Result<bool, RuntimeError> {
+        let file = &mut self.files[handle];
+        match (&mut file.mode, &file.component) {
+            (Mode::Reading(input), _) => input.at_end(),
+            (Mode::Records { bytes, position, .. }, Some(component)) => {
+                Ok(*position >= bytes.len() / component.file_size().unwrap())
+            }
+            _ => Err(file.error("is not open for reading")),
+        }
+    }
+    /// The text of a TEXT file open for reading
+    pub fn input(&mut self, handle: usize) -> Result<&mut Input, RuntimeError> {
+        let file = &mut self.files[handle];
+        if !matches!(file.mode, Mode::Reading(_)) {
+            return Err(file.error("is not open for reading"));
+        }
+        let Mode::Reading(input) = &mut file.mode else { unreachable!() };
+        Ok(input)
+    }
+    /// Whether the file holds components of a type rather than text
+    pub fn is_typed(&self, handle: usize) -> bool {
+        self.files[handle].component.is_some()
+    }
+    /// The bytes of an open typed file, the position in it counted in
+    /// components, whether it was written to, and the type of its components
+    fn records(
+        &mut self,
+        handle: usize,
+    ) -> Result<(&mut Vec<u8>, &mut usize, &mut bool, &Type), RuntimeError> {
+        let file = &mut self.files[handle];
+        if !matches!((&file.mode, &file.component), (Mode::Records { .. }, Some(_))) {
+            return Err(file.error("is not open"));
+        }
+        let (Mode::Records { bytes, position, modified, .. }, Some(component)) =
+            (&mut file.mode, &file.component)
+        else {
+            unreachable!()
+        };
+        Ok((bytes, position, modified, component))
+    }
+    /// Read the component at the position of a typed file, moving past it
+    pub fn read_component(&mut self, handle: usize) -> Result<Value, RuntimeError> {
+        let name = self.files[handle].name.clone();
+        let (bytes, position, _, component) = self.records(handle)?;
+        let size = component.file_size().unwrap();
+        let start = *position * size;
+        if start + size > bytes.len() {
+            let message = String::from("is read beyond its end");
+            return Err(RuntimeError::File { name, message });
+        }
+        *position += 1;
+        component.decode(&bytes[start..start + size])
+    }
+    /// Write a component at the position of a typed file, replacing the one
+    /// there, if any, and moving past it
+    pub fn write_component(&mut self, handle: usize, value: &Value) -> Result<(), RuntimeError> {
+        let (bytes, position, modified, component) = self.records(handle)?;
+        let size = component.file_size().unwrap();
+        let mut encoded = Vec::with_capacity(size);
+        component.encode(value, &mut encoded)?;
+        let start = *position * size;
+        let end = (start + size).min(bytes.len());
+        bytes.splice(start..end, encoded);
+        *position += 1;
+        *modified = true;
+        Ok(())
+    }
+    /// Move to the component numbered `index`, counting from 0, of a typed file
+    pub fn seek(&mut self, handle: usize, index: i32) -> Result<(), RuntimeError> {
+        let name = self.files[handle].name.clone();
+        let (bytes, position, _, component) = self.records(handle)?;
+        let count = bytes.len() / component.file_size().unwrap();
+        match usize::try_from(index) {
+            Ok(index) if index <= count => {
+                *position = index;
+                Ok(())
+            }
+            _ => Err(RuntimeError::File {
+                name,
+                message: format!("cannot be sought to component {} of {}", index, count),
+            }),
+        }
+    }
+    /// The number of the component at the position of a typed file
+    pub fn position(&mut self, handle: usize) -> Result<usize, RuntimeError> {
+        Ok(*self.records(handle)?.1)
+    }
+    /// The number of components of a typed file
+    pub fn size(&mut self, handle: usize) -> Result<usize, RuntimeError> {
+        let (bytes, _, _, component) = self.records(handle)?;
+        Ok(bytes.len() / component.file_size().unwrap())
+    }
+    /// Write text to a file open for writing
+    pub fn write(&mut self, handle: usize, text: &str) -> Result<(), RuntimeError> {
+        let file = &mut self.files[handle];
+        let Mode::Writing(writer) = &mut file.mode else {
+            return Err(file.error("is not open for writing"));
+        };
+        writer
+            .write_all(text.as_bytes())
+            .map_err(|error| file.error(format!("cannot be written: {}", error)))
+    }
+    /// Close every file, as the program ends, even when closing one of them
+    /// fails, returning the first failure
+    pub fn close_all(&mut self) -> Result<(), RuntimeError> {
+        let mut result = Ok(());
+        for handle in 0..self.files.len() {
+            let closed = self.close(handle);
+            if result.is_ok() {
+                result = closed;
+            }
+        }
+        result
+    }
+}
+
+impl AssignedFile {
+    fn error(&self, message: impl Into<String>) -> RuntimeError {
+        RuntimeError::File { name: self.name.clone(), message: message.into() }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::error::RuntimeError;
+use crate::types::Type;
+use crate::value::Value;
+
+/// The storage of the values created by NEW, which pointers refer to by address
+#[derive(Default)]
+pub struct Heap {
+    /// the value in each cell along with its type, `None` once disposed
+    cells: Vec<Option<(Value, Type)>>,
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap { cells: Vec::new() }
+    }
+    /// Create a cell holding the default value of the type, returning its address
+    pub fn allocate(&mut self, type_node: Type) -> Result<usize, RuntimeError> {
+        self.cells.push(Some((type_node.default_value()?, type_node)));
+        Ok(self.cells.len() - 1)
+    }
+    pub fn dispose(&mut self, address: usize) -> Result<(), RuntimeError> {
+        match self.cells[address].take() {
+            Some(_) => Ok(()),
+            None => Err(RuntimeError::new("pointer disposed twice")),
+        }
+    }
+    pub fn get(&self, address: usize) -> Result<&Value, RuntimeError> {
+        match &self.cells[address] {
+            Some((value, _)) => Ok(value),
+            None => Err(dangling()),
+        }
+    }
+    pub fn get_mut(&mut self, address: usize) -> Result<(&mut Value, &Type), RuntimeError> {
+        match &mut self.cells[address] {
+            Some((value, type_node)) => Ok((value, type_node)),
+            None => Err(dangling()),
+        }
+    }
+}
+
+fn dangling() -> RuntimeError {
+    RuntimeError::new("dangling pointer dereferenced")
+}
@@ -0,0 +1,82 @@
+use std::io::BufRead;
+
+use crate::error::RuntimeError;
+
+/// The text READ and READLN take their values from, fetched a line at a time
+pub struct Input {
+    reader: Box<dyn BufRead>,
+    /// what is left of the line being read, including its line break
+    line: String,
+}
+
+impl Input {
+    pub fn new(reader: Box<dyn BufRead>) -> Input {
+        Input { reader, line: String::new() }
+    }
+    /// Make sure there is something left to read, returning false at the end of the input
+    fn fill(&mut self) -> Result<bool, RuntimeError> {
+        if self.line.is_empty() {
+            self.reader.read_line(&mut self.line).map_err(|error| {
+                RuntimeError::new(format!("cannot read the input: {}", error))
+            })?;
+        }
+        Ok(!self.line.is_empty())
+    }
+    /// Whether everything has been read
+    pub fn at_end(&mut self) -> Result<bool, RuntimeError> {
+        Ok(!self.fill()?)
+    }
+    /// Read a number, skipping the white space and line breaks before it.
+    /// Where no digit starts the text, the word there is read instead, so
+    /// that the error tells what could not be read as a number.
+    pub fn read_number(&mut self) -> Result<String, RuntimeError> {
+        loop {
+            if !self.fill()? {
+                return Err(RuntimeError::EndOfInput);
+            }
+            let trimmed = self.line.trim_start();
+            if !trimmed.is_empty() {
+                self.line = String::from(trimmed);
+                break;
+            }
+            self.line.clear();
+        }
+        let mut number = String::new();
+        for ch in self.line.chars() {
+            let sign_allowed = number.is_empty() || number.ends_with(['e', 'E']);
+            if ch.is_ascii_digit() || ".eE".contains(ch) || ("+-".contains(ch) && sign_allowed) {
+                number.push(ch);
+            } else {
+                break;
+            }
+        }
+        if !number.contains(|ch: char| ch.is_ascii_digit()) {
+            let end = self.line.find(char::is_whitespace).unwrap_or(self.line.len());
+            number = String::from(&self.line[..end]);
+        }
+        self.line.drain(..number.len());
+        Ok(number)
+    }
+    /// Read one character, where the end of a line reads as a space
+    pub fn read_char(&mut self) -> Result<char, RuntimeError> {
+        if !self.fill()? {
+            return Err(RuntimeError::EndOfInput);
+        }
+        Ok(match self.line.remove(0) {
+            '\n' => ' ',
+            ch => ch,
+        })
+    }
+    /// Read the rest of the current line, leaving its line break to be read
+    pub fn read_string(&mut self) -> Result<String, RuntimeError> {
+        self.fill()?;
+        let end = self.line.find('\n').unwrap_or(self.line.len());
+        Ok(self.line.drain(..end).collect())
+    }
+    /// Skip what is left of the current line, including its line break
+    pub fn skip_line(&mut self) -> Result<(), RuntimeError> {
+        self.fill()?;
+        self.line.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,1673 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+use crate::ast::{
+    Assign, BinOp, Block, Case, Compound, ConstDecl, Decl, Deref, ExceptionHandler, Exit, Expr,
+    Field, For, FormattedParam, ForwardDecl, FunctionCall, FunctionDecl, Goto, If, Index,
+    LabelDecl, Labeled, Literal, ProcedureCall, ProcedureDecl, Program, Raise, Repeat, Routine,
+    SetConstructor, Stmt, TryExcept, TryFinally, TypeDecl, TypedConstDecl, UnaryOp, Var, VarDecl,
+    While, With, node_address,
+};
+use crate::call_stack::{ActivationRecord, CallStack};
+use crate::clock::{Clock, DateTime, SystemClock, delphi_time};
+use crate::diagnostic::Sources;
+use crate::environment::{Environment, ProcessEnvironment};
+use crate::dialect::Feature;
+use crate::error::{Error, RuntimeError};
+use crate::file::{FileSystem, Files, HostFileSystem};
+use crate::heap::Heap;
+use crate::input::Input;
+use crate::lexer::Lexer;
+use crate::names::same_name;
+use crate::operation::{binary_operation, insert_range, unary_operation};
+use crate::parser::Parser;
+use crate::random::Random;
+use crate::semantic_analyzer::SemanticAnalyzer;
+use crate::symbol::{IT, RESULT};
+use crate::set::Set;
+use crate::builtin::{
+    builtin_function, is_standard_procedure, parse_number, standard_function, type_function,
+};
+use crate::token::{OpType, Position, Span};
+use crate::types::{MAX_ARRAY_LENGTH, RangeError, Type};
+use crate::value::{Selector, Value};
+use crate::visitor::Visitor;
+
+/// The widest field and the most decimals WRITE lays a value out in
+const MAX_FIELD_WIDTH: i32 = u16::MAX as i32;
+
+/// How many calls may be running at once before the program is stopped
+/// with a runtime error, rather than overflowing the stack of the thread
+/// running it, which has to be large enough for that many
+const MAX_CALL_DEPTH: usize = 10_000;
+
+/// The file named by the first argument of READ or WRITE, if any, and the
+/// arguments after it
+type FileArguments<'a> = (Option<usize>, &'a [Expr]);
+
+/// Where the value of a variable access lives
+#[derive(Clone)]
+enum Root {
+    Variable(String),
+    Heap(usize),
+}
+
+/// A variable access resolved to its root along with the selectors leading
+/// from the root to the part of it being accessed
+#[derive(Clone)]
+struct Place {
+    root: Root,
+    selectors: Vec<Selector>,
+}
+
+impl Place {
+    fn variable(name: &str) -> Place {
+        Place { root: Root::Variable(String::from(name)), selectors: Vec::new() }
+    }
+}
+
+/// How a statement finished, telling the statements around it whether to go on.
+///
+/// A statement running into a runtime error or raising an exception returns
+/// the error instead, which unwinds until reaching a TRY.
+#[derive(PartialEq)]
+pub enum Flow {
+    Normal,
+    Break,
+    Continue,
+    /// leave the running routine, unwinding all the statements it is inside
+    Exit,
+    /// stop the whole program, unwinding every running routine
+    Halt,
+    /// unwind until reaching a statement list holding the statement with this label
+    Goto(String),
+}
+
+/// The value an exception handler binds an exception or a runtime error to,
+/// a record holding its message
+fn exception_value(error: &RuntimeError) -> Value {
+    let message = match error {
+        RuntimeError::Exception(message) => message.clone(),
+        error => error.to_string(),
+    };
+    Value::Record(vec![(String::from("Message"), Value::String(message))])
+}
+
+pub struct Interpreter {
+    parser: Parser,
+    call_stack: CallStack,
+    heap: Heap,
+    /// where READ and READLN take their values from
+    input: Input,
+    /// where WRITE and WRITELN send their text
+    output: Box<dyn Write>,
+    /// the files assigned to the file variables
+    files: Files,
+    /// the addresses of the file variables READ, READLN, WRITE and WRITELN
+    /// are given as their first argument
+    file_arguments: HashSet<usize>,
+    /// the addresses of the packed arrays of characters written or compared,
+    /// which are taken as strings there
+    packed_strings: HashSet<usize>,
+    /// the types of the arguments of LOW, HIGH and SIZEOF, by their addresses
+    type_arguments: HashMap<usize, Type>,
+    /// reject values assigned to a subrange which lie outside of it, and
+    /// array indices outside of the bounds with a range error, where no
+    /// `{$R+}` or `{$R-}` directive says whether to
+    range_checks: bool,
+    /// where the innermost statement being run starts, which the range
+    /// errors and the `{$R}` directives are found by
+    position: Option<Position>,
+    /// the source of the innermost node the error being raised came from,
+    /// until a TRY handles the error
+    error_span: Option<Span>,
+    /// skip the right operand of AND and OR when the left one decides the result
+    short_circuit: bool,
+    /// check the conditions given to ASSERT, unless a `{$C-}` directive turns it off
+    assertions: bool,
+    /// the code the program was stopped with by HALT
+    exit_code: Option<i32>,
+    /// the records opened by the WITH statements being run, innermost last,
+    /// along with the depth of the call stack at the time they were opened
+    with_records: Vec<(usize, Place)>,
+    /// the heap cells holding the typed constants, by the address of their
+    /// declaration, allocated the first time the declaration is run
+    static_cells: HashMap<usize, usize>,
+    /// the names the semantic analyzer gave the overloaded routines and the
+    /// calls to them, by the address of their nodes
+    routine_names: HashMap<usize, String>,
+    /// the addresses of the names standing for a routine rather than calling it
+    routine_values: HashSet<usize>,
+    /// the exceptions being handled, innermost last, which a bare RAISE raises again
+    handling: Vec<RuntimeError>,
+    /// the generator RANDOM takes its numbers from
+    random: Random,
+    /// the seed RANDOMIZE starts the generator from again, instead of the
+    /// clock, when the embedder asked for reproducible runs
+    random_seed: Option<u64>,
+    /// the name the program was run by followed by the arguments it was
+    /// given, which PARAMSTR returns and PARAMCOUNT counts
+    arguments: Vec<String>,
+    /// the variables GETENV looks up
+    environment: Box<dyn Environment>,
+    /// the time GETDATE, GETTIME and NOW tell, which RANDOMIZE seeds the generator from
+    clock: Box<dyn Clock>,
+    /// how many statements have been run so far
+    statements_run: u64,
+    /// the analyzer of the inputs run by `interpret_input`, holding the
+    /// global scope they declare their names in
+    session: Option<SemanticAnalyzer>,
+    /// the trees of the inputs run so far, kept so that no node of a later
+    /// input takes over the address of one of their nodes
+    inputs: Vec<Rc<Block>>,
+    /// the trees of the inputs evaluated so far, kept like `inputs`
+    expressions: Vec<Rc<Expr>>,
+    /// the source of the declarations of the inputs run so far
+    declarations: Vec<String>,
+}
+
+impl Interpreter {
+    pub fn new(parser: Parser, input: Box<dyn BufRead>, output: Box<dyn Write>) -> Interpreter {
+        Interpreter {
+            parser,
+            call_stack: CallStack::new(),
+            heap: Heap::new(),
+            input: Input::new(input),
+            output,
+            files: Files::new(Box::new(HostFileSystem)),
+            file_arguments: HashSet::new(),
+            packed_strings: HashSet::new(),
+            type_arguments: HashMap::new(),
+            range_checks: false,
+            position: None,
+            error_span: None,
+            short_circuit: false,
+            assertions: true,
+            exit_code: None,
+            with_records: Vec::new(),
+            static_cells: HashMap::new(),
+            routine_names: HashMap::new(),
+            routine_values: HashSet::new(),
+            handling: Vec::new(),
+            random: Random::new(0),
+            random_seed: None,
+            arguments: Vec::new(),
+            environment: Box::new(ProcessEnvironment),
+            clock: Box::new(SystemClock),
+            statements_run: 0,
+            session: None,
+            inputs: Vec::new(),
+            expressions: Vec::new(),
+            declarations: Vec::new(),
+        }
+    }
+    pub fn set_range_checks(&mut self, range_checks: bool) {
+        self.range_checks = range_checks;
+    }
+    /// Choose between short-circuit evaluation of AND and OR, as Turbo Pascal
+    /// does by default, and evaluating both operands, as ISO Pascal allows,
+    /// where no `{$B+}` or `{$B-}` directive says which
+    pub fn set_short_circuit(&mut self, short_circuit: bool) {
+        self.short_circuit = short_circuit;
+    }
+
+    /// Check the conditions given to ASSERT where no `{$C+}` or `{$C-}`
+    /// directive says whether to
+    pub fn set_assertions(&mut self, assertions: bool) {
+        self.assertions = assertions;
+    }
+
+    /// Open the files named by ASSIGN in `file_system` rather than among the
+    /// files of the machine
+    pub fn set_file_system(&mut self, file_system: Box<dyn FileSystem>) {
+        self.files.set_file_system(file_system);
+    }
+
+    /// Give the program the arguments PARAMCOUNT and PARAMSTR tell it of,
+    /// the first being the name it was run by, which PARAMSTR(0) returns
+    pub fn set_arguments(&mut self, arguments: Vec<String>) {
+        self.arguments = arguments;
+    }
+
+    /// Have GETENV look the variables up in `environment` rather than among
+    /// those of the process
+    pub fn set_environment(&mut self, environment: Box<dyn Environment>) {
+        self.environment = environment;
+    }
+
+    /// Take the time from `clock` rather than from the time of day
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Start the generator RANDOM takes its numbers from at `seed`, which
+    /// RANDOMIZE goes back to rather than the clock, so that every run
+    /// gives the same numbers
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.random = Random::new(seed);
+        self.random_seed = Some(seed);
+    }
+
+    /// The text of the program and of the files it includes, which its errors
+    /// are rendered with
+    pub fn sources(&self) -> &Sources {
+        self.parser.sources()
+    }
+    /// The code passed to HALT, if the program was stopped by it
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+    /// How many statements have been run so far, those run again by a loop
+    /// or by calling a routine again counting once for each time
+    pub fn statements_run(&self) -> u64 {
+        self.statements_run
+    }
+
+    /// Run the program, returning the frame holding its global variables, or
+    /// the error which kept it from being run to its end
+    pub fn interpret(&mut self) -> Result<ActivationRecord, Error> {
+        let tree = self.parser.parse()?;
+        self.run(&tree)
+    }
+    /// Run a source made of statements alone, as the body of a program
+    /// declaring nothing
+    pub fn interpret_statements(&mut self) -> Result<ActivationRecord, Error> {
+        let tree = self.parser.parse_statements()?;
+        self.run(&tree)
+    }
+    /// The value of a source which is a single expression
+    pub fn evaluate(&mut self) -> Result<Value, Error> {
+        let node = self.parser.parse_expression()?;
+        let mut analyzer = self.analyzer();
+        analyzer.analyze_expression(&node)?;
+        self.keep_analysis(&mut analyzer);
+        self.call_stack.push(ActivationRecord::new("global", 1, None));
+        let result = self.eval(&node);
+        self.call_stack.pop();
+        let span = self.error_span.take().map(Box::new);
+        result.map_err(|error| Error::Runtime { error, span })
+    }
+    /// Run an input typed in at a REPL, read from `lexer`: declarations,
+    /// followed by statements. The input is run in the global scope the
+    /// inputs run before it left, which its declarations add to, unless it
+    /// fails before its statements are run, which declares nothing.
+    pub fn interpret_input(&mut self, lexer: Lexer) -> Result<(), Error> {
+        let tree = Rc::new(self.parser.parse_input(lexer)?);
+        // the analysis tells the interpreter about the nodes by their addresses
+        self.inputs.push(Rc::clone(&tree));
+        let mut analyzer = self.session.take().unwrap_or_else(|| self.analyzer());
+        let result = self.run_input(&tree, &mut analyzer);
+        self.session = Some(analyzer);
+        result
+    }
+    /// The source of the declarations of the inputs run so far, in the order
+    /// they were run, which declares the same again when run as an input
+    pub fn declarations_source(&self) -> String {
+        self.declarations.iter().map(|source| format!("{}\n", source)).collect()
+    }
+    /// The value of an input typed in at a REPL which is a single
+    /// expression, evaluated in the global scope the inputs run before it
+    /// left. The value is kept in the variable `it` for the inputs after it,
+    /// when its type can be told before running it.
+    pub fn evaluate_input(&mut self, lexer: Lexer) -> Result<Value, Error> {
+        let node = Rc::new(self.parser.parse_input_expression(lexer)?);
+        self.expressions.push(Rc::clone(&node));
+        let mut analyzer = self.session.take().unwrap_or_else(|| self.analyzer());
+        let result = self.evaluate_in_session(&node, &mut analyzer);
+        self.session = Some(analyzer);
+        result
+    }
+    /// The type of an expression read from `lexer` in the global scope of
+    /// the inputs run so far, as far as it can be told without evaluating it
+    pub fn input_expression_type(&mut self, lexer: Lexer) -> Result<Option<Type>, Error> {
+        let node = self.parser.parse_input_expression(lexer)?;
+        let mut analyzer = self.session.take().unwrap_or_else(|| self.analyzer());
+        let result = analyzer.input_expression_type(&node);
+        self.session = Some(analyzer);
+        Ok(result?)
+    }
+    /// The frame holding the global variables and routines declared by the
+    /// inputs run so far, `None` before the first one
+    pub fn globals(&self) -> Option<&ActivationRecord> {
+        // the frame of the global scope is the first one pushed
+        self.session.as_ref().filter(|_| self.call_stack.depth() > 0)?;
+        Some(self.call_stack.get(0))
+    }
+    /// The names declared by the inputs run so far
+    pub fn declared_names(&self) -> Vec<String> {
+        self.session.as_ref().map(SemanticAnalyzer::input_names).unwrap_or_default()
+    }
+    /// Write out the files left open by the inputs run, once the session ends
+    pub fn close_files(&mut self) -> Result<(), Error> {
+        Ok(self.files.close_all()?)
+    }
+    fn run(&mut self, tree: &Program) -> Result<ActivationRecord, Error> {
+        let mut analyzer = self.analyzer();
+        analyzer.analyze(tree)?;
+        self.keep_analysis(&mut analyzer);
+        self.call_stack.push(ActivationRecord::new("global", 1, None));
+        let result = self.visit_program(tree);
+        // the files are written out even when the program fails
+        let closed = self.files.close_all();
+        let span = self.error_span.take().map(Box::new);
+        result.map_err(|error| Error::Runtime { error, span })?;
+        closed?;
+        Ok(self.call_stack.pop())
+    }
+    /// The semantic analyzer of the source, in its dialect
+    fn analyzer(&self) -> SemanticAnalyzer {
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_dialect(self.parser.dialect());
+        analyzer
+    }
+    fn run_input(&mut self, tree: &Block, analyzer: &mut SemanticAnalyzer) -> Result<(), Error> {
+        if let Err(error) = analyzer.analyze_input(tree) {
+            self.parser.forget_input();
+            return Err(error.into());
+        }
+        self.keep_analysis(analyzer);
+        self.enter_session();
+        let global = self.call_stack.peek().clone();
+        let declared = tree.declarations.iter().try_for_each(|node| self.visit_decl(node));
+        let result = match declared {
+            Ok(()) => {
+                let statements = &tree.compound_statement.span;
+                let source = self.parser.sources().between(&tree.span, statements);
+                if let Some(source) = source.map(str::trim).filter(|source| !source.is_empty()) {
+                    self.declarations.push(String::from(source));
+                }
+                self.visit_body(tree).map(|_| ())
+            }
+            Err(error) => {
+                *self.call_stack.peek() = global;
+                self.parser.forget_input();
+                analyzer.forget_input();
+                Err(error)
+            }
+        };
+        self.leave_session(result)
+    }
+    fn evaluate_in_session(
+        &mut self,
+        node: &Expr,
+        analyzer: &mut SemanticAnalyzer,
+    ) -> Result<Value, Error> {
+        let type_node = analyzer.analyze_input_expression(node)?;
+        self.keep_analysis(analyzer);
+        self.enter_session();
+        let result = self.eval(node);
+        let value = self.leave_session(result)?;
+        if let Some(type_node) = type_node {
+            analyzer.declare_it(type_node.clone());
+            let record = self.call_stack.peek();
+            record.members.insert(String::from(IT), value.clone());
+            record.member_types.insert(String::from(IT), type_node);
+        }
+        Ok(value)
+    }
+    /// Run the frame of the global scope of the inputs, unless it runs already
+    fn enter_session(&mut self) {
+        if self.call_stack.depth() == 0 {
+            self.call_stack.push(ActivationRecord::new("global", 1, None));
+        }
+    }
+    /// Go back to the frame of the global scope of the inputs once an input
+    /// has run, leaving the routines and the WITH statements an error
+    /// unwinds from
+    fn leave_session<T>(&mut self, result: Result<T, RuntimeError>) -> Result<T, Error> {
+        while self.call_stack.depth() > 1 {
+            self.call_stack.pop();
+        }
+        self.with_records.clear();
+        let span = self.error_span.take().map(Box::new);
+        result.map_err(|error| Error::Runtime { error, span })
+    }
+    /// Keep what the analysis of the source found out about it for running
+    /// it, along with what it found out about the inputs run before
+    fn keep_analysis(&mut self, analyzer: &mut SemanticAnalyzer) {
+        self.file_arguments.extend(analyzer.take_file_arguments());
+        self.packed_strings.extend(analyzer.take_packed_strings());
+        self.type_arguments.extend(analyzer.take_type_arguments());
+        let (routine_names, routine_values) = analyzer.take_routine_names();
+        self.routine_names.extend(routine_names);
+        self.routine_values.extend(routine_values);
+    }
+
+    /// Execute a statement
+    fn visit(&mut self, node: &Stmt) -> Result<Flow, RuntimeError> {
+        // once halted, nothing runs any more, not even the routines called
+        // by the rest of the expression which called HALT
+        if self.exit_code.is_some() {
+            return Ok(Flow::Halt);
+        }
+        self.statements_run += 1;
+        // the statement calling a routine goes on where the routine returns
+        let position = self.position.replace(node.span().start.clone());
+        let result = self.dispatch_stmt(node);
+        self.position = position;
+        self.locate_error(node.span(), result)
+    }
+    /// Run a declaration, making what it declares known to the frame of its block
+    fn visit_decl(&mut self, node: &Decl) -> Result<(), RuntimeError> {
+        let result = self.dispatch_decl(node);
+        self.locate_error(node.span(), result)
+    }
+    /// Evaluate an expression
+    fn eval(&mut self, node: &Expr) -> Result<Value, RuntimeError> {
+        let result = self.dispatch_expr(node);
+        self.locate_error(node.span(), result)
+    }
+    /// Evaluate an expression written or compared, where a packed array of
+    /// characters stands for the string of its characters
+    fn eval_text(&mut self, node: &Expr) -> Result<Value, RuntimeError> {
+        match self.eval(node)? {
+            Value::Array { elements, .. } if self.packed_strings.contains(&node_address(node)) => {
+                Ok(Value::String(elements.iter().map(Value::as_string).collect::<Result<_, _>>()?))
+            }
+            value => Ok(value),
+        }
+    }
+    /// Reject comparing a packed array of characters with a string of
+    /// another length
+    fn check_packed_length(
+        &self,
+        node: &BinOp,
+        left: &Value,
+        right: &Value,
+    ) -> Result<(), RuntimeError> {
+        let packed = |operand: &Expr| self.packed_strings.contains(&node_address(operand));
+        if !(packed(&node.left) || packed(&node.right)) || !left.is_text() || !right.is_text() {
+            return Ok(());
+        }
+        let (array, other) = if packed(&node.left) { (left, right) } else { (right, left) };
+        let (length, other_length) =
+            (array.as_string()?.chars().count(), other.as_string()?.chars().count());
+        if length != other_length {
+            return Err(RuntimeError::new(format!(
+                "cannot compare a packed array of {} characters with a string of length {}",
+                length, other_length
+            )));
+        }
+        Ok(())
+    }
+
+    /// Run the statements of a block, once its declarations have been run
+    fn visit_body(&mut self, node: &Block) -> Result<Flow, RuntimeError> {
+        let result = match self.visit_compound(&node.compound_statement)? {
+            Flow::Goto(label) => Err(RuntimeError::new(format!(
+                "GOTO {} jumps into a structured statement",
+                label
+            ))),
+            flow => Ok(flow),
+        };
+        self.locate_error(&node.span, result)
+    }
+
+    /// How the flow goes on after a statement which cannot break it by
+    /// itself, but may have called a routine which called HALT
+    fn flow(&self) -> Flow {
+        if self.exit_code.is_some() { Flow::Halt } else { Flow::Normal }
+    }
+    /// Remember the source of a node failing with an error, unless a node
+    /// inside it already told where the error came from
+    fn locate_error<T>(
+        &mut self,
+        span: &Span,
+        result: Result<T, RuntimeError>,
+    ) -> Result<T, RuntimeError> {
+        if result.is_err() && self.error_span.is_none() {
+            self.error_span = Some(span.clone());
+        }
+        result
+    }
+    /// Evaluate the expression guarding an IF or a loop
+    fn condition(&mut self, node: &Expr) -> Result<bool, RuntimeError> {
+        self.eval(node)?.as_boolean()
+    }
+
+    /// Run statements one after the other until one of them breaks the flow.
+    ///
+    /// A GOTO to a label on one of the statements goes on from that statement.
+    fn visit_statements(&mut self, statements: &[Stmt]) -> Result<Flow, RuntimeError> {
+        let mut i = 0;
+        while i < statements.len() {
+            match self.visit(&statements[i])? {
+                Flow::Normal => i += 1,
+                Flow::Goto(label) => match statements.iter().position(|statement| {
+                    matches!(statement, Stmt::Labeled(labeled) if labeled.label == label)
+                }) {
+                    Some(target) => i = target,
+                    None => return Ok(Flow::Goto(label)),
+                },
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+    /// `ASSERT(condition)` or `ASSERT(condition, message)`, failing with a
+    /// runtime error when the condition is false and assertions are on
+    fn visit_assert(&mut self, call: &ProcedureCall) -> Result<(), RuntimeError> {
+        let (condition, message) = match call.actual_params.as_slice() {
+            [condition] => (condition, None),
+            [condition, message] => (condition, Some(message)),
+            params => return Err(wrong_arguments("ASSERT", "1 or 2 arguments", params.len())),
+        };
+        let assertions = self.parser.switch_at('C', &call.span.start).unwrap_or(self.assertions);
+        if !assertions || self.condition(condition)? {
+            return Ok(());
+        }
+        let message = match message {
+            Some(message) => Some(self.eval(message)?.as_string()?),
+            None => None,
+        };
+        Err(RuntimeError::AssertionFailed { position: call.span.start.clone(), message })
+    }
+    /// Run statements which a TRY guards, leaving the routines and the WITH
+    /// statements a runtime error or an exception unwinds from them
+    fn guarded(&mut self, statements: &[Stmt]) -> Result<Flow, RuntimeError> {
+        let depth = self.call_stack.depth();
+        let with_records = self.with_records.len();
+        let result = self.visit_statements(statements);
+        if result.is_err() {
+            while self.call_stack.depth() > depth {
+                self.call_stack.pop();
+            }
+            self.with_records.truncate(with_records);
+        }
+        result
+    }
+    /// Run a handler with the exception being handled bound to its variable,
+    /// if it has one
+    fn visit_exception_handler(
+        &mut self,
+        handler: &ExceptionHandler,
+    ) -> Result<Flow, RuntimeError> {
+        let Some(variable) = &handler.variable else {
+            return self.guarded(std::slice::from_ref(&handler.body));
+        };
+        let exception = exception_value(self.handling.last().unwrap());
+        let record = self.call_stack.peek();
+        let hidden = record.members.remove(variable).zip(record.member_types.remove(variable));
+        record.members.insert(variable.clone(), exception);
+        record.member_types.insert(variable.clone(), Type::exception());
+        let result = self.guarded(std::slice::from_ref(&handler.body));
+        let record = self.call_stack.peek();
+        record.members.remove(variable);
+        record.member_types.remove(variable);
+        if let Some((value, type_node)) = hidden {
+            record.members.insert(variable.clone(), value);
+            record.member_types.insert(variable.clone(), type_node);
+        }
+        result
+    }
+    /// The name a routine or a call to one is known by at run time, which
+    /// differs from its declared name for an overloaded routine
+    fn routine_name<T>(&self, node: &T, name: &str) -> String {
+        match self.routine_names.get(&node_address(node)) {
+            Some(name) => name.clone(),
+            None => String::from(name),
+        }
+    }
+    /// Replace the names of declared types by what they stand for
+    fn resolve_type(&self, type_node: &Type) -> Type {
+        match type_node {
+            Type::Named(name) => match self.call_stack.lookup_type(name) {
+                Some(type_node) => type_node.clone(),
+                None => panic!("unknown type {}", name),
+            },
+            Type::Array { index, element, packed } => Type::Array {
+                index: Box::new(self.resolve_type(index)),
+                element: Box::new(self.resolve_type(element)),
+                packed: *packed,
+            },
+            Type::DynamicArray(element) => Type::DynamicArray(Box::new(self.resolve_type(element))),
+            Type::Set(element) => Type::Set(Box::new(self.resolve_type(element))),
+            Type::File(component) => Type::File(Box::new(self.resolve_type(component))),
+            Type::Record { fields, variant_parts } => Type::Record {
+                fields: fields
+                    .iter()
+                    .map(|(name, type_node)| (name.clone(), self.resolve_type(type_node)))
+                    .collect(),
+                variant_parts: variant_parts.clone(),
+            },
+            _ => type_node.clone(),
+        }
+    }
+    fn store(&mut self, place: &Place, value: Value) -> Result<(), RuntimeError> {
+        let (range_checks, position) = (self.range_checks(), self.position.clone());
+        let (target, type_node) = self.locate(place, Some(&value))?;
+        let value = type_node.converted(value, target)?;
+        if range_checks {
+            check_range(type_node, &value, position.as_ref())?;
+        }
+        *target = value;
+        Ok(())
+    }
+    /// The state the directives of the program set a switch to at a
+    /// position, `None` where none of them sets it
+    fn switch(&self, switch: char, position: Option<&Position>) -> Option<bool> {
+        self.parser.switch_at(switch, position?)
+    }
+    /// Whether the range checks are on at the statement being run
+    fn range_checks(&self) -> bool {
+        self.switch('R', self.position.as_ref()).unwrap_or(self.range_checks)
+    }
+    /// Set the result of the running function
+    fn set_result(&mut self, value: Value) -> Result<(), RuntimeError> {
+        // a function which called HALT leaves no result worth storing
+        if self.exit_code.is_some() {
+            return Ok(());
+        }
+        let (range_checks, position) = (self.range_checks(), self.position.clone());
+        let record = self.call_stack.peek();
+        let (Some(target), Some(type_node)) = (&mut record.return_value, &record.return_type) else {
+            unreachable!()
+        };
+        let value = type_node.converted(value, target)?;
+        if range_checks {
+            check_range(type_node, &value, position.as_ref())?;
+        }
+        *target = value;
+        Ok(())
+    }
+    /// Resolve a variable access, evaluating its indices and following its pointers
+    fn place(&mut self, node: &Expr) -> Result<Place, RuntimeError> {
+        match node {
+            Expr::Var(var) => self.variable_place(&var.name),
+            Expr::Index(index) => self.index_place(index),
+            Expr::Field(field) => self.field_place(field),
+            Expr::Deref(deref) => self.deref_place(deref),
+            _ => panic!("cannot assign to an expression"),
+        }
+    }
+    fn index_place(&mut self, node: &Index) -> Result<Place, RuntimeError> {
+        let mut place = self.place(&node.array)?;
+        place.selectors.push(Selector::Index(self.eval(&node.index)?.ordinal()?));
+        Ok(place)
+    }
+    fn field_place(&mut self, node: &Field) -> Result<Place, RuntimeError> {
+        let mut place = self.place(&node.record)?;
+        place.selectors.push(Selector::Field(node.name.clone()));
+        Ok(place)
+    }
+    fn deref_place(&mut self, node: &Deref) -> Result<Place, RuntimeError> {
+        match self.eval(&node.pointer)?.as_pointer()? {
+            Some(address) => Ok(Place { root: Root::Heap(address), selectors: Vec::new() }),
+            None => Err(RuntimeError::new("NIL pointer dereferenced")),
+        }
+    }
+    /// The storage and declared type of the part of a variable or heap cell
+    /// a place refers to, `storing` being the value about to be stored there
+    /// if any, which makes active the variants of the records on the way
+    fn locate(
+        &mut self,
+        place: &Place,
+        storing: Option<&Value>,
+    ) -> Result<(&mut Value, &Type), RuntimeError> {
+        let (range_checks, position) = (self.range_checks(), self.position.clone());
+        let result_variable = self.parser.dialect().allows(Feature::ResultVariable);
+        let (mut value, mut type_node) = match &place.root {
+            Root::Variable(name) => self.call_stack.variable_mut(name, result_variable),
+            Root::Heap(address) => self.heap.get_mut(*address)?,
+        };
+        for selector in &place.selectors {
+            if let Selector::Field(name) = selector {
+                if let Some(stored) = storing {
+                    type_node.switch_variant(value, name, stored)?;
+                }
+                type_node.check_variant(value, name)?;
+            }
+            if let Selector::Index(index) = selector
+                && range_checks
+            {
+                check_index(type_node, value, *index, position.as_ref())?;
+            }
+            value = value.select_mut(selector)?;
+            type_node = type_node.select(selector);
+        }
+        Ok((value, type_node))
+    }
+
+    /// The place of a variable named on its own, which may be the field of a
+    /// record opened by a WITH or a typed constant
+    fn variable_place(&mut self, name: &str) -> Result<Place, RuntimeError> {
+        if let Some(place) = self.with_field(name)? {
+            return Ok(place);
+        }
+        Ok(match self.call_stack.lookup_static(name) {
+            Some(address) => Place { root: Root::Heap(address), selectors: Vec::new() },
+            None => Place::variable(name),
+        })
+    }
+    /// The place of the field `name` of the innermost record opened by a WITH
+    /// having one; the routines called from within a WITH do not see its fields
+    fn with_field(&mut self, name: &str) -> Result<Option<Place>, RuntimeError> {
+        let depth = self.call_stack.depth();
+        for i in (0..self.with_records.len()).rev() {
+            let (opened_at, place) = self.with_records[i].clone();
+            if opened_at != depth {
+                break;
+            }
+            if let Value::Record(fields) = self.locate(&place, None)?.0
+                && fields.iter().any(|(field, _)| same_name(field, name))
+            {
+                let mut place = place;
+                place.selectors.push(Selector::Field(String::from(name)));
+                return Ok(Some(place));
+            }
+        }
+        Ok(None)
+    }
+    /// Call a procedure or function, returning the result of a function
+    fn call(
+        &mut self,
+        name: &str,
+        actual_params: &[Expr],
+    ) -> Result<Option<Value>, RuntimeError> {
+        let Some((decl, declared_in)) = self.call_stack.lookup_routine(name)? else {
+            if is_standard_procedure(name) {
+                self.call_standard_procedure(name, actual_params)?;
+                return Ok(None);
+            }
+            if standard_function(name).is_some() {
+                return Ok(Some(self.call_standard_function(name, actual_params)?));
+            }
+            if let Some(function) = builtin_function(name) {
+                let args = actual_params
+                    .iter()
+                    .map(|param| self.eval(param))
+                    .collect::<Result<Vec<_>, _>>()?;
+                return Ok(Some((function.apply)(&args)?));
+            }
+            panic!("unknown routine {}", name)
+        };
+        // the routine may be called through a procedural parameter, whose
+        // name is not the one the routine assigns its result to
+        let (routine_name, params, block, return_type) = match &decl {
+            Routine::Procedure(decl) => (&decl.name, &decl.params, &decl.block, None),
+            Routine::Function(decl) => {
+                (&decl.name, &decl.params, &decl.block, Some(&decl.return_type))
+            }
+        };
+        if params.len() != actual_params.len() {
+            let expected = format!("{} arguments", params.len());
+            return Err(wrong_arguments(name, &expected, actual_params.len()));
+        }
+        // the arguments are evaluated in the caller's frame, where the names
+        // passed for procedural parameters are looked up too
+        let args = actual_params
+            .iter()
+            .map(|param| self.eval(param))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if self.call_stack.depth() > MAX_CALL_DEPTH {
+            let message = format!("stack overflow: more than {} nested calls", MAX_CALL_DEPTH);
+            return Err(RuntimeError::new(message));
+        }
+        let nesting_level = self.call_stack.get(declared_in).nesting_level + 1;
+        let record = ActivationRecord::new(routine_name, nesting_level, Some(declared_in));
+        self.call_stack.push(record);
+        // the types of the parameters are resolved in the routine's own
+        // scope, which sees the frame declaring it rather than the caller's
+        for (param, arg) in params.iter().zip(args) {
+            let type_node = self.resolve_type(&param.type_node);
+            let value = type_node.converted(arg, &type_node.default_value()?)?;
+            if self.range_checks() {
+                check_range(&type_node, &value, self.position.as_ref())?;
+            }
+            let record = self.call_stack.peek();
+            record.members.insert(param.var_node.name.clone(), value);
+            record.member_types.insert(param.var_node.name.clone(), type_node);
+        }
+        if let Some(return_type) = return_type {
+            let return_type = self.resolve_type(return_type);
+            let return_value = return_type.default_value()?;
+            let record = self.call_stack.peek();
+            record.return_value = Some(return_value);
+            record.return_type = Some(return_type);
+        }
+        // a runtime error leaves the frame to the TRY handling it, which
+        // pops the frames of all the routines the error unwinds
+        self.visit_block(block)?;
+        Ok(self.call_stack.pop().return_value)
+    }
+
+    fn call_standard_function(
+        &mut self,
+        name: &str,
+        actual_params: &[Expr],
+    ) -> Result<Value, RuntimeError> {
+        Ok(match name.to_uppercase().as_str() {
+            "RANDOM" => match actual_params {
+                [] => Value::Real(self.random.real()),
+                [limit] => {
+                    let limit = self.eval(limit)?.as_integer()?;
+                    Value::Integer(self.random.below(limit))
+                }
+                _ => return Err(wrong_arguments("RANDOM", "0 to 1 arguments", actual_params.len())),
+            },
+            "EOF" => match actual_params {
+                [] => Value::Boolean(self.input.at_end()?),
+                [file] => {
+                    let handle = self.eval(file)?.as_file()?;
+                    Value::Boolean(self.files.eof(handle)?)
+                }
+                _ => return Err(wrong_arguments("EOF", "0 to 1 arguments", actual_params.len())),
+            },
+            "FILEPOS" | "FILESIZE" => {
+                let [file] = actual_params else {
+                    return Err(wrong_arguments(name, "1 argument", actual_params.len()));
+                };
+                let handle = self.eval(file)?.as_file()?;
+                let count = match name.to_uppercase().as_str() {
+                    "FILEPOS" => self.files.position(handle)?,
+                    _ => self.files.size(handle)?,
+                };
+                Value::Integer(count as i32)
+            }
+            "PARAMCOUNT" => Value::Integer(self.arguments.len().saturating_sub(1) as i32),
+            // there is no argument beyond the last one, just an empty string
+            "PARAMSTR" => {
+                let [index] = actual_params else {
+                    return Err(wrong_arguments("PARAMSTR", "1 argument", actual_params.len()));
+                };
+                let index = self.eval(index)?.as_integer()?;
+                let argument = usize::try_from(index).ok().and_then(|i| self.arguments.get(i));
+                Value::String(argument.cloned().unwrap_or_default())
+            }
+            "NOW" => Value::Real(delphi_time(self.clock.now())),
+            "LOW" | "HIGH" | "SIZEOF" => {
+                let [argument] = actual_params else {
+                    return Err(wrong_arguments(name, "1 argument", actual_params.len()));
+                };
+                let type_node = self.type_arguments[&node_address(argument)].clone();
+                // the length of a dynamic array is all its type does not tell
+                let value = match type_node {
+                    Type::DynamicArray(_) => Some(self.eval(argument)?),
+                    _ => None,
+                };
+                type_function(name, &type_node, value.as_ref())?
+            }
+            // a variable which is not set reads as an empty string
+            "GETENV" => {
+                let [variable] = actual_params else {
+                    return Err(wrong_arguments("GETENV", "1 argument", actual_params.len()));
+                };
+                let variable = self.eval(variable)?.as_string()?;
+                Value::String(self.environment.get(&variable).unwrap_or_default())
+            }
+            _ => panic!("unknown standard function {}", name),
+        })
+    }
+
+    fn call_standard_procedure(
+        &mut self,
+        name: &str,
+        actual_params: &[Expr],
+    ) -> Result<(), RuntimeError> {
+        let name = name.to_uppercase();
+        match name.as_str() {
+            "WRITE" | "WRITELN" => {
+                let (file, actual_params) = self.file_argument(actual_params)?;
+                if let Some(handle) = file.filter(|&handle| self.files.is_typed(handle)) {
+                    if name == "WRITELN" {
+                        return Err(RuntimeError::new("WRITELN can only write to a TEXT file"));
+                    }
+                    for param in actual_params {
+                        let value = self.eval(param)?;
+                        self.files.write_component(handle, &value)?;
+                    }
+                    return Ok(());
+                }
+                let mut text = String::new();
+                for param in actual_params {
+                    text += &self.write_text(param)?;
+                }
+                if name == "WRITELN" {
+                    text.push('\n');
+                }
+                return match file {
+                    Some(handle) => self.files.write(handle, &text),
+                    None => self
+                        .output
+                        .write_all(text.as_bytes())
+                        .and_then(|_| self.output.flush())
+                        .map_err(|error| {
+                            RuntimeError::new(format!("cannot write the output: {}", error))
+                        }),
+                };
+            }
+            "READ" | "READLN" => {
+                let (file, actual_params) = self.file_argument(actual_params)?;
+                if let Some(handle) = file.filter(|&handle| self.files.is_typed(handle)) {
+                    if name == "READLN" {
+                        return Err(RuntimeError::new("READLN can only read from a TEXT file"));
+                    }
+                    for param in actual_params {
+                        let place = self.place(param)?;
+                        let value = self.files.read_component(handle)?;
+                        self.store(&place, value)?;
+                    }
+                    return Ok(());
+                }
+                for param in actual_params {
+                    let place = self.place(param)?;
+                    let type_name = self.locate(&place, None)?.0.type_name();
+                    let value = self.read_value(type_name, file)?;
+                    self.store(&place, value)?;
+                }
+                if name == "READLN" {
+                    self.input_of(file)?.skip_line()?;
+                }
+                return Ok(());
+            }
+            "ASSIGN" => {
+                let [target, file_name] = actual_params else {
+                    return Err(wrong_arguments("ASSIGN", "2 arguments", actual_params.len()));
+                };
+                let place = self.place(target)?;
+                let (handle, component) = match self.locate(&place, None)? {
+                    (Value::File(handle), Type::Text) => (*handle, None),
+                    (Value::File(handle), Type::File(component)) => {
+                        (*handle, Some(*component.clone()))
+                    }
+                    (value, _) => return Err(value.mismatch("a file variable")),
+                };
+                let file_name = self.eval(file_name)?.as_string()?;
+                let handle = self.files.assign(handle, &file_name, component)?;
+                return self.store(&place, Value::File(Some(handle)));
+            }
+            "RESET" | "REWRITE" | "CLOSE" => {
+                let [file] = actual_params else {
+                    return Err(wrong_arguments(&name, "1 argument", actual_params.len()));
+                };
+                let handle = self.eval(file)?.as_file()?;
+                return match name.as_str() {
+                    "RESET" => self.files.reset(handle),
+                    "REWRITE" => self.files.rewrite(handle),
+                    _ => self.files.close(handle),
+                };
+            }
+            "GETDATE" | "GETTIME" => {
+                if actual_params.len() != 4 {
+                    return Err(wrong_arguments(&name, "4 arguments", actual_params.len()));
+                }
+                let now = DateTime::new(self.clock.now());
+                let parts = match name.as_str() {
+                    "GETDATE" => [now.year, now.month, now.day, now.day_of_week],
+                    _ => [now.hour, now.minute, now.second, now.hundredths],
+                };
+                for (param, part) in actual_params.iter().zip(parts) {
+                    let place = self.place(param)?;
+                    self.store(&place, Value::Integer(part))?;
+                }
+                return Ok(());
+            }
+            "SEEK" => {
+                let [file, index] = actual_params else {
+                    return Err(wrong_arguments("SEEK", "2 arguments", actual_params.len()));
+                };
+                let handle = self.eval(file)?.as_file()?;
+                let index = self.eval(index)?.as_integer()?;
+                return self.files.seek(handle, index);
+            }
+            "HALT" => {
+                let code = match actual_params {
+                    [] => 0,
+                    [code] => self.eval(code)?.as_integer()?,
+                    _ => {
+                        let count = actual_params.len();
+                        return Err(wrong_arguments("HALT", "0 or 1 arguments", count));
+                    }
+                };
+                self.exit_code = Some(code);
+                return Ok(());
+            }
+            "INC" | "DEC" => {
+                if !(1..=2).contains(&actual_params.len()) {
+                    return Err(wrong_arguments(&name, "1 or 2 arguments", actual_params.len()));
+                }
+                let place = self.place(&actual_params[0])?;
+                let amount = match actual_params.get(1) {
+                    Some(amount) => self.eval(amount)?.as_integer()?,
+                    None => 1,
+                };
+                let value = self.locate(&place, None)?.0.clone();
+                let ordinal = if name == "INC" {
+                    value.ordinal()?.checked_add(amount)
+                } else {
+                    value.ordinal()?.checked_sub(amount)
+                };
+                return match ordinal.and_then(|ordinal| value.try_with_ordinal(ordinal)) {
+                    Some(result) => self.store(&place, result),
+                    None => {
+                        Err(RuntimeError::new(format!("{} of {} is out of range", name, value)))
+                    }
+                };
+            }
+            "SETLENGTH" => {
+                let [array, length] = actual_params else {
+                    return Err(wrong_arguments("SETLENGTH", "2 arguments", actual_params.len()));
+                };
+                let place = self.place(array)?;
+                let length = self.eval(length)?.as_integer()?;
+                if !(0..=MAX_ARRAY_LENGTH).contains(&(length as i64)) {
+                    return Err(RuntimeError::new(format!(
+                        "cannot set the length of an array to {}",
+                        length
+                    )));
+                }
+                let (value, type_node) = self.locate(&place, None)?;
+                let Type::DynamicArray(element) = type_node else {
+                    return Err(value.mismatch("a dynamic array"));
+                };
+                // the elements kept keep their values, the new ones start out
+                // like those of a variable
+                let default = element.default_value()?;
+                let Value::Array { elements, .. } = value else { unreachable!() };
+                elements.resize(length as usize, default);
+                return Ok(());
+            }
+            "INSERT" => {
+                let [source, target, index] = actual_params else {
+                    return Err(wrong_arguments("INSERT", "3 arguments", actual_params.len()));
+                };
+                let source = self.eval(source)?.as_string()?;
+                let place = self.place(target)?;
+                let index = self.eval(index)?.as_integer()?;
+                let mut text: Vec<char> =
+                    self.locate(&place, None)?.0.as_string()?.chars().collect();
+                // an index outside of the string inserts at its start or its end
+                let at = (index.max(1) as usize - 1).min(text.len());
+                text.splice(at..at, source.chars());
+                return self.store(&place, Value::String(text.into_iter().collect()));
+            }
+            "STR" => {
+                let [value, target] = actual_params else {
+                    return Err(wrong_arguments("STR", "2 arguments", actual_params.len()));
+                };
+                let text = self.write_text(value)?;
+                let place = self.place(target)?;
+                return self.store(&place, Value::String(text));
+            }
+            "RANDOMIZE" => {
+                let seed = self.random_seed.unwrap_or(self.clock.now().as_nanos() as u64);
+                self.random = Random::new(seed);
+                return Ok(());
+            }
+            "VAL" => {
+                let [text, target, code] = actual_params else {
+                    return Err(wrong_arguments("VAL", "3 arguments", actual_params.len()));
+                };
+                let text = self.eval(text)?.as_string()?;
+                let place = self.place(target)?;
+                let is_real = matches!(self.locate(&place, None)?.0, Value::Real(_));
+                // the number is set to zero when the text does not hold one
+                let (value, error) = match parse_number(&text, is_real) {
+                    Ok(value) => (value, 0),
+                    Err(position) if is_real => (Value::Real(0.0), position as i32),
+                    Err(position) => (Value::Integer(0), position as i32),
+                };
+                self.store(&place, value)?;
+                let place = self.place(code)?;
+                return self.store(&place, Value::Integer(error));
+            }
+            "DELETE" => {
+                let [target, index, count] = actual_params else {
+                    return Err(wrong_arguments("DELETE", "3 arguments", actual_params.len()));
+                };
+                let place = self.place(target)?;
+                let index = self.eval(index)?.as_integer()?;
+                let count = self.eval(count)?.as_integer()?;
+                let mut text: Vec<char> =
+                    self.locate(&place, None)?.0.as_string()?.chars().collect();
+                // an index outside of the string deletes nothing
+                if (1..=text.len() as i64).contains(&(index as i64)) && count > 0 {
+                    let start = index as usize - 1;
+                    text.drain(start..(start + count as usize).min(text.len()));
+                }
+                return self.store(&place, Value::String(text.into_iter().collect()));
+            }
+            _ if actual_params.len() != 1 => {
+                return Err(wrong_arguments(&name, "1 argument", actual_params.len()));
+            }
+            _ => {}
+        }
+        let place = self.place(&actual_params[0])?;
+        match name.as_str() {
+            "NEW" => {
+                let (value, type_node) = self.locate(&place, None)?;
+                let Type::Pointer(target) = type_node.clone() else {
+                    return Err(value.mismatch("a pointer variable"));
+                };
+                let target = self.resolve_type(&target);
+                let address = self.heap.allocate(target)?;
+                self.store(&place, Value::Pointer(Some(address)))
+            }
+            "DISPOSE" => {
+                let pointer = self.locate(&place, None)?.0.as_pointer()?;
+                match pointer {
+                    Some(address) => self.heap.dispose(address),
+                    None => Err(RuntimeError::new("NIL pointer disposed")),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// The text WRITE outputs and STR gives for an argument, right-aligned
+    /// within the field width if one is given and with reals rounded to the
+    /// given number of decimals
+    fn write_text(&mut self, param: &Expr) -> Result<String, RuntimeError> {
+        let Expr::FormattedParam(param) = param else {
+            return Ok(self.eval_text(param)?.to_string());
+        };
+        let value = self.eval_text(&param.expr)?;
+        let width = self.field_width(&param.width, "field width")?;
+        let text = match &param.precision {
+            Some(precision) => {
+                let precision = self.field_width(precision, "number of decimals")?;
+                match value {
+                    Value::Real(r) => format!("{:.*}", precision, r),
+                    _ => {
+                        return Err(RuntimeError::new(format!(
+                            "a precision can only be given for a REAL, not {}",
+                            value.type_name()
+                        )));
+                    }
+                }
+            }
+            None => value.to_string(),
+        };
+        Ok(format!("{:>1$}", text, width))
+    }
+    /// A field width or number of decimals given to WRITE, negative ones
+    /// counting as none
+    fn field_width(&mut self, node: &Expr, what: &str) -> Result<usize, RuntimeError> {
+        let width = self.eval(node)?.as_integer()?;
+        if width > MAX_FIELD_WIDTH {
+            let message = format!("{} {} is larger than {}", what, width, MAX_FIELD_WIDTH);
+            return Err(RuntimeError::new(message));
+        }
+        Ok(width.max(0) as usize)
+    }
+    /// The file the first of the arguments of READ, READLN, WRITE or WRITELN
+    /// names, if it is one, along with the rest of the arguments
+    fn file_argument<'a>(
+        &mut self,
+        actual_params: &'a [Expr],
+    ) -> Result<FileArguments<'a>, RuntimeError> {
+        Ok(match actual_params.split_first() {
+            Some((first, rest)) if self.file_arguments.contains(&node_address(first)) => {
+                (Some(self.eval(first)?.as_file()?), rest)
+            }
+            _ => (None, actual_params),
+        })
+    }
+    /// The text read from the file with the given handle, or from the standard input
+    fn input_of(&mut self, file: Option<usize>) -> Result<&mut Input, RuntimeError> {
+        match file {
+            Some(handle) => self.files.input(handle),
+            None => Ok(&mut self.input),
+        }
+    }
+    /// Read a value for a variable of the given type from a file, or from the standard input
+    fn read_value(&mut self, type_name: &str, file: Option<usize>) -> Result<Value, RuntimeError> {
+        let input = self.input_of(file)?;
+        Ok(match type_name {
+            "INTEGER" => {
+                let number = input.read_number()?;
+                match number.parse::<i32>() {
+                    Ok(i) => Value::Integer(i),
+                    Err(_) => {
+                        let message = format!("invalid INTEGER input {:?}", number);
+                        return Err(RuntimeError::new(message));
+                    }
+                }
+            }
+            "REAL" => {
+                let number = input.read_number()?;
+                match number.parse::<f64>() {
+                    Ok(r) => Value::Real(r),
+                    Err(_) => {
+                        return Err(RuntimeError::new(format!("invalid REAL input {:?}", number)));
+                    }
+                }
+            }
+            "CHAR" => Value::Char(input.read_char()?),
+            "STRING" => Value::String(input.read_string()?),
+            _ => {
+                return Err(RuntimeError::new(format!(
+                    "cannot read a value of type {}",
+                    type_name
+                )));
+            }
+        })
+    }
+
+    fn read(&mut self, place: &Place) -> Result<Value, RuntimeError> {
+        Ok(self.locate(place, None)?.0.clone())
+    }
+    /// The routine a name passed for a procedural parameter or assigned to a
+    /// variable stands for.
+    ///
+    /// The value may outlive the call of the routine declaring it, so the
+    /// frames it reads and writes locals of are kept from then on.
+    fn routine_value(&mut self, var: &Var) -> Value {
+        match self.call_stack.routine_value(&self.routine_name(var, &var.name)) {
+            Some(value) => {
+                if let Value::Routine(Some((_, frame))) = &value {
+                    self.call_stack.capture(*frame);
+                }
+                value
+            }
+            None => panic!("{} is not a routine", var.name),
+        }
+    }
+}
+
+impl Visitor for Interpreter {
+    type StmtOutput = Result<Flow, RuntimeError>;
+    type ExprOutput = Result<Value, RuntimeError>;
+    type DeclOutput = Result<(), RuntimeError>;
+
+    /// Run the units the program needs, each after those it uses, then the
+    /// program itself
+    fn visit_program(&mut self, node: &Program) -> Result<Flow, RuntimeError> {
+        // the frame of a unit stays once its initialization has run, for its
+        // routines and the program and the later units to see
+        let mut access_link = None;
+        for unit in &node.units {
+            let mut record = ActivationRecord::new(&unit.name, 1, access_link);
+            record.interface = Some(unit.interface.iter().map(|name| (name.clone(), ())).collect());
+            record.uses = unit.uses.clone();
+            let index = self.call_stack.push(record);
+            self.call_stack.capture(index);
+            let flow = self.visit_block(&unit.block)?;
+            self.call_stack.pop();
+            // EXIT leaves only the initialization of the unit
+            if !matches!(flow, Flow::Normal | Flow::Exit) {
+                return Ok(flow);
+            }
+            access_link = Some(index);
+        }
+        let record = self.call_stack.peek();
+        record.access_link = access_link;
+        record.uses = node.uses.clone();
+        self.visit_block(&node.block)
+    }
+    fn visit_block(&mut self, node: &Block) -> Result<Flow, RuntimeError> {
+        if self.exit_code.is_some() {
+            return Ok(Flow::Halt);
+        }
+        for declaration in &node.declarations {
+            self.visit_decl(declaration)?;
+        }
+        self.visit_body(node)
+    }
+
+    fn visit_compound(&mut self, node: &Compound) -> Result<Flow, RuntimeError> {
+        self.visit_statements(&node.children)
+    }
+    fn visit_assign(&mut self, node: &Assign) -> Result<Flow, RuntimeError> {
+        let value = self.eval(&node.right)?;
+        // a function which called HALT leaves no result worth storing
+        if self.exit_code.is_some() {
+            return Ok(Flow::Halt);
+        }
+        let place = self.place(&node.left)?;
+        self.store(&place, value)?;
+        Ok(Flow::Normal)
+    }
+    fn visit_procedure_call(&mut self, node: &ProcedureCall) -> Result<Flow, RuntimeError> {
+        let name = self.routine_name(node, &node.name);
+        // ASSERT tells where it failed, unless a routine of the program hides it
+        let is_assert = name.eq_ignore_ascii_case("ASSERT");
+        if is_assert && self.call_stack.lookup_routine(&name)?.is_none() {
+            self.visit_assert(node)?;
+        } else {
+            self.call(&name, &node.actual_params)?;
+        }
+        Ok(self.flow())
+    }
+    fn visit_if(&mut self, node: &If) -> Result<Flow, RuntimeError> {
+        if self.condition(&node.condition)? {
+            self.visit(&node.then_branch)
+        } else if let Some(else_branch) = &node.else_branch {
+            self.visit(else_branch)
+        } else {
+            Ok(Flow::Normal)
+        }
+    }
+    fn visit_while(&mut self, node: &While) -> Result<Flow, RuntimeError> {
+        while self.condition(&node.condition)? {
+            match self.visit(&node.body)? {
+                Flow::Break => break,
+                flow @ (Flow::Exit | Flow::Halt | Flow::Goto(_)) => return Ok(flow),
+                Flow::Normal | Flow::Continue => {}
+            }
+        }
+        Ok(Flow::Normal)
+    }
+    fn visit_for(&mut self, node: &For) -> Result<Flow, RuntimeError> {
+        // the bounds are evaluated only once, before the first iteration
+        let start = self.eval(&node.start)?;
+        let end = self.eval(&node.end)?.ordinal()?;
+        let place = self.variable_place(&node.variable.name)?;
+        let mut i = start.ordinal()?;
+        while (!node.downto && i <= end) || (node.downto && i >= end) {
+            self.store(&place, start.with_ordinal(i)?)?;
+            match self.visit(&node.body)? {
+                Flow::Break => break,
+                flow @ (Flow::Exit | Flow::Halt | Flow::Goto(_)) => return Ok(flow),
+                Flow::Normal | Flow::Continue => {}
+            }
+            // stepping past the last value could overflow at MAXINT or -MAXINT-1
+            if i == end {
+                break;
+            }
+            if node.downto { i -= 1 } else { i += 1 }
+        }
+        Ok(Flow::Normal)
+    }
+    fn visit_repeat(&mut self, node: &Repeat) -> Result<Flow, RuntimeError> {
+        // the body always runs at least once; CONTINUE goes on with the condition
+        loop {
+            match self.visit_statements(&node.children)? {
+                Flow::Break => break,
+                flow @ (Flow::Exit | Flow::Halt | Flow::Goto(_)) => return Ok(flow),
+                Flow::Normal | Flow::Continue => {}
+            }
+            if self.condition(&node.condition)? {
+                break;
+            }
+        }
+        Ok(Flow::Normal)
+    }
+    fn visit_case(&mut self, node: &Case) -> Result<Flow, RuntimeError> {
+        let selector = self.eval(&node.selector)?.ordinal()?;
+        for arm in &node.arms {
+            for label in &arm.labels {
+                let low = self.eval(&label.low)?.ordinal()?;
+                let high = match &label.high {
+                    Some(high) => self.eval(high)?.ordinal()?,
+                    None => low,
+                };
+                if low <= selector && selector <= high {
+                    return self.visit(&arm.body);
+                }
+            }
+        }
+        match &node.else_branch {
+            Some(else_branch) => self.visit_statements(else_branch),
+            None => Ok(Flow::Normal),
+        }
+    }
+    fn visit_with(&mut self, node: &With) -> Result<Flow, RuntimeError> {
+        // the record is picked once, before running the body, even if the
+        // body changes the indices or pointers leading to it
+        let place = self.place(&node.record)?;
+        self.with_records.push((self.call_stack.depth(), place));
+        let flow = self.visit(&node.body)?;
+        self.with_records.pop();
+        Ok(flow)
+    }
+    fn visit_break(&mut self, _: &Span) -> Result<Flow, RuntimeError> {
+        Ok(Flow::Break)
+    }
+    fn visit_continue(&mut self, _: &Span) -> Result<Flow, RuntimeError> {
+        Ok(Flow::Continue)
+    }
+    fn visit_exit(&mut self, node: &Exit) -> Result<Flow, RuntimeError> {
+        if let Some(result) = &node.result {
+            let value = self.eval(result)?;
+            self.set_result(value)?;
+        }
+        Ok(Flow::Exit)
+    }
+    fn visit_goto(&mut self, node: &Goto) -> Result<Flow, RuntimeError> {
+        Ok(Flow::Goto(node.label.clone()))
+    }
+    fn visit_labeled(&mut self, node: &Labeled) -> Result<Flow, RuntimeError> {
+        self.visit(&node.statement)
+    }
+    fn visit_try_except(&mut self, node: &TryExcept) -> Result<Flow, RuntimeError> {
+        let error = match self.guarded(&node.body) {
+            Ok(flow) => return Ok(flow),
+            Err(error) => error,
+        };
+        // a handled error is raised again where the handler raises it
+        self.error_span = None;
+        self.handling.push(error);
+        // every exception is of the one class there is, so the first handler handles it
+        let result = match node.handlers.first() {
+            Some(handler) => self.visit_exception_handler(handler),
+            None => self.guarded(node.else_branch.as_deref().unwrap_or_default()),
+        };
+        self.handling.pop();
+        result
+    }
+    fn visit_try_finally(&mut self, node: &TryFinally) -> Result<Flow, RuntimeError> {
+        let result = self.guarded(&node.body);
+        // HALT stops the program right away
+        if let Ok(Flow::Halt) = result {
+            return result;
+        }
+        // an error in the FINALLY part is told about where it happened
+        let error_span = self.error_span.take();
+        // leaving the FINALLY part some other way forgets how the body was left
+        match self.visit_statements(&node.finally)? {
+            Flow::Normal => {
+                self.error_span = error_span;
+                result
+            }
+            finally_flow => Ok(finally_flow),
+        }
+    }
+    fn visit_raise(&mut self, node: &Raise) -> Result<Flow, RuntimeError> {
+        Err(match &node.message {
+            Some(message) => RuntimeError::Exception(self.eval(message)?.as_string()?),
+            None => self.handling.last().unwrap().clone(),
+        })
+    }
+    /// The empty statement does nothing
+    fn visit_no_op(&mut self, _: &Span) -> Result<Flow, RuntimeError> {
+        Ok(Flow::Normal)
+    }
+
+    fn visit_bin_op(&mut self, node: &BinOp) -> Result<Value, RuntimeError> {
+        let left = self.eval_text(&node.left)?;
+        // `{$B+}` asks for complete evaluation of AND and OR, `{$B-}` for short-circuit
+        let complete = self.switch('B', Some(&node.span.start));
+        if complete.map_or(self.short_circuit, |complete| !complete) {
+            match (node.op_type, &left) {
+                (OpType::AND, Value::Boolean(false)) => return Ok(Value::Boolean(false)),
+                (OpType::OR, Value::Boolean(true)) => return Ok(Value::Boolean(true)),
+                _ => {}
+            }
+        }
+        let right = self.eval_text(&node.right)?;
+        self.check_packed_length(node, &left, &right)?;
+        binary_operation(node.op_type, &left, &right, self.parser.dialect())
+    }
+    fn visit_literal(&mut self, node: &Literal) -> Result<Value, RuntimeError> {
+        Ok(node.value.clone())
+    }
+    fn visit_unary_op(&mut self, node: &UnaryOp) -> Result<Value, RuntimeError> {
+        unary_operation(node.op_type, &self.eval(&node.expr)?)
+    }
+    fn visit_var(&mut self, node: &Var) -> Result<Value, RuntimeError> {
+        if let Some(place) = self.with_field(&node.name)? {
+            return self.read(&place);
+        }
+        if self.routine_values.contains(&node_address(node)) {
+            return Ok(self.routine_value(node));
+        }
+        // naming an overloaded function calls the overload without parameters
+        if let Some(name) = self.routine_names.get(&node_address(node)) {
+            return Ok(self.call(&name.clone(), &[])?.unwrap());
+        }
+        if let Some(address) = self.call_stack.lookup_static(&node.name) {
+            return Ok(self.heap.get(address)?.clone());
+        }
+        for i in self.call_stack.visible_frames(&node.name) {
+            let record = self.call_stack.get(i);
+            if let Some(value) = record.members.get(&node.name) {
+                // naming a variable holding a function calls the function
+                if let Value::Routine(_) = value {
+                    return Ok(self.call(&node.name, &[])?.unwrap());
+                }
+                return Ok(value.clone());
+            }
+            if let Some(value) = record.constants.get(&node.name) {
+                return Ok(value.clone());
+            }
+            if let Some(value) = &record.return_value
+                && same_name(&node.name, RESULT)
+                && self.parser.dialect().allows(Feature::ResultVariable)
+            {
+                return Ok(value.clone());
+            }
+            // naming a function, even inside itself, calls it
+            if record.routines.contains_key(&node.name) {
+                return Ok(self.call(&node.name, &[])?.unwrap());
+            }
+        }
+        if standard_function(&node.name).is_some() {
+            return self.call_standard_function(&node.name, &[]);
+        }
+        panic!("undeclared variable {}", node.name)
+    }
+    /// Read an element, walking down to it instead of copying the whole variable
+    fn visit_index(&mut self, node: &Index) -> Result<Value, RuntimeError> {
+        let place = self.index_place(node)?;
+        self.read(&place)
+    }
+    fn visit_field(&mut self, node: &Field) -> Result<Value, RuntimeError> {
+        let place = self.field_place(node)?;
+        self.read(&place)
+    }
+    fn visit_deref(&mut self, node: &Deref) -> Result<Value, RuntimeError> {
+        let place = self.deref_place(node)?;
+        self.read(&place)
+    }
+    /// Build a set from elements computed at runtime. The element type of
+    /// `[]` is unknown until it meets another set or is stored in a variable.
+    fn visit_set_constructor(&mut self, node: &SetConstructor) -> Result<Value, RuntimeError> {
+        let mut set = Set::new(None);
+        for element in &node.elements {
+            let low = self.eval(&element.low)?;
+            match &element.high {
+                Some(high) => insert_range(&mut set, &low, &self.eval(high)?)?,
+                None => set.insert(&low)?,
+            }
+        }
+        Ok(Value::Set(set))
+    }
+    fn visit_function_call(&mut self, node: &FunctionCall) -> Result<Value, RuntimeError> {
+        let name = self.routine_name(node, &node.name);
+        Ok(self.call(&name, &node.actual_params)?.unwrap())
+    }
+    fn visit_formatted_param(&mut self, _: &FormattedParam) -> Result<Value, RuntimeError> {
+        panic!("a field width can only be given to WRITE, WRITELN and STR")
+    }
+
+    /// The labeled statements are found when jumping to them
+    fn visit_label_decl(&mut self, _: &LabelDecl) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+    fn visit_const_decl(&mut self, node: &ConstDecl) -> Result<(), RuntimeError> {
+        self.call_stack.peek().constants.insert(node.name.clone(), node.constant.value.clone());
+        Ok(())
+    }
+    fn visit_typed_const_decl(&mut self, decl: &TypedConstDecl) -> Result<(), RuntimeError> {
+        let key = node_address(decl);
+        let address = match self.static_cells.get(&key) {
+            Some(address) => *address,
+            None => {
+                let type_node = self.resolve_type(&decl.type_node);
+                let value = type_node.initial_value(&decl.initializer)?;
+                let address = self.heap.allocate(type_node)?;
+                *self.heap.get_mut(address)?.0 = value;
+                self.static_cells.insert(key, address);
+                address
+            }
+        };
+        self.call_stack.peek().statics.insert(decl.name.clone(), address);
+        Ok(())
+    }
+    fn visit_type_decl(&mut self, node: &TypeDecl) -> Result<(), RuntimeError> {
+        let type_node = self.resolve_type(&node.type_node);
+        self.call_stack.peek().types.insert(node.name.clone(), type_node);
+        Ok(())
+    }
+    fn visit_var_decl(&mut self, node: &VarDecl) -> Result<(), RuntimeError> {
+        let type_node = self.resolve_type(&node.type_node);
+        let value = type_node.default_value()?;
+        let record = self.call_stack.peek();
+        record.members.insert(node.var_node.name.clone(), value);
+        record.member_types.insert(node.var_node.name.clone(), type_node);
+        Ok(())
+    }
+    /// Forward routines are run from their later definitions
+    fn visit_forward_decl(&mut self, _: &ForwardDecl) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+    fn visit_procedure_decl(&mut self, node: &Rc<ProcedureDecl>) -> Result<(), RuntimeError> {
+        let name = self.routine_name(node.as_ref(), &node.name);
+        let routine = Routine::Procedure(Rc::clone(node));
+        self.call_stack.peek().routines.insert(name, routine);
+        Ok(())
+    }
+    fn visit_function_decl(&mut self, node: &Rc<FunctionDecl>) -> Result<(), RuntimeError> {
+        let name = self.routine_name(node.as_ref(), &node.name);
+        let routine = Routine::Function(Rc::clone(node));
+        self.call_stack.peek().routines.insert(name, routine);
+        Ok(())
+    }
+}
+
+/// Fail with a range error at `position` unless a value lies in the ranges of its type
+fn check_range(
+    type_node: &Type,
+    value: &Value,
+    position: Option<&Position>,
+) -> Result<(), RuntimeError> {
+    type_node.check_range(value).map_err(|error| match error {
+        RuntimeError::Range(mut error) => {
+            error.position = position.cloned();
+            RuntimeError::Range(error)
+        }
+        error => error,
+    })
+}
+
+/// Fail with a range error at `position` when an index lies outside of the bounds of an array
+fn check_index(
+    type_node: &Type,
+    array: &Value,
+    index: i32,
+    position: Option<&Position>,
+) -> Result<(), RuntimeError> {
+    let Value::Array { low, elements } = array else {
+        return Ok(());
+    };
+    let high = *low as i64 + elements.len() as i64 - 1;
+    if (*low as i64..=high).contains(&(index as i64)) {
+        return Ok(());
+    }
+    // the bounds of a static array are told as values of its index type
+    let (low, high) = match type_node {
+        Type::Array { index: index_type, .. } => index_type.bounds(),
+        _ => (Value::Integer(*low), Value::Integer(high as i32)),
+    };
+    let value = low.try_with_ordinal(index).unwrap_or(Value::Integer(index));
+    Err(RuntimeError::from(RangeError { value, low, high, position: position.cloned() }))
+}
+
+/// The error of a standard routine called with the wrong number of
+/// arguments, `expected` telling how many it takes, like `1 or 2 arguments`
+fn wrong_arguments(name: &str, expected: &str, count: usize) -> RuntimeError {
+    RuntimeError::new(format!("{} expects {} but got {}", name, expected, count))
+}
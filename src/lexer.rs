@@ -0,0 +1,622 @@
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::diagnostic::Sources;
+use crate::dialect::{Dialect, Feature};
+use crate::directive::{Directive, Directives};
+use crate::error::LexError;
+use crate::token::{OpType, Position, Span, Token, keyword};
+
+/// How deep `{$I}` may nest, which stops a file that includes itself
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// A source whose reading is suspended while a file it includes is read
+struct Includer {
+    file: Option<Rc<str>>,
+    text: String,
+    line_starts: Vec<usize>,
+    pos: usize,
+}
+
+pub struct Lexer {
+    text: String,
+    /// the offsets in bytes of the lines of the text being read
+    line_starts: Vec<usize>,
+    /// the offset of the position in the text whose column was found last,
+    /// along with that column, which later positions on its line are
+    /// counted on from instead of from the start of the line
+    last_column: Cell<(usize, usize)>,
+    /// the offset in bytes of the current character in the text being read
+    pos: usize,
+    current_char: Option<char>,
+    /// where the token returned last starts
+    token_start: usize,
+    /// the handler of the directives met so far
+    directives: Directives,
+    /// where the first directive of the source itself is, the files and
+    /// units it includes aside
+    first_directive: Option<Position>,
+    /// the file being read, `None` while reading a program given as text
+    file: Option<Rc<str>>,
+    /// how many bytes of the token stream come before the text being read
+    base: usize,
+    /// the sources including the one being read, innermost last
+    includers: Vec<Includer>,
+    /// the dialect of the program, deciding which extensions it may use
+    dialect: Dialect,
+    /// the text of the program and of the files read so far
+    sources: Sources,
+    /// the values of the tokens read so far, which the tokens of the same
+    /// value share
+    symbols: HashSet<Rc<str>>,
+}
+
+impl Lexer {
+    pub fn new(text: String) -> Lexer {
+        // the `#!` line making a script executable is left for the shell
+        let start = if text.starts_with("#!") { text.find('\n').unwrap_or(text.len()) } else { 0 };
+        Lexer {
+            pos: start,
+            current_char: text[start..].chars().next(),
+            sources: Sources::new(&text),
+            line_starts: line_starts(&text),
+            last_column: Cell::new((0, 1)),
+            text,
+            token_start: start,
+            directives: Directives::new(),
+            first_directive: None,
+            file: None,
+            base: 0,
+            includers: Vec::new(),
+            dialect: Dialect::default(),
+            symbols: HashSet::new(),
+        }
+    }
+    /// Where the token returned last starts
+    pub fn position(&self) -> Position {
+        self.position_of(self.token_start)
+    }
+    /// The text of the program and of the files it included so far, which
+    /// the errors found in them are shown along with
+    pub fn sources(&self) -> &Sources {
+        &self.sources
+    }
+    /// What the directives met so far ask for
+    pub fn directives(&self) -> &Directives {
+        &self.directives
+    }
+    /// Where the first directive of the source is, leaving out those of the
+    /// files and units it includes
+    pub fn first_directive(&self) -> Option<&Position> {
+        self.first_directive.as_ref()
+    }
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+    /// Choose the dialect of the program before lexing the source
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+    }
+    /// Define a symbol for conditional compilation before lexing the source
+    pub fn define(&mut self, symbol: &str) {
+        self.directives.define(symbol);
+    }
+    /// Name the file the source was read from before lexing it, which the
+    /// positions in the source tell and the files it names are found next to
+    pub fn set_file(&mut self, name: &str) {
+        let file: Rc<str> = Rc::from(name);
+        self.sources.add_file(file.clone(), &self.text);
+        self.file = Some(file);
+    }
+    fn advance(&mut self) {
+        if let Some(ch) = self.current_char {
+            self.pos += ch.len_utf8();
+        }
+        self.current_char = self.text[self.pos..].chars().next();
+    }
+    fn peek(&self) -> Option<char> {
+        self.peek_at(1)
+    }
+    /// The character `offset` characters after the current one
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.text[self.pos..].chars().nth(offset)
+    }
+    fn skip_space(&mut self) {
+        while let Some(ch) = self.current_char && ch == ' ' {
+            self.advance()
+        }
+    }
+    /// Skip a comment opened at the current position by `{` or `(*`, up to
+    /// and including the matching `close`
+    fn skip_comment(&mut self, close: &str) -> Result<(), LexError> {
+        let start = self.pos;
+        let mut close_chars = close.chars();
+        let (first, second) = (close_chars.next(), close_chars.next());
+        // step over the opening `{` or `(*`
+        self.advance();
+        if second.is_some() {
+            self.advance();
+        }
+        loop {
+            match self.current_char {
+                None => return Err(LexError::UnterminatedComment(self.position_of(start))),
+                ch if ch == first && (second.is_none() || self.peek() == second) => {
+                    self.advance();
+                    if second.is_some() {
+                        self.advance();
+                    }
+                    return Ok(());
+                }
+                _ => self.advance(),
+            }
+        }
+    }
+    /// Read a comment starting with `{$`, which is a directive to the
+    /// compiler, and feed it to the directive handler
+    fn directive(&mut self) -> Result<(), LexError> {
+        let start = self.pos;
+        self.skip_comment("}")?;
+        let text = String::from(&self.text[start + 2..self.pos - 1]);
+        let position = self.position_of(start);
+        if self.includers.is_empty() && self.first_directive.is_none() {
+            self.first_directive = Some(position.clone());
+        }
+        let directive = Directive::parse(&text).map_err(|message| LexError::Directive {
+            message,
+            position: position.clone(),
+        })?;
+        match directive {
+            Directive::Parameter { name, argument }
+                if (name == "I" || name == "INCLUDE") && !self.directives.skipping() =>
+            {
+                self.include(&argument, &position)
+            }
+            directive => self
+                .directives
+                .handle(directive, &position)
+                .map_err(|message| LexError::Directive { message, position }),
+        }
+    }
+    /// Splice the file named by `{$I name}` into the token stream, going on
+    /// with the rest of the current source once the file ends. The name is
+    /// taken relative to the directory of the file including it.
+    fn include(&mut self, name: &str, position: &Position) -> Result<(), LexError> {
+        let name = name.trim_matches('\'');
+        if name.is_empty() {
+            let message = String::from("{$I} expects the name of a file");
+            return Err(LexError::Include { message, position: position.clone() });
+        }
+        self.splice(self.path(name), position)
+    }
+    /// Splice the file of the unit named by the token returned last into
+    /// the token stream, right after that token. The file is named after the
+    /// unit, as is or in lower case, with a `.pas` extension.
+    pub fn include_unit(&mut self, name: &str) -> Result<(), LexError> {
+        let position = self.position_of(self.token_start);
+        let file_names = [format!("{}.pas", name), format!("{}.pas", name.to_lowercase())];
+        let Some(path) = file_names.iter().map(|file| self.path(file)).find(|path| path.exists())
+        else {
+            let message =
+                format!("cannot find unit {}, there being no file {}", name, file_names[0]);
+            return Err(LexError::Include { message, position });
+        };
+        self.splice(path, &position)
+    }
+    /// How many files including one another are being read
+    pub fn include_depth(&self) -> usize {
+        self.includers.len()
+    }
+    /// The path of a file named by the source being read, relative to the
+    /// directory of the file it lies in
+    fn path(&self, name: &str) -> PathBuf {
+        match self.file.as_deref().and_then(|file| Path::new(file).parent()) {
+            Some(directory) => directory.join(name),
+            None => PathBuf::from(name),
+        }
+    }
+    /// Go on reading the file at `path`, then the rest of the current source
+    fn splice(&mut self, path: PathBuf, position: &Position) -> Result<(), LexError> {
+        let error = |message| LexError::Include { message, position: position.clone() };
+        if self.includers.len() >= MAX_INCLUDE_DEPTH {
+            let message = String::from("files are included too deeply, maybe including themselves");
+            return Err(error(message));
+        }
+        let text = fs::read_to_string(&path).map_err(|reason| {
+            error(format!("cannot include {}: {}", path.display(), reason))
+        })?;
+        let file: Rc<str> = Rc::from(path.to_string_lossy());
+        self.sources.add_file(file.clone(), &text);
+        self.includers.push(Includer {
+            file: self.file.replace(file),
+            line_starts: std::mem::replace(&mut self.line_starts, line_starts(&text)),
+            text: std::mem::replace(&mut self.text, text),
+            pos: self.pos,
+        });
+        self.last_column.set((0, 1));
+        self.base += self.pos;
+        self.pos = 0;
+        self.current_char = self.text.chars().next();
+        Ok(())
+    }
+    /// Go back to the source including the file that has just ended, or
+    /// return false when the program itself has ended
+    fn leave_include(&mut self) -> bool {
+        let Some(includer) = self.includers.pop() else {
+            return false;
+        };
+        // the rest of the includer comes after the whole included file
+        let end = self.base + self.text.len();
+        self.file = includer.file;
+        self.text = includer.text;
+        self.line_starts = includer.line_starts;
+        self.last_column.set((0, 1));
+        self.pos = includer.pos;
+        self.base = end - includer.pos;
+        self.current_char = self.text[self.pos..].chars().next();
+        true
+    }
+    /// The token read from where the one being read starts up to the current character
+    fn token(&mut self, op_type: OpType, value: &str) -> Token {
+        let value = intern(&mut self.symbols, value);
+        Token::new(op_type, value, self.span_of(self.token_start))
+    }
+    /// The token whose value is the source it was read from
+    fn lexeme(&mut self, op_type: OpType) -> Token {
+        let value = intern(&mut self.symbols, &self.text[self.token_start..self.pos]);
+        Token::new(op_type, value, self.span_of(self.token_start))
+    }
+    /// The span from a position in the text being read up to the current character
+    fn span_of(&self, start: usize) -> Span {
+        Span { start: self.position_of(start), bytes: start..self.pos }
+    }
+    /// Where a position in the text being read lies in the token stream
+    fn position_of(&self, pos: usize) -> Position {
+        let (line, column) = self.line_and_column(pos);
+        Position { offset: self.base + pos, line, column, file: self.file.clone() }
+    }
+    /// The line and column, both counted from 1, of a position in the text
+    fn line_and_column(&self, pos: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&line_start| line_start <= pos);
+        let line_start = self.line_starts[line - 1];
+        let column = match self.last_column.get() {
+            (last, column) if (line_start..=pos).contains(&last) => {
+                column + self.text[last..pos].chars().count()
+            }
+            _ => self.text[line_start..pos].chars().count() + 1,
+        };
+        self.last_column.set((pos, column));
+        (line, column)
+    }
+    /// Handle integer and real literals like `42`, `1.5`, `1.5e3` and `2E-7`
+    fn number(&mut self) -> Result<Token, LexError> {
+        let start = self.pos;
+        self.digits();
+        let mut is_real = false;
+        // a dot only makes a real if a digit follows it, so `1..5` is still a range
+        if self.current_char == Some('.') && self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+            self.advance();
+            self.digits();
+            is_real = true;
+        }
+        // likewise an `e` only starts an exponent if digits follow it, possibly after a sign
+        let exponent_digit = match self.peek() {
+            Some('+' | '-') => self.peek_at(2),
+            next => next,
+        };
+        if matches!(self.current_char, Some('e' | 'E'))
+            && exponent_digit.is_some_and(|ch| ch.is_ascii_digit())
+        {
+            self.advance();
+            if let Some('+' | '-') = self.current_char {
+                self.advance();
+            }
+            self.digits();
+            is_real = true;
+        }
+        if !is_real {
+            return self.integer(10, start);
+        }
+        let literal = &self.text[start..self.pos];
+        if !literal.parse::<f64>().unwrap().is_finite() {
+            let (literal, position) = (String::from(literal), self.position_of(start));
+            return Err(LexError::LiteralOutOfRange { literal, position });
+        }
+        Ok(self.lexeme(OpType::REAL_CONST))
+    }
+    fn digits(&mut self) {
+        while let Some(ch) = self.current_char && ch.is_ascii_digit() {
+            self.advance();
+        }
+    }
+    /// Handle `$FF` hexadecimal and `%1010` binary integer literals
+    fn radix_number(&mut self, radix: u32) -> Result<Token, LexError> {
+        let start = self.pos;
+        if !self.dialect.allows(Feature::RadixLiterals) {
+            self.advance();
+            return Err(LexError::Unsupported {
+                feature: Feature::RadixLiterals,
+                dialect: self.dialect,
+                position: self.position_of(start),
+            });
+        }
+        self.advance();
+        while let Some(ch) = self.current_char && ch.is_digit(radix) {
+            self.advance();
+        }
+        if self.pos == start + 1 {
+            return Err(LexError::MissingDigits(self.position_of(start)));
+        }
+        self.integer(radix, start)
+    }
+    /// The token of an integer literal starting at `start`, the prefix of
+    /// its radix included, carrying its value in decimal
+    fn integer(&mut self, radix: u32, start: usize) -> Result<Token, LexError> {
+        let digits = &self.text[start + usize::from(radix != 10)..self.pos];
+        match i32::from_str_radix(digits, radix) {
+            // a decimal literal without leading zeros is its own value
+            Ok(_) if radix == 10 && (digits.len() == 1 || !digits.starts_with('0')) => {
+                Ok(self.lexeme(OpType::INTEGER_CONST))
+            }
+            Ok(value) => Ok(self.token(OpType::INTEGER_CONST, &value.to_string())),
+            Err(_) => {
+                let position = self.position_of(start);
+                let literal = String::from(&self.text[start..self.pos]);
+                Err(LexError::LiteralOutOfRange { literal, position })
+            }
+        }
+    }
+    /// Handle a quoted literal like `'it''s'`, where a doubled quote stands for one quote.
+    ///
+    /// A literal holding exactly one character is a CHAR constant, and `''`
+    /// is the empty string. A literal has to end on the line it starts on.
+    fn string(&mut self) -> Result<Token, LexError> {
+        let start = self.pos;
+        let mut doubled_quotes = false;
+        self.advance();
+        loop {
+            match self.current_char {
+                Some('\'') if self.peek() == Some('\'') => {
+                    doubled_quotes = true;
+                    self.advance();
+                    self.advance();
+                }
+                Some('\'') => {
+                    self.advance();
+                    break;
+                }
+                None | Some('\n') => {
+                    return Err(LexError::UnterminatedString(self.position_of(start)));
+                }
+                Some(_) => self.advance(),
+            }
+        }
+        let quoted = &self.text[start + 1..self.pos - 1];
+        let value = match doubled_quotes {
+            true => intern(&mut self.symbols, &quoted.replace("''", "'")),
+            false => intern(&mut self.symbols, quoted),
+        };
+        let op_type =
+            if value.chars().count() == 1 { OpType::CHAR_CONST } else { OpType::STRING_CONST };
+        Ok(Token::new(op_type, value, self.span_of(start)))
+    }
+    /// Handle identifiers and reserved keywords, an identifier being a
+    /// letter or an underscore followed by letters, digits and underscores
+    fn id(&mut self) -> Token {
+        while let Some(ch) = self.current_char && (ch.is_ascii_alphanumeric() || ch == '_') {
+            self.advance();
+        }
+        // the keywords of an extension the dialect lacks are free for names
+        let op_type = keyword(&self.text[self.token_start..self.pos])
+            .filter(|&op_type| {
+                Feature::of_keyword(op_type).is_none_or(|feature| self.dialect.allows(feature))
+            })
+            .unwrap_or(OpType::ID);
+        self.lexeme(op_type)
+    }
+
+    pub fn get_next_token(&mut self) -> Result<Token, LexError> {
+        loop {
+            let Some(ch) = self.current_char else {
+                // the end of an included file goes back to the source including it
+                if self.leave_include() {
+                    continue;
+                }
+                break;
+            };
+            self.token_start = self.pos;
+            // println!("next token ch {}", ch);
+            // a region turned off by conditional directives is skipped, but
+            // for the directives telling where it ends
+            if self.directives.skipping() {
+                match ch {
+                    '{' if self.peek() == Some('$') => self.directive()?,
+                    '{' => self.skip_comment("}")?,
+                    '(' if self.peek() == Some('*') => self.skip_comment("*)")?,
+                    // what looks like a directive inside a string is not one
+                    '\'' => {
+                        self.advance();
+                        while !matches!(self.current_char, None | Some('\'' | '\n')) {
+                            self.advance();
+                        }
+                        if self.current_char == Some('\'') {
+                            self.advance();
+                        }
+                    }
+                    _ => self.advance(),
+                }
+                continue;
+            }
+            if ch.is_ascii_digit() {
+                return self.number();
+            }
+            if ch.is_ascii_alphabetic() || ch == '_' {
+                return Ok(self.id());
+            }
+            match ch {
+            ' ' => {
+                self.skip_space();
+                continue;
+            }
+            '\t' | '\r' | '\n' => {
+                self.advance();
+                continue;
+            }
+            '{' if self.peek() == Some('$') => {
+                self.directive()?;
+                continue;
+            }
+            '{' => {
+                self.skip_comment("}")?;
+                continue;
+            }
+            '(' if self.peek() == Some('*') => {
+                self.skip_comment("*)")?;
+                continue;
+            }
+            ':' if self.peek() == Some('=') => {
+                self.advance();
+                self.advance();
+                return Ok(self.token(OpType::ASSIGN, ":="))
+            },
+            ':' => {
+                self.advance();
+                return Ok(self.token(OpType::COLON, ":"))
+            },
+            ';' => {
+                self.advance();
+                return Ok(self.token(OpType::SEMI, ";"))
+            },
+            ',' => {
+                self.advance();
+                return Ok(self.token(OpType::COMMA, ","))
+            },
+            '.' if self.peek() == Some('.') => {
+                self.advance();
+                self.advance();
+                return Ok(self.token(OpType::RANGE, ".."))
+            },
+            '.' => {
+                self.advance();
+                return Ok(self.token(OpType::DOT, "."))
+            },
+            '\'' => {
+                return self.string()
+            },
+            '$' => {
+                return self.radix_number(16)
+            },
+            '%' => {
+                return self.radix_number(2)
+            },
+            '=' => {
+                self.advance();
+                return Ok(self.token(OpType::EQUAL, "="))
+            },
+            '<' if self.peek() == Some('>') => {
+                self.advance();
+                self.advance();
+                return Ok(self.token(OpType::NOT_EQUAL, "<>"))
+            },
+            '<' if self.peek() == Some('=') => {
+                self.advance();
+                self.advance();
+                return Ok(self.token(OpType::LESS_EQUAL, "<="))
+            },
+            '<' => {
+                self.advance();
+                return Ok(self.token(OpType::LESS, "<"))
+            },
+            '>' if self.peek() == Some('=') => {
+                self.advance();
+                self.advance();
+                return Ok(self.token(OpType::GREATER_EQUAL, ">="))
+            },
+            '>' => {
+                self.advance();
+                return Ok(self.token(OpType::GREATER, ">"))
+            },
+            '+' => {
+                self.advance();
+                return Ok(self.token(OpType::PLUS, "+"))
+            },
+            '-' => {
+                self.advance();
+                return Ok(self.token(OpType::MINUS, "-"))
+            },
+            '*' => {
+                self.advance();
+                return Ok(self.token(OpType::MUL, "*"))
+            },
+            '/' => {
+                self.advance();
+                return Ok(self.token(OpType::FLOAT_DIV, "/"))
+            },
+            '(' => {
+                self.advance();
+                return Ok(self.token(OpType::LPAREN, "("))
+            },
+            ')' => {
+                self.advance();
+                return Ok(self.token(OpType::RPAREN, ")"))
+            },
+            '[' => {
+                self.advance();
+                return Ok(self.token(OpType::LBRACKET, "["))
+            },
+            ']' => {
+                self.advance();
+                return Ok(self.token(OpType::RBRACKET, "]"))
+            },
+            '^' => {
+                self.advance();
+                return Ok(self.token(OpType::CARET, "^"))
+            },
+            _ => {
+                let position = self.position_of(self.token_start);
+                // skipped, so that the parser can go on after reporting it
+                self.advance();
+                return Err(LexError::UnknownCharacter { ch, position });
+            }
+            }
+        }
+        let span = self.span_of(self.pos);
+        if let Err(message) = self.directives.finish() {
+            return Err(LexError::Directive { message, position: span.start });
+        }
+        Ok(Token::new(OpType::EOF, intern(&mut self.symbols, ""), span))
+    }
+}
+
+/// The tokens of the source up to its end, which ends the iteration rather
+/// than being given as an EOF token, along with the errors found among them.
+/// The lexer goes on after an error from past the text it could not read.
+impl Iterator for Lexer {
+    type Item = Result<Token, LexError>;
+
+    fn next(&mut self) -> Option<Result<Token, LexError>> {
+        match self.get_next_token() {
+            Ok(token) if token.op_type == OpType::EOF => None,
+            result => Some(result),
+        }
+    }
+}
+
+/// The offsets in bytes at which the lines of a text start
+fn line_starts(text: &str) -> Vec<usize> {
+    let breaks = text.match_indices('\n').map(|(offset, _)| offset + 1);
+    std::iter::once(0).chain(breaks).collect()
+}
+
+/// The value among those read so far which is spelled like `text`, or a new
+/// one if there is none
+fn intern(symbols: &mut HashSet<Rc<str>>, text: &str) -> Rc<str> {
+    if let Some(symbol) = symbols.get(text) {
+        return Rc::clone(symbol);
+    }
+    let symbol: Rc<str> = Rc::from(text);
+    symbols.insert(Rc::clone(&symbol));
+    symbol
+}
@@ -0,0 +1,96 @@
+//! An interpreter of Pascal programs, which an embedder can run in one call
+//! with `run_program`, or drive stage by stage with the `Lexer`, `Parser`,
+//! `SemanticAnalyzer` and `Interpreter` to choose the dialect, the files,
+//! the environment and the clock a program sees. `Sources::render` shows an
+//! error along with the line of the program it was found in, and `print`
+//! lays out the tree of a program as its source again.
+
+mod ast;
+mod builtin;
+mod call_stack;
+mod clock;
+mod diagnostic;
+mod dialect;
+mod directive;
+mod environment;
+mod error;
+mod file;
+mod heap;
+mod input;
+mod interpreter;
+mod lexer;
+mod names;
+mod operation;
+mod parser;
+mod printer;
+mod random;
+mod semantic_analyzer;
+mod set;
+mod symbol;
+mod token;
+mod types;
+mod value;
+mod visitor;
+
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+pub use ast::{Block, Decl, Expr, Program, Routine, Stmt};
+pub use call_stack::ActivationRecord;
+pub use clock::{Clock, FixedClock, SystemClock};
+pub use diagnostic::Sources;
+pub use dialect::Dialect;
+pub use environment::{Environment, ProcessEnvironment};
+pub use error::{Error, LexError, ParseError, RuntimeError, SemanticError};
+pub use file::{FileSystem, HostFileSystem, NoFileSystem};
+pub use interpreter::Interpreter;
+pub use lexer::Lexer;
+pub use parser::Parser;
+pub use printer::print;
+pub use semantic_analyzer::SemanticAnalyzer;
+pub use token::{OpType, Position, Span, Token};
+pub use types::Type;
+pub use value::Value;
+
+/// What a program run to its end by `run_program` left behind
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+    /// the text the program wrote with WRITE and WRITELN
+    pub text: String,
+    /// the code the program was stopped with by HALT
+    pub exit_code: Option<i32>,
+}
+
+/// The text written by a program, kept where its runner can take it back
+struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The reserved words and the names of the routines every program can call
+/// without declaring them, in upper case
+pub fn predefined_names() -> Vec<&'static str> {
+    token::keywords().chain(builtin::routine_names()).collect()
+}
+
+/// Run a program in the default dialect with nothing to read, returning what
+/// it wrote, or the error which stopped it. A program recursing deeply needs
+/// a thread with a larger stack than the main one, the interpreter stopping
+/// it only past 10000 nested calls.
+pub fn run_program(source: &str) -> Result<Output, Error> {
+    let text = Rc::new(RefCell::new(Vec::new()));
+    let output = Box::new(SharedOutput(Rc::clone(&text)));
+    let parser = Parser::new(Lexer::new(String::from(source)));
+    let mut interpreter = Interpreter::new(parser, Box::new(io::empty()), output);
+    interpreter.interpret()?;
+    let text = String::from_utf8_lossy(&text.borrow()).into_owned();
+    Ok(Output { text, exit_code: interpreter.exit_code() })
+}
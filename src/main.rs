@@ -1,196 +1,900 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-mod ast;
+use caculator::{
+    ActivationRecord, Dialect, Error, FixedClock, Interpreter, LexError, Lexer, NoFileSystem,
+    OpType, ParseError, Parser, Routine, SemanticAnalyzer, Sources,
+};
+use clap::{Args, Subcommand};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
 
-#[derive(PartialEq)]
-enum OpType {
-    INTEGER,
-    PLUS,
-    MINUS,
-    MUL,
-    DIV,
-    LPAREN,
-    RPAREN,
-    EOF,
+/// The code the process exits with when a program stops with an error
+const RUNTIME_ERROR: i32 = 1;
+/// The code the process exits with when a program has a syntax error, the
+/// command line having one too being told by the code 2
+const SYNTAX_ERROR: i32 = 3;
+/// The code the process exits with when a program breaks a semantic rule
+const SEMANTIC_ERROR: i32 = 4;
+/// The code the process exits with when a program cannot be read
+const UNREADABLE: i32 = 5;
+/// The stack of the thread running the program, large enough for the most
+/// calls the interpreter lets be running at once
+const STACK_SIZE: usize = 1 << 30;
+/// The exit codes as `--help` lists them
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  the program ran to its end, or the code given to HALT if it called it
+  1  the program stopped with a runtime error
+  2  the command line is invalid
+  3  the program has a syntax error
+  4  the program breaks a semantic rule
+  5  the program cannot be read";
+
+/// Run Pascal programs, or any of the stages they go through, the REPL
+/// being started when no program is given
+#[derive(clap::Parser)]
+#[command(name = "pascal", args_conflicts_with_subcommands = true, after_help = EXIT_CODES)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// the file of a program to run, `-` for the standard input
+    path: Option<String>,
+    /// print the value of an expression, or run statements, and exit
+    #[arg(
+        short = 'e',
+        long = "eval",
+        value_name = "CODE",
+        conflicts_with = "path",
+        allow_hyphen_values = true
+    )]
+    eval: Option<String>,
+    /// the parameters of the programs, for PARAMCOUNT and PARAMSTR
+    #[arg(last = true)]
+    arguments: Vec<String>,
+    #[command(flatten)]
+    options: Options,
 }
 
-struct Token {
-    op_type: OpType,
-    value: String,
+#[derive(Subcommand)]
+enum Command {
+    /// Run a program
+    Run {
+        /// the file of the program, `-` for the standard input
+        path: String,
+        /// the parameters of the program, for PARAMCOUNT and PARAMSTR
+        #[arg(last = true)]
+        arguments: Vec<String>,
+    },
+    /// Look for the errors of a program without running it
+    Check {
+        /// the file of the program, `-` for the standard input
+        path: String,
+    },
+    /// Print the tokens of a program, one to a line
+    Tokens {
+        /// the file of the program, `-` for the standard input
+        path: String,
+    },
+    /// Print the syntax tree of a program
+    Ast {
+        /// the file of the program, `-` for the standard input
+        path: String,
+        /// print the tree as the Pascal source it stands for
+        #[arg(long)]
+        pretty: bool,
+        /// print the tree as JSON, for the tools reading it
+        #[arg(long, conflicts_with = "pretty")]
+        json: bool,
+    },
+    /// Print a program laid out again, without its comments; a program
+    /// with compiler directives is refused
+    Fmt {
+        /// the file of the program, `-` for the standard input
+        path: String,
+    },
 }
 
-impl Token {
-    fn new(op_type: OpType, value: &str) -> Token {
-        Token {
-            op_type,
-            value: String::from(value),
-        }
+/// How the programs are read and run, as the command line asks
+#[derive(Args)]
+struct Options {
+    /// reject values outside of a subrange instead of silently storing them
+    #[arg(long, global = true)]
+    range_checks: bool,
+    /// skip the right operand of AND and OR when the left one decides the result
+    #[arg(long, global = true)]
+    short_circuit: bool,
+    /// skip the conditions given to ASSERT where no {$C+} directive asks for them
+    #[arg(long, global = true)]
+    no_assertions: bool,
+    /// refuse to open the files named by ASSIGN, for running untrusted programs
+    #[arg(long, global = true)]
+    no_files: bool,
+    /// hide the environment variables from GETENV
+    #[arg(long = "no-env", global = true)]
+    no_environment: bool,
+    /// define a symbol for {$IFDEF}
+    #[arg(short = 'D', value_name = "NAME", global = true)]
+    defines: Vec<String>,
+    /// the dialect of the programs: iso, turbo or delphi
+    #[arg(long, value_name = "NAME", value_parser = dialect, global = true)]
+    dialect: Option<Dialect>,
+    /// short for --dialect=delphi
+    #[arg(long, conflicts_with = "dialect", global = true)]
+    delphi: bool,
+    /// the seed of RANDOM, making it give the same numbers on every run
+    #[arg(long, value_name = "N", global = true)]
+    seed: Option<u64>,
+    /// the time the clock is stopped at, in seconds from the start of 1970
+    #[arg(long, value_name = "SECONDS", global = true)]
+    fixed_time: Option<u64>,
+}
+
+fn dialect(name: &str) -> Result<Dialect, String> {
+    Dialect::from_name(name).ok_or_else(|| String::from("expected iso, turbo or delphi"))
+}
+
+fn main() {
+    // the calls of a deeply recursive program need more stack than the main
+    // thread has, so the program runs on a thread of its own
+    let thread = std::thread::Builder::new().stack_size(STACK_SIZE).spawn(run_cli);
+    // a panic is reported by the thread, and exits with the code a panic
+    // of the main thread would
+    if thread.expect("cannot start a thread").join().is_err() {
+        std::process::exit(101);
     }
 }
 
-struct Lexer {
-    text: String,
-    pos: usize,
-    current_char: Option<char>,
+/// Run the command the command line asks for
+fn run_cli() {
+    let cli = <Cli as clap::Parser>::parse();
+    let options = &cli.options;
+    match cli.command {
+        Some(Command::Run { path, arguments }) => run_file(&path, options, arguments),
+        Some(Command::Check { path }) => check(&path, options),
+        Some(Command::Tokens { path }) => tokens(&path, options),
+        Some(Command::Ast { path, pretty, json }) => ast(&path, pretty, json, options),
+        Some(Command::Fmt { path }) => fmt(&path, options),
+        None => match (cli.path, cli.eval) {
+            (Some(path), _) => run_file(&path, options, cli.arguments),
+            (None, Some(code)) => eval(&code, options, cli.arguments),
+            (None, None) => repl(options, cli.arguments),
+        },
+    }
 }
 
-impl Lexer {
-    fn new(text: String) -> Lexer {
-        Lexer { pos: 0, current_char: Some(text.chars().nth(0).unwrap()), text }
+/// Run the program in a file, exiting with the code it was stopped with by
+/// HALT, or with the code of the error which stopped it
+fn run_file(path: &str, options: &Options, arguments: Vec<String>) {
+    let lexer = open(path, options);
+    let mut interpreter = interpreter(lexer, stdin(), options, arguments);
+    if let Err(error) = interpreter.interpret() {
+        fail(interpreter.sources(), &error);
     }
-    fn advance(&mut self) {
-        self.pos += 1;
-        if self.pos >= self.text.len() {
-            self.current_char = None;
-        } else {
-            self.current_char = Some(self.text.chars().nth(self.pos).unwrap());
+    std::process::exit(interpreter.exit_code().unwrap_or(0));
+}
+
+/// Print the value of the code given by `--eval` when it is an expression,
+/// or else run it as statements, exiting as `run_file` does
+fn eval(code: &str, options: &Options, arguments: Vec<String>) {
+    let source = lexer(String::from(code), None, options);
+    let mut expression = interpreter(source, stdin(), options, arguments.clone());
+    let (expression_error, rendered) = match expression.evaluate() {
+        Ok(value) => {
+            println!("{}", value);
+            std::process::exit(expression.exit_code().unwrap_or(0));
+        }
+        // the code is an expression, which failed while it was evaluated
+        Err(error @ Error::Runtime { .. }) => fail(expression.sources(), &error),
+        Err(error) => {
+            let rendered = expression.sources().render(&error);
+            (error, rendered)
+        }
+    };
+    // the interpreter holds the standard input until it is dropped
+    drop(expression);
+    let source = lexer(String::from(code), None, options);
+    let mut statements = interpreter(source, stdin(), options, arguments);
+    match statements.interpret_statements() {
+        Ok(_) => std::process::exit(statements.exit_code().unwrap_or(0)),
+        Err(error) if tells_more(&expression_error, &error) => {
+            eprintln!("{}", rendered);
+            std::process::exit(exit_code(&expression_error))
         }
+        Err(error) => fail(statements.sources(), &error),
     }
-    fn skip_space(&mut self) {
-        while let Some(ch) = self.current_char && ch == ' ' {
-            self.advance()
+}
+
+/// Whether the error of a code read as an expression tells more than that
+/// of the code read as statements, which it does when it is of the same
+/// stage and found no nearer the start of the code, or when the code could
+/// only be parsed as an expression
+fn tells_more(expression_error: &Error, statements_error: &Error) -> bool {
+    let offset = |error: &Error| match error {
+        Error::Parse(errors) => errors.first().map(|error| error.position().offset),
+        Error::Semantic(error) => error.span.as_ref().map(|span| span.start.offset),
+        Error::Runtime { .. } => None,
+    };
+    match (expression_error, statements_error) {
+        (Error::Parse(_), Error::Parse(_)) | (Error::Semantic(_), Error::Semantic(_)) => {
+            offset(expression_error) >= offset(statements_error)
         }
+        (Error::Semantic(_), Error::Parse(_)) => true,
+        _ => false,
     }
-    fn integer_lexer(&mut self) -> String {
-        let mut res = String::from("");
-        while let Some(ch) = self.current_char && ch.is_digit(10) {
-            res.push(ch);
-            self.advance();
+}
+
+/// Parse and analyze the program in a file, exiting with the code of the
+/// first error it has if any
+fn check(path: &str, options: &Options) {
+    let mut parser = Parser::new(open(path, options));
+    let tree = parser.parse().unwrap_or_else(|errors| fail(parser.sources(), &errors.into()));
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.set_dialect(parser.dialect());
+    if let Err(error) = analyzer.analyze(&tree) {
+        fail(parser.sources(), &error.into());
+    }
+}
+
+/// Print each token of the program in a file with the line and the column
+/// it starts at
+fn tokens(path: &str, options: &Options) {
+    let mut lexer = open(path, options);
+    if let Err(error) = print_tokens(&mut lexer) {
+        fail(lexer.sources(), &error);
+    }
+}
+
+/// Print the tree the program in a file is parsed into, or the source
+/// printed from it when `pretty` is set, or the tree as JSON when `json` is
+fn ast(path: &str, pretty: bool, json: bool, options: &Options) {
+    let mut parser = Parser::new(open(path, options));
+    let printed = if pretty {
+        laid_out(&mut parser).map(|text| print!("{}", text))
+    } else if json {
+        let tree = parser.parse().map_err(Error::from);
+        // a tree of strings, numbers and lists is always written
+        tree.map(|tree| println!("{}", serde_json::to_string_pretty(&tree).unwrap()))
+    } else {
+        print_tree(&mut parser)
+    };
+    if let Err(error) = printed {
+        fail(parser.sources(), &error);
+    }
+}
+
+/// Print each token read by a lexer with the line and the column it starts at
+fn print_tokens(lexer: &mut Lexer) -> Result<(), Error> {
+    for token in lexer {
+        let token = token.map_err(lex_error)?;
+        let start = &token.span.start;
+        println!("{}:{}\t{:?}\t{:?}", start.line, start.column, token.op_type, token.value);
+    }
+    Ok(())
+}
+
+/// Print the tree a parser parses its program into
+fn print_tree(parser: &mut Parser) -> Result<(), Error> {
+    println!("{:#?}", parser.parse()?);
+    Ok(())
+}
+
+/// Print the program in a file laid out again
+fn fmt(path: &str, options: &Options) {
+    let text = read(path);
+    let shebang = text.lines().next().filter(|line| line.starts_with("#!")).map(String::from);
+    let mut parser = Parser::new(lexer(text, file_name(path), options));
+    match laid_out(&mut parser) {
+        Ok(text) => {
+            // the program laid out stays a script the shell can run
+            if let Some(line) = shebang {
+                println!("{}", line);
+            }
+            print!("{}", text);
         }
-        res
+        Err(error) => fail(parser.sources(), &error),
+    }
+}
+
+/// The source of the program a parser parses, printed from its tree. The
+/// tree keeps what the compiler directives select rather than the
+/// directives, so a program with directives is refused instead of being
+/// laid out without them.
+fn laid_out(parser: &mut Parser) -> Result<String, Error> {
+    let tree = parser.parse()?;
+    if let Some(position) = parser.first_directive() {
+        let message = String::from("a program with compiler directives cannot be laid out");
+        let error = ParseError::Invalid { message, position: position.clone() };
+        return Err(Error::Parse(vec![error]));
     }
+    Ok(caculator::print(&tree))
+}
 
-    fn get_next_token(&mut self) -> Token {
-        while let Some(ch) = self.current_char {
-            // println!("next token ch {}", ch);
-            if ch.is_digit(10) {
-                return Token::new(OpType::INTEGER, &self.integer_lexer());
+/// Run each input typed in, the declarations and statements one after the
+/// other in a session, where what the inputs before declared stays known,
+/// and a whole program, ending with its dot, on its own, showing the values
+/// its global variables are left with. The value of an expression is shown
+/// and kept in the variable `it`. A file is run in the session by `:load`,
+/// and the declarations run so far are written to one by `:save`. The
+/// inputs are kept in a history which the next sessions start with.
+fn repl(options: &Options, arguments: Vec<String>) {
+    let config = Config::builder().completion_type(CompletionType::List).build();
+    let mut editor = LineEditor::with_config(config).unwrap_or_else(|error| {
+        eprintln!("cannot read the input: {}", error);
+        std::process::exit(UNREADABLE)
+    });
+    editor.set_helper(Some(Completions::default()));
+    let history = history_file();
+    if let Some(history) = &history {
+        // the first session finds no history to start with
+        let _ = editor.load_history(history);
+    }
+    let mut session = Session::default();
+    loop {
+        if let Some(completions) = editor.helper_mut() {
+            let interpreter = session.interpreter.as_ref();
+            completions.declared = interpreter.map(Interpreter::declared_names).unwrap_or_default();
+        }
+        let Some(text) = read_input(&mut editor, options) else {
+            break;
+        };
+        let _ = editor.add_history_entry(text.trim_end());
+        // a command is told from Pascal by its colon, which no statement starts with
+        if let Some(command) = text.trim().strip_prefix(':') {
+            if run_command(command, &mut session, options, &arguments) {
+                break;
             }
-            match ch {
-            ' ' => {
-                self.skip_space();
-                continue;
+            // a file loaded may stop the session by HALT
+            if let Some(code) = session.interpreter.as_ref().and_then(Interpreter::exit_code) {
+                close_files(&mut session);
+                save_history(&mut editor, history.as_deref());
+                std::process::exit(code);
             }
-            '+' => {
-                self.advance();
-                return Token::new(OpType::PLUS, "+")
-            },
-            '-' => { 
-                self.advance();
-                return Token::new(OpType::MINUS, "-")
-            },
-            '*' => { 
-                self.advance();
-                return Token::new(OpType::MUL, "*")
-            },
-            '/' => { 
-                self.advance();
-                return Token::new(OpType::DIV, "/")
-            },
-            '(' => { 
-                self.advance();
-                return Token::new(OpType::LPAREN, "(")
-            },
-            ')' => { 
-                self.advance();
-                return Token::new(OpType::RPAREN, ")")
-            },
-            '\n' => {
-                break
+            continue;
+        }
+        let started = Instant::now();
+        let (exit_code, statements) = if is_program(&text, options) {
+            session.input = Some(text.clone());
+            session.expression = false;
+            let program = run_program(text, options, arguments.clone());
+            (program.exit_code(), program.statements_run())
+        } else {
+            session.input = Some(text.clone());
+            let interpreter = session.interpreter(options, &arguments);
+            let before = interpreter.statements_run();
+            let expression = run_input(interpreter, text, options);
+            let run = (interpreter.exit_code(), interpreter.statements_run() - before);
+            session.expression = expression;
+            run
+        };
+        if session.timing {
+            let milliseconds = started.elapsed().as_secs_f64() * 1000.0;
+            let plural = if statements == 1 { "" } else { "s" };
+            eprintln!("time: {:.3} ms, {} statement{} run", milliseconds, statements, plural);
+        }
+        if let Some(code) = exit_code {
+            close_files(&mut session);
+            save_history(&mut editor, history.as_deref());
+            std::process::exit(code);
+        }
+    }
+    close_files(&mut session);
+    save_history(&mut editor, history.as_deref());
+}
+
+/// What the REPL keeps of the inputs typed in so far
+#[derive(Default)]
+struct Session {
+    /// the last input run, which `:ast` and `:tokens` show
+    input: Option<String>,
+    /// whether the last input was evaluated as an expression rather than
+    /// run as declarations and statements
+    expression: bool,
+    /// whether the time each input takes to run is shown, along with the
+    /// number of statements it ran
+    timing: bool,
+    /// the interpreter the inputs other than whole programs are run by,
+    /// holding what they declared, made for the first of them
+    interpreter: Option<Interpreter>,
+}
+
+impl Session {
+    /// The interpreter of the session, made the first time it is needed
+    fn interpreter(&mut self, options: &Options, arguments: &[String]) -> &mut Interpreter {
+        self.interpreter.get_or_insert_with(|| {
+            let empty = lexer(String::new(), None, options);
+            interpreter(empty, shared_stdin(), options, arguments.to_vec())
+        })
+    }
+}
+
+/// The commands of the REPL as `:help` lists them
+const COMMANDS: &str = "\
+:ast     print the syntax tree of the last input
+:tokens  print the tokens of the last input
+:type    print the type of an expression, without evaluating it
+:vars    list the variables and routines declared so far
+:load    run a file in the session, keeping what it declares
+:save    write the declarations of the inputs run so far to a file
+:time    show how long each input takes to run, or stop showing it
+:reset   forget what the inputs typed in so far declared
+:quit    end the session
+:help    list the commands";
+
+/// Whether an input is a whole program, ending with its dot, rather than
+/// declarations and statements to run in the session
+fn is_program(text: &str, options: &Options) -> bool {
+    let last = lexer(String::from(text), None, options).filter_map(Result::ok).last();
+    last.is_some_and(|token| token.op_type == OpType::DOT)
+}
+
+/// Print the value of an input when it is an expression, or else run it
+/// as declarations and statements, returning whether it was evaluated. An
+/// error ends the input it stops, not the session.
+fn run_input(interpreter: &mut Interpreter, text: String, options: &Options) -> bool {
+    let (expression_error, rendered) =
+        match interpreter.evaluate_input(lexer(text.clone(), None, options)) {
+            Ok(value) => {
+                println!("{}", value);
+                return true;
             }
-            _ => {
-                panic!("unknown syntax {}", ch);
+            // the input is an expression, which failed while it was evaluated
+            Err(error @ Error::Runtime { .. }) => {
+                eprintln!("{}", interpreter.sources().render(&error));
+                return true;
             }
+            Err(error) => {
+                let rendered = interpreter.sources().render(&error);
+                (error, rendered)
             }
-        }
-        Token::new(OpType::EOF, "")
+        };
+    match interpreter.interpret_input(lexer(text, None, options)) {
+        Ok(()) => {}
+        Err(error) if tells_more(&expression_error, &error) => eprintln!("{}", rendered),
+        Err(error) => eprintln!("{}", interpreter.sources().render(&error)),
     }
+    false
 }
 
-struct Interpreter {
-    lexer: Lexer,
-    current_token: Token,
+/// Run a whole program typed in at the prompt on its own, showing the values
+/// its global variables are left with, and returning the interpreter which
+/// ran it
+fn run_program(text: String, options: &Options, arguments: Vec<String>) -> Interpreter {
+    let mut program = interpreter(lexer(text, None, options), shared_stdin(), options, arguments);
+    match program.interpret() {
+        Ok(global) => print_variables(&global),
+        // an error ends the program it stops, not the session
+        Err(error) => eprintln!("{}", program.sources().render(&error)),
+    }
+    program
 }
 
-impl Interpreter {
-    fn new(mut lexer: Lexer) -> Interpreter {
-        Interpreter {
-            current_token: lexer.get_next_token(),
-            lexer
+/// Write out the files the inputs of a session left open
+fn close_files(session: &mut Session) {
+    if let Some(interpreter) = &mut session.interpreter
+        && let Err(error) = interpreter.close_files()
+    {
+        eprintln!("{}", interpreter.sources().render(&error));
+    }
+}
+
+/// Run a command typed in at the prompt, without its colon, returning
+/// whether it ends the session
+fn run_command(
+    command: &str,
+    session: &mut Session,
+    options: &Options,
+    arguments: &[String],
+) -> bool {
+    let name = command.split_whitespace().next().unwrap_or_default();
+    let argument = command.trim_start()[name.len()..].trim();
+    let globals = session.interpreter.as_ref().and_then(Interpreter::globals);
+    match (name, &session.input, globals) {
+        ("ast" | "tokens" | "vars" | "time" | "reset" | "quit" | "help", _, _)
+            if !argument.is_empty() =>
+        {
+            eprintln!(":{} takes no argument", name)
         }
+        ("ast" | "tokens", None, _) => eprintln!("there is no input yet"),
+        ("ast", Some(text), _) if session.expression => {
+            let mut parser = Parser::new(lexer(String::new(), None, options));
+            match parser.parse_input_expression(lexer(text.clone(), None, options)) {
+                Ok(tree) => println!("{:#?}", tree),
+                Err(errors) => eprintln!("{}", parser.sources().render(&errors.into())),
+            }
+        }
+        ("ast", Some(text), _) if !is_program(text, options) => {
+            let mut parser = Parser::new(lexer(String::new(), None, options));
+            match parser.parse_input(lexer(text.clone(), None, options)) {
+                Ok(tree) => println!("{:#?}", tree),
+                Err(errors) => eprintln!("{}", parser.sources().render(&errors.into())),
+            }
+        }
+        ("ast", Some(text), _) => {
+            let mut parser = Parser::new(lexer(text.clone(), None, options));
+            if let Err(error) = print_tree(&mut parser) {
+                eprintln!("{}", parser.sources().render(&error));
+            }
+        }
+        ("tokens", Some(text), _) => {
+            let mut lexer = lexer(text.clone(), None, options);
+            if let Err(error) = print_tokens(&mut lexer) {
+                eprintln!("{}", lexer.sources().render(&error));
+            }
+        }
+        ("type", _, _) if argument.is_empty() => eprintln!(":type takes an expression"),
+        ("type", _, _) => {
+            let interpreter = session.interpreter(options, arguments);
+            let source = lexer(String::from(argument), None, options);
+            match interpreter.input_expression_type(source) {
+                Ok(Some(type_node)) => println!("{}", type_node),
+                Ok(None) => eprintln!("the type of the expression is only known once it runs"),
+                Err(error) => eprintln!("{}", interpreter.sources().render(&error)),
+            }
+        }
+        ("vars", _, None) => eprintln!("nothing has been declared yet"),
+        ("vars", _, Some(globals)) => {
+            print_variables(globals);
+            print_routines(globals);
+        }
+        ("load" | "save", _, _) if argument.is_empty() => eprintln!(":{} takes a file name", name),
+        ("load", _, _) => match std::fs::read_to_string(argument) {
+            Ok(text) => {
+                let interpreter = session.interpreter(options, arguments);
+                let source = lexer(text, Some(argument), options);
+                if let Err(error) = interpreter.interpret_input(source) {
+                    eprintln!("{}", interpreter.sources().render(&error));
+                }
+            }
+            Err(error) => eprintln!("cannot read {}: {}", argument, error),
+        },
+        ("save", _, _) => {
+            let interpreter = session.interpreter.as_ref();
+            let source = interpreter.map(Interpreter::declarations_source).unwrap_or_default();
+            if let Err(error) = std::fs::write(argument, source) {
+                eprintln!("cannot write {}: {}", argument, error);
+            }
+        }
+        ("time", _, _) => {
+            session.timing = !session.timing;
+            println!("timing {}", if session.timing { "on" } else { "off" });
+        }
+        ("reset", _, _) => {
+            close_files(session);
+            // showing the time is a setting of the REPL, not a declaration
+            *session = Session { timing: session.timing, ..Session::default() };
+        }
+        ("quit", _, _) => return true,
+        ("help", _, _) => println!("{}", COMMANDS),
+        _ => eprintln!("unknown command :{}, :help lists the commands", name),
     }
-    fn eat(&mut self, op_type: OpType) {
-        // println!("eat: old current token {}", self.current_token.value);
-        if self.current_token.op_type == op_type {
-            self.current_token = self.lexer.get_next_token();
-        } else {
-            panic!("unknown syntax")
+    false
+}
+
+/// Print the global variables a program left, by their names
+fn print_variables(global: &ActivationRecord) {
+    let mut names: Vec<&String> = global.members.keys().collect();
+    names.sort();
+    for name in names {
+        println!("{} = {}", name, global.members[name]);
+    }
+}
+
+/// Print the headings of the routines a program declared, by their names
+fn print_routines(global: &ActivationRecord) {
+    let mut names: Vec<&String> = global.routines.keys().collect();
+    names.sort();
+    for name in names {
+        match &global.routines[name] {
+            Routine::Procedure(_) => println!("PROCEDURE {}", name),
+            Routine::Function(_) => println!("FUNCTION {}", name),
         }
-        // println!("eat: new current token {}", self.current_token.value);
     }
-    fn factor(&mut self) -> i32 {
-        match self.current_token.op_type {
-        OpType::INTEGER => {
-            let res = self.current_token.value.parse::<i32>().unwrap();
-            self.eat(OpType::INTEGER);
-            return res;
-        },
-        OpType::LPAREN => {
-            self.eat(OpType::LPAREN);
-            let res = self.expr();
-            self.eat(OpType::RPAREN);
-            res
-        },
-        _ => panic!("syntax error")
+}
+
+/// The editor the REPL reads its inputs with
+type LineEditor = Editor<Completions, DefaultHistory>;
+
+/// Completes the word before the cursor with the reserved words, the names
+/// of the built-in routines and the names declared by the inputs run so far
+#[derive(Default)]
+struct Completions {
+    /// the names declared by the inputs run so far
+    declared: Vec<String>,
+}
+
+impl Completer for Completions {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let start = before.trim_end_matches(|ch: char| ch.is_alphanumeric() || ch == '_').len();
+        let word = &before[start..];
+        if word.is_empty() {
+            return Ok((pos, Vec::new()));
         }
+        // the predefined names are offered in lower case for a word typed in lower case
+        let lower = !word.chars().any(char::is_uppercase);
+        let predefined = caculator::predefined_names().into_iter().map(|name| match lower {
+            true => name.to_lowercase(),
+            false => String::from(name),
+        });
+        let completes = |name: &String| {
+            name.get(..word.len()).is_some_and(|head| head.eq_ignore_ascii_case(word))
+        };
+        let mut candidates: Vec<String> =
+            predefined.chain(self.declared.iter().cloned()).filter(completes).collect();
+        candidates.sort();
+        candidates.dedup();
+        Ok((start, candidates))
     }
-    fn term(&mut self) -> i32 {
-        let mut res = self.factor();
-        while self.current_token.op_type == OpType::MUL ||
-            self.current_token.op_type == OpType::DIV {
-            match self.current_token.op_type {
-            OpType::MUL => {
-                self.eat(OpType::MUL);
-                res *= self.factor();
-            },
-            OpType::DIV => {
-                self.eat(OpType::DIV);
-                res /= self.factor();
+}
+
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
+
+/// The next input typed at the prompt, read on as many lines as it takes to
+/// complete it, `None` once the session ends
+fn read_input(editor: &mut LineEditor, options: &Options) -> Option<String> {
+    let mut text = String::new();
+    loop {
+        let prompt = if text.is_empty() { "calc> " } else { "....> " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                text.push_str(&line);
+                text.push('\n');
             }
-            _ => ()
+            // Ctrl-C drops the input being typed, as shells do
+            Err(ReadlineError::Interrupted) => text.clear(),
+            // Ctrl-D, or the end of the input, ends the session once the
+            // input typed so far is run
+            Err(ReadlineError::Eof) if text.is_empty() => return None,
+            Err(ReadlineError::Eof) => return Some(text),
+            Err(error) => {
+                eprintln!("cannot read the input: {}", error);
+                std::process::exit(UNREADABLE)
             }
         }
-        res
+        if text.trim().is_empty() {
+            text.clear();
+        } else if is_complete(&text, options) {
+            return Some(text);
+        }
     }
-    fn expr(&mut self) -> i32 {
-        let mut res = self.term();
-        while self.current_token.op_type == OpType::PLUS ||
-            self.current_token.op_type == OpType::MINUS {
-            match self.current_token.op_type {
-            OpType::PLUS => {
-                self.eat(OpType::PLUS);
-                res += self.term();
+}
+
+/// Whether an input is complete, rather than missing the END or UNTIL of
+/// a BEGIN, CASE, TRY, RECORD or REPEAT, a closing parenthesis or bracket,
+/// the end of a comment, the block of a routine whose heading it ends with,
+/// what comes after its last token, like the statement after a THEN or the
+/// operand after an operator, or the final dot of a program started with
+/// its heading. Any other error is left for the parser to tell.
+fn is_complete(text: &str, options: &Options) -> bool {
+    let mut open = Vec::new();
+    let mut nesting = 0;
+    // the routines whose block has not ended yet, by how many blocks were
+    // open at their heading
+    let mut routines = Vec::new();
+    let (mut first, mut last) = (None, None);
+    for token in lexer(String::from(text), None, options) {
+        let op_type = match token {
+            Ok(token) => token.op_type,
+            Err(LexError::UnterminatedComment(_)) => return false,
+            Err(_) => return true,
+        };
+        match op_type {
+            OpType::BEGIN | OpType::CASE | OpType::TRY | OpType::RECORD | OpType::REPEAT => {
+                open.push(op_type)
             }
-            OpType::MINUS => {
-                self.eat(OpType::MINUS);
-                res -= self.term();
+            OpType::END | OpType::UNTIL => {
+                let closed = open.pop();
+                // the variant part of a record ends with the record itself
+                if closed == Some(OpType::CASE) && open.last() == Some(&OpType::RECORD) {
+                    open.pop();
+                }
+                if closed == Some(OpType::BEGIN) && routines.last() == Some(&open.len()) {
+                    routines.pop();
+                }
             }
-            _ => ()
+            OpType::LPAREN | OpType::LBRACKET => nesting += 1,
+            OpType::RPAREN | OpType::RBRACKET => nesting -= 1,
+            // a procedural type or parameter has no block
+            OpType::PROCEDURE | OpType::FUNCTION
+                if nesting <= 0 && !matches!(last, Some(OpType::EQUAL | OpType::COLON)) =>
+            {
+                routines.push(open.len())
             }
+            OpType::FORWARD => {
+                routines.pop();
+            }
+            _ => {}
         }
-        res
+        first.get_or_insert(op_type);
+        last = Some(op_type);
+    }
+    if first == Some(OpType::PROGRAM) {
+        return open.is_empty() && nesting <= 0 && last == Some(OpType::DOT);
     }
+    open.is_empty() && nesting <= 0 && routines.is_empty() && !last.is_some_and(goes_on)
 }
 
+/// Whether an input ending with a token goes on after it, as after THEN or
+/// an operator, or after VAR and its kin starting a section of declarations
+fn goes_on(op_type: OpType) -> bool {
+    matches!(
+        op_type,
+        OpType::THEN
+            | OpType::DO
+            | OpType::ELSE
+            | OpType::OF
+            | OpType::IF
+            | OpType::WHILE
+            | OpType::FOR
+            | OpType::WITH
+            | OpType::TO
+            | OpType::DOWNTO
+            | OpType::GOTO
+            | OpType::ASSIGN
+            | OpType::COMMA
+            | OpType::COLON
+            | OpType::RANGE
+            | OpType::PLUS
+            | OpType::MINUS
+            | OpType::MUL
+            | OpType::FLOAT_DIV
+            | OpType::INTEGER_DIV
+            | OpType::MOD
+            | OpType::AND
+            | OpType::OR
+            | OpType::NOT
+            | OpType::IN
+            | OpType::EQUAL
+            | OpType::NOT_EQUAL
+            | OpType::LESS
+            | OpType::LESS_EQUAL
+            | OpType::GREATER
+            | OpType::GREATER_EQUAL
+            | OpType::VAR
+            | OpType::CONST
+            | OpType::TYPE
+            | OpType::LABEL
+            | OpType::USES
+    )
+}
 
-fn main() {
-    loop {
-        print!("calc> ");
-        std::io::Write::flush(&mut std::io::stdout()).unwrap();
-        let mut text = String::new();
-        std::io::stdin().read_line(&mut text).unwrap();
-        let lexer = Lexer::new(text);
-        let mut interpreter = Interpreter::new(lexer);
-        let res = interpreter.expr();
-        print!("{}\n", res);
+/// The file the history of the REPL is kept in, in the home directory
+fn history_file() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(Path::new(&home).join(".pascal_history"))
+}
+
+fn save_history(editor: &mut LineEditor, history: Option<&Path>) {
+    if let Some(history) = history
+        && let Err(error) = editor.save_history(history)
+    {
+        eprintln!("cannot save the history to {}: {}", history.display(), error);
+    }
+}
+
+/// The text of a file, or of the standard input for `-`, exiting when it
+/// cannot be read
+fn read(path: &str) -> String {
+    let result = match path {
+        "-" => std::io::read_to_string(std::io::stdin()),
+        _ => std::fs::read_to_string(path),
+    };
+    result.unwrap_or_else(|error| {
+        eprintln!("cannot read {}: {}", file_name(path).unwrap_or("the standard input"), error);
+        std::process::exit(UNREADABLE)
+    })
+}
+
+/// The name of the file a program is read from, `None` for the standard input
+fn file_name(path: &str) -> Option<&str> {
+    (path != "-").then_some(path)
+}
+
+/// The lexer of the program in a file set up as the options ask
+fn open(path: &str, options: &Options) -> Lexer {
+    lexer(read(path), file_name(path), options)
+}
+
+/// Show an error along with the line it was found in, and exit with its code
+fn fail(sources: &Sources, error: &Error) -> ! {
+    eprintln!("{}", sources.render(error));
+    std::process::exit(exit_code(error))
+}
+
+/// The code the process exits with for an error, by the stage finding it
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::Parse(_) => SYNTAX_ERROR,
+        Error::Semantic(_) => SEMANTIC_ERROR,
+        Error::Runtime { .. } => RUNTIME_ERROR,
+    }
+}
+
+/// An error of the lexer as the parser would have reported it
+fn lex_error(error: LexError) -> Error {
+    Error::from(vec![ParseError::from(error)])
+}
+
+/// The lexer of a program set up as the options ask, `file` naming the file
+/// the program was read from if any
+fn lexer(text: String, file: Option<&str>, options: &Options) -> Lexer {
+    let mut lexer = Lexer::new(text);
+    match options.dialect {
+        Some(dialect) => lexer.set_dialect(dialect),
+        None if options.delphi => lexer.set_dialect(Dialect::Delphi),
+        None => {}
+    }
+    if let Some(file) = file {
+        lexer.set_file(file);
+    }
+    for symbol in &options.defines {
+        lexer.define(symbol);
+    }
+    lexer
+}
+
+/// The standard input, which the interpreter holds locked until it is dropped
+fn stdin() -> Box<dyn BufRead> {
+    Box::new(std::io::stdin().lock())
+}
+
+/// The standard input read a byte at a time without holding it locked, so
+/// that the REPL reads its own lines from it in between the inputs it runs,
+/// and a program reads none of them ahead
+fn shared_stdin() -> Box<dyn BufRead> {
+    Box::new(BufReader::with_capacity(1, std::io::stdin()))
+}
+
+/// The interpreter of a program set up as the options ask, reading from
+/// `input`, `arguments` being the parameters the program is given
+fn interpreter(
+    lexer: Lexer,
+    input: Box<dyn BufRead>,
+    options: &Options,
+    arguments: Vec<String>,
+) -> Interpreter {
+    let parser = Parser::new(lexer);
+    let mut interpreter = Interpreter::new(parser, input, Box::new(std::io::stdout()));
+    interpreter.set_range_checks(options.range_checks);
+    interpreter.set_short_circuit(options.short_circuit);
+    interpreter.set_assertions(!options.no_assertions);
+    // PARAMSTR(0) is the name the interpreter was run by
+    let name = std::env::args().next().unwrap_or_default();
+    interpreter.set_arguments(std::iter::once(name).chain(arguments).collect());
+    if options.no_files {
+        interpreter.set_file_system(Box::new(NoFileSystem));
+    }
+    if options.no_environment {
+        interpreter.set_environment(Box::new(HashMap::new()));
+    }
+    if let Some(seconds) = options.fixed_time {
+        interpreter.set_clock(Box::new(FixedClock(Duration::from_secs(seconds))));
+    }
+    if let Some(seed) = options.seed {
+        interpreter.set_random_seed(seed);
     }
+    interpreter
 }
@@ -0,0 +1,93 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::hash_map::IntoValues;
+use std::ops::Index;
+
+/// Whether two spellings stand for the same name, as Pascal ignores the
+/// case of the letters of identifiers
+pub fn same_name(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+/// The spelling all the spellings of a name are found under
+fn key(name: &str) -> Cow<'_, str> {
+    if name.bytes().any(|byte| byte.is_ascii_uppercase()) {
+        Cow::Owned(name.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// A map from the names declared in a scope, which are found however their
+/// case is spelled; each entry keeps the spelling it was declared with, which
+/// the messages and listings show
+#[derive(Clone, Debug)]
+pub struct Names<V> {
+    entries: HashMap<String, (String, V)>,
+}
+
+impl<V> Default for Names<V> {
+    fn default() -> Names<V> {
+        Names { entries: HashMap::new() }
+    }
+}
+
+impl<V> Names<V> {
+    pub fn new() -> Names<V> {
+        Names::default()
+    }
+    /// Declare a name, returning what it stood for before
+    pub fn insert(&mut self, name: String, value: V) -> Option<V> {
+        let key = key(&name).into_owned();
+        self.entries.insert(key, (name, value)).map(|(_, value)| value)
+    }
+    pub fn get(&self, name: &str) -> Option<&V> {
+        self.entries.get(key(name).as_ref()).map(|(_, value)| value)
+    }
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut V> {
+        self.entries.get_mut(key(name).as_ref()).map(|(_, value)| value)
+    }
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.entries.contains_key(key(name).as_ref())
+    }
+    pub fn remove(&mut self, name: &str) -> Option<V> {
+        self.entries.remove(key(name).as_ref()).map(|(_, value)| value)
+    }
+    /// The names as they were declared
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.values().map(|(name, _)| name)
+    }
+}
+
+impl<V> Index<&str> for Names<V> {
+    type Output = V;
+
+    fn index(&self, name: &str) -> &V {
+        self.get(name).expect("name not declared")
+    }
+}
+
+impl<V> Extend<(String, V)> for Names<V> {
+    fn extend<I: IntoIterator<Item = (String, V)>>(&mut self, entries: I) {
+        for (name, value) in entries {
+            self.insert(name, value);
+        }
+    }
+}
+
+impl<V> FromIterator<(String, V)> for Names<V> {
+    fn from_iter<I: IntoIterator<Item = (String, V)>>(entries: I) -> Names<V> {
+        let mut names = Names::new();
+        names.extend(entries);
+        names
+    }
+}
+
+impl<V> IntoIterator for Names<V> {
+    type Item = (String, V);
+    type IntoIter = IntoValues<String, (String, V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_values()
+    }
+}
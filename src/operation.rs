@@ -0,0 +1,223 @@
+use std::cmp::Ordering;
+
+use crate::dialect::Dialect;
+use crate::error::RuntimeError;
+use crate::set::Set;
+use crate::token::OpType;
+use crate::value::Value;
+
+/// Apply an operator to the values of its operands, as both the interpreter
+/// and the folding of constant expressions do, the dialect deciding what MOD
+/// gives for negative operands
+pub fn binary_operation(
+    op_type: OpType,
+    left: &Value,
+    right: &Value,
+    dialect: Dialect,
+) -> Result<Value, RuntimeError> {
+    let ordering = || left.compare(right);
+    Ok(match (op_type, left, right) {
+        (OpType::IN, _, Value::Set(set)) => Value::Boolean(set.contains(left)?),
+        (OpType::IN, _, _) => return Err(right.mismatch("a SET value")),
+        (_, Value::Set(left), Value::Set(right)) => set_operation(op_type, left, right)?,
+        (OpType::EQUAL, _, _) => Value::Boolean(ordering()? == Some(Ordering::Equal)),
+        (OpType::NOT_EQUAL, _, _) => Value::Boolean(ordering()? != Some(Ordering::Equal)),
+        (OpType::LESS, _, _) => Value::Boolean(ordering()? == Some(Ordering::Less)),
+        (OpType::LESS_EQUAL, _, _) => Value::Boolean(ordering()?.is_some_and(|o| o.is_le())),
+        (OpType::GREATER, _, _) => Value::Boolean(ordering()? == Some(Ordering::Greater)),
+        (OpType::GREATER_EQUAL, _, _) => Value::Boolean(ordering()?.is_some_and(|o| o.is_ge())),
+        (OpType::AND, _, _) => Value::Boolean(left.as_boolean()? && right.as_boolean()?),
+        (OpType::OR, _, _) => Value::Boolean(left.as_boolean()? || right.as_boolean()?),
+        (OpType::FLOAT_DIV, _, _) => Value::Real(left.as_real()? / right.as_real()?),
+        (OpType::INTEGER_DIV, _, _) => {
+            Value::Integer(div(left.as_integer()?, right.as_integer()?)?)
+        }
+        (OpType::MOD, _, _) => {
+            Value::Integer(modulo(left.as_integer()?, right.as_integer()?, dialect)?)
+        }
+        (OpType::PLUS, _, _) if left.is_text() && right.is_text() => {
+            Value::String(left.as_string()? + &right.as_string()?)
+        }
+        (_, Value::Integer(left), Value::Integer(right)) => Value::Integer(match op_type {
+            OpType::PLUS => left.checked_add(*right),
+            OpType::MINUS => left.checked_sub(*right),
+            OpType::MUL => left.checked_mul(*right),
+            _ => return Err(bad_operand("INTEGER", op_type)),
+        }.ok_or(RuntimeError::IntegerOverflow)?),
+        // as soon as one operand is a real the other one is promoted
+        _ => {
+            let (left, right) = (left.as_real()?, right.as_real()?);
+            Value::Real(match op_type {
+                OpType::PLUS => left + right,
+                OpType::MINUS => left - right,
+                OpType::MUL => left * right,
+                _ => return Err(bad_operand("REAL", op_type)),
+            })
+        }
+    })
+}
+
+/// Apply a sign or NOT to the value of its operand
+pub fn unary_operation(op_type: OpType, value: &Value) -> Result<Value, RuntimeError> {
+    Ok(match (op_type, value) {
+        (OpType::NOT, _) => Value::Boolean(!value.as_boolean()?),
+        (OpType::PLUS, Value::Integer(_) | Value::Real(_)) => value.clone(),
+        (OpType::MINUS, Value::Integer(i)) => {
+            Value::Integer(i.checked_neg().ok_or(RuntimeError::IntegerOverflow)?)
+        }
+        (OpType::MINUS, _) => Value::Real(-value.as_real()?),
+        _ => return Err(bad_operand(value.type_name(), op_type)),
+    })
+}
+
+/// Add the values from `low` to `high` to a set; a range whose high bound
+/// is below its low one adds nothing
+pub fn insert_range(set: &mut Set, low: &Value, high: &Value) -> Result<(), RuntimeError> {
+    if std::mem::discriminant(low) != std::mem::discriminant(high) {
+        return Err(RuntimeError::new(format!(
+            "bounds {} and {} of a set range differ in type",
+            low.type_name(), high.type_name()
+        )));
+    }
+    for ordinal in low.ordinal()?..=high.ordinal()? {
+        set.insert(&low.with_ordinal(ordinal)?)?;
+    }
+    Ok(())
+}
+
+/// `i DIV j`, truncating the quotient towards zero as ISO 7185 and Turbo
+/// Pascal both do, so that -7 DIV 2 is -3
+fn div(i: i32, j: i32) -> Result<i32, RuntimeError> {
+    match i.checked_div(j) {
+        Some(quotient) => Ok(quotient),
+        None if j == 0 => Err(RuntimeError::DivisionByZero),
+        None => Err(RuntimeError::IntegerOverflow),
+    }
+}
+
+/// `i MOD j`, where the dialects part ways. ISO 7185 makes it an error for
+/// `j` not to be positive and gives a result from 0 to `j - 1` even for a
+/// negative `i`, so that -7 MOD 2 is 1. Turbo Pascal and Delphi give the
+/// remainder of DIV instead, which takes the sign of `i`, so that -7 MOD 2
+/// is -1, and accept a negative `j`, ignoring its sign.
+fn modulo(i: i32, j: i32, dialect: Dialect) -> Result<i32, RuntimeError> {
+    if dialect == Dialect::Iso {
+        if j <= 0 {
+            return Err(RuntimeError::new(format!(
+                "the divisor of MOD must be positive but is {}",
+                j
+            )));
+        }
+        return Ok(i.rem_euclid(j));
+    }
+    match i.checked_rem(j) {
+        Some(remainder) => Ok(remainder),
+        None if j == 0 => Err(RuntimeError::DivisionByZero),
+        // only i32::MIN MOD -1 overflows, whose remainder is 0
+        None => Ok(0),
+    }
+}
+
+fn set_operation(op_type: OpType, left: &Set, right: &Set) -> Result<Value, RuntimeError> {
+    Ok(match op_type {
+        OpType::PLUS => Value::Set(left.union(right)?),
+        OpType::MINUS => Value::Set(left.difference(right)?),
+        OpType::MUL => Value::Set(left.intersection(right)?),
+        OpType::EQUAL => Value::Boolean(left == right),
+        OpType::NOT_EQUAL => Value::Boolean(left != right),
+        OpType::LESS_EQUAL => Value::Boolean(left.is_subset(right)?),
+        OpType::GREATER_EQUAL => Value::Boolean(right.is_subset(left)?),
+        _ => return Err(bad_operand("SET", op_type)),
+    })
+}
+
+fn bad_operand(type_name: &str, op_type: OpType) -> RuntimeError {
+    RuntimeError::new(format!("bad operand type {} for {:?}", type_name, op_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn div_truncates_towards_zero() {
+        assert_eq!(div(7, 2), Ok(3));
+        assert_eq!(div(-7, 2), Ok(-3));
+        assert_eq!(div(7, -2), Ok(-3));
+        assert_eq!(div(-7, -2), Ok(3));
+        assert_eq!(div(-6, 3), Ok(-2));
+    }
+
+    #[test]
+    fn div_by_zero() {
+        assert_eq!(div(1, 0), Err(RuntimeError::DivisionByZero));
+    }
+
+    #[test]
+    fn div_overflow() {
+        assert_eq!(div(i32::MIN, -1), Err(RuntimeError::IntegerOverflow));
+    }
+
+    #[test]
+    fn iso_mod_is_never_negative() {
+        assert_eq!(modulo(7, 3, Dialect::Iso), Ok(1));
+        assert_eq!(modulo(-7, 3, Dialect::Iso), Ok(2));
+        assert_eq!(modulo(-6, 3, Dialect::Iso), Ok(0));
+        assert_eq!(modulo(-1, 5, Dialect::Iso), Ok(4));
+        assert_eq!(modulo(i32::MIN, 2, Dialect::Iso), Ok(0));
+    }
+
+    #[test]
+    fn iso_mod_by_negative() {
+        let error = modulo(7, -3, Dialect::Iso).unwrap_err();
+        assert_eq!(error.to_string(), "the divisor of MOD must be positive but is -3");
+    }
+
+    #[test]
+    fn iso_mod_by_zero() {
+        let error = modulo(7, 0, Dialect::Iso).unwrap_err();
+        assert_eq!(error.to_string(), "the divisor of MOD must be positive but is 0");
+    }
+
+    #[test]
+    fn turbo_mod_takes_the_sign_of_the_dividend() {
+        for dialect in [Dialect::Turbo, Dialect::Delphi] {
+            assert_eq!(modulo(7, 3, dialect), Ok(1));
+            assert_eq!(modulo(-7, 3, dialect), Ok(-1));
+            assert_eq!(modulo(7, -3, dialect), Ok(1));
+            assert_eq!(modulo(-7, -3, dialect), Ok(-1));
+            assert_eq!(modulo(i32::MIN, -1, dialect), Ok(0));
+        }
+    }
+
+    #[test]
+    fn turbo_mod_by_zero() {
+        assert_eq!(modulo(7, 0, Dialect::Turbo), Err(RuntimeError::DivisionByZero));
+    }
+
+    #[test]
+    fn div_and_mod_rebuild_the_dividend() {
+        for i in [-9, -8, -1, 0, 1, 8, 9] {
+            for j in [-4, -3, 3, 4] {
+                assert_eq!(div(i, j).unwrap() * j + modulo(i, j, Dialect::Turbo).unwrap(), i);
+            }
+        }
+    }
+
+    #[test]
+    fn binary_operation_follows_the_dialect() {
+        let (left, right) = (Value::Integer(-7), Value::Integer(2));
+        let mod_in = |dialect| binary_operation(OpType::MOD, &left, &right, dialect);
+        assert_eq!(mod_in(Dialect::Iso), Ok(Value::Integer(1)));
+        assert_eq!(mod_in(Dialect::Turbo), Ok(Value::Integer(-1)));
+        let div = binary_operation(OpType::INTEGER_DIV, &left, &right, Dialect::Iso);
+        assert_eq!(div, Ok(Value::Integer(-3)));
+    }
+
+    #[test]
+    fn integer_overflow_is_an_error() {
+        let (left, right) = (Value::Integer(i32::MAX), Value::Integer(1));
+        let sum = binary_operation(OpType::PLUS, &left, &right, Dialect::Turbo);
+        assert_eq!(sum, Err(RuntimeError::IntegerOverflow));
+    }
+}
@@ -0,0 +1,1846 @@
+use std::collections::{HashSet, VecDeque};
+use std::rc::Rc;
+
+use crate::ast::{
+    Assign, BinOp, Block, Case, CaseArm, Compound, ConstDecl, Constant, Decl, Deref,
+    ExceptionHandler, Exit, Expr, Field, For, FormattedParam, ForwardDecl, FunctionCall,
+    FunctionDecl, Goto, If, Index, Initializer, LabelDecl, Labeled, Literal, ProcedureCall,
+    ProcedureDecl, Program, Raise, Repeat, SetConstructor, Stmt, TryExcept, TryFinally, TypeDecl,
+    TypedConstDecl, UnaryOp, Unit, ValueRange, Var, VarDecl, While, With,
+};
+use crate::builtin::{builtin_function, is_type_function, type_function};
+use crate::dialect::{Dialect, Feature};
+use crate::diagnostic::Sources;
+use crate::error::{LexError, ParseError, RuntimeError};
+use crate::lexer::Lexer;
+use crate::names::{Names, same_name};
+use crate::operation::{binary_operation, insert_range, unary_operation};
+use crate::set::Set;
+use crate::token::{OpType, Position, Span, Token, keyword};
+use crate::types::{ParamNames, Type, VariantPart};
+use crate::value::Value;
+
+/// The ON handlers of an EXCEPT part and the statements after its ELSE, or
+/// its statements alone when it has no handlers
+type ExceptParts = (Vec<ExceptionHandler>, Option<Vec<Stmt>>);
+
+/// What an identifier declared in a CONST or TYPE section stands for, which
+/// the constants in the declarations after it may need
+#[derive(Clone)]
+enum Definition {
+    Constant(Value),
+    Type(Type),
+}
+
+pub struct Parser {
+    lexer: Lexer,
+    current_token: Token,
+    /// the tokens after the current one read ahead by `peek_nth`, which are
+    /// handed out before reading any more of the source
+    lookahead: VecDeque<Result<Token, LexError>>,
+    /// the span of the token eaten last, where the node being parsed ends so far
+    previous_span: Span,
+    /// the syntax errors of the statements skipped so far, which `parse`
+    /// reports together
+    errors: Vec<ParseError>,
+    /// how many loops we are currently inside, for checking BREAK and CONTINUE
+    loop_depth: usize,
+    /// the constants and types declared by each block we are inside,
+    /// innermost last
+    definitions: Vec<Names<Definition>>,
+    /// the declarations of the identifiers of the enumerated types parsed
+    /// since the last declaration was added to a block
+    enum_constants: Vec<Decl>,
+    /// whether we are in the interface of a unit, which declares only the
+    /// headings of its routines
+    in_interface: bool,
+    /// whether we are parsing an input typed in at a REPL, whose statements
+    /// may follow its declarations right away
+    in_input: bool,
+    /// the constants and types the inputs parsed before the current one
+    /// declared, which `forget_input` goes back to
+    before_input: Names<Definition>,
+    /// the units parsed so far, each after the units it uses
+    units: Vec<Unit>,
+    /// the units being parsed, the innermost last, which may not be used again
+    loading: Vec<String>,
+    /// the constants and types declared by the interface of each unit parsed
+    unit_definitions: Names<Names<Definition>>,
+}
+
+impl Parser {
+    pub fn new(lexer: Lexer) -> Parser {
+        let span = Span { start: lexer.position(), bytes: 0..0 };
+        Parser {
+            // the first token is only read by `parse`, which can fail
+            current_token: Token::new(OpType::EOF, Rc::from(""), span.clone()),
+            lookahead: VecDeque::new(),
+            previous_span: span,
+            errors: Vec::new(),
+            lexer,
+            loop_depth: 0,
+            definitions: Vec::new(),
+            enum_constants: Vec::new(),
+            in_interface: false,
+            in_input: false,
+            before_input: Names::new(),
+            units: Vec::new(),
+            loading: Vec::new(),
+            unit_definitions: Names::new(),
+        }
+    }
+    /// Fail unless the dialect of the program accepts a feature, which the
+    /// current token starts
+    fn require(&self, feature: Feature) -> Result<(), ParseError> {
+        if !self.lexer.dialect().allows(feature) {
+            return Err(self.unsupported(feature));
+        }
+        Ok(())
+    }
+    /// The error rejecting a feature the dialect of the program lacks, which
+    /// the current token starts
+    fn unsupported(&self, feature: Feature) -> ParseError {
+        let (dialect, position) = (self.lexer.dialect(), self.position());
+        ParseError::Unsupported { feature, dialect, position }
+    }
+    fn eat(&mut self, op_type: OpType) -> Result<(), ParseError> {
+        // println!("eat: old current token {}", self.current_token.value);
+        if self.current_token.op_type != op_type {
+            return Err(self.unexpected(op_type));
+        }
+        let next_token = self.next_token()?;
+        self.previous_span = std::mem::replace(&mut self.current_token, next_token).span;
+        // println!("eat: new current token {}", self.current_token.value);
+        Ok(())
+    }
+    /// Read the token after the current one
+    fn next_token(&mut self) -> Result<Token, LexError> {
+        match self.lookahead.pop_front() {
+            Some(token) => token,
+            None => self.lexer.get_next_token(),
+        }
+    }
+    /// The token right after the current one
+    fn peek(&mut self) -> Option<&Token> {
+        self.peek_nth(1)
+    }
+    /// The token `n` tokens after the current one, `peek_nth(1)` being the
+    /// next one, or `None` when it cannot be read, the error being reported
+    /// once the parser gets to it
+    fn peek_nth(&mut self, n: usize) -> Option<&Token> {
+        while self.lookahead.len() < n {
+            let token = self.lexer.get_next_token();
+            self.lookahead.push_back(token);
+        }
+        self.lookahead[n - 1].as_ref().ok()
+    }
+    /// Where the current token starts
+    fn position(&self) -> Position {
+        self.current_token.span.start.clone()
+    }
+    /// The span of the current token, which the node starting with it starts from
+    fn start(&self) -> Span {
+        self.current_token.span.clone()
+    }
+    /// The span from `start` up to the end of the token eaten last
+    fn span_from(&self, start: &Span) -> Span {
+        start.to(&self.previous_span)
+    }
+    /// An error about the current token
+    fn invalid(&self, message: impl Into<String>) -> ParseError {
+        ParseError::Invalid { message: message.into(), position: self.position() }
+    }
+    /// The error of finding the current token where `expected` should be
+    fn unexpected(&self, expected: OpType) -> ParseError {
+        ParseError::UnexpectedToken {
+            expected,
+            found: self.current_token.value.to_string(),
+            position: self.position(),
+        }
+    }
+    /// Skip the tokens up to the semicolon or END closing the statement a
+    /// syntax error was found in, or up to the end of the source. The blocks
+    /// which start inside the statement are skipped whole, and the lexical
+    /// errors in the skipped tokens are kept along with the syntax ones.
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        loop {
+            let op_type = self.current_token.op_type;
+            match op_type {
+                OpType::EOF => return,
+                OpType::SEMI | OpType::END if depth == 0 => return,
+                OpType::BEGIN | OpType::CASE | OpType::TRY | OpType::RECORD => depth += 1,
+                OpType::END => depth -= 1,
+                // in a dialect lacking exceptions TRY is read as a name, and
+                // a statement after it tells it starts a block all the same
+                OpType::ID if self.opens_try() => depth += 1,
+                _ => {}
+            }
+            // every lexical error consumes some source, so this ends
+            match self.next_token() {
+                Ok(token) => self.current_token = token,
+                Err(error) => self.errors.push(error.into()),
+            }
+        }
+    }
+
+    fn opens_try(&mut self) -> bool {
+        keyword(&self.current_token.value) == Some(OpType::TRY)
+            && self.peek().is_some_and(|token| starts_statement(token.op_type))
+    }
+
+    /// program : (PROGRAM variable SEMI)? uses_clause? block DOT
+    ///
+    /// The program header is optional so that short snippets can be typed into the REPL.
+    fn program(&mut self) -> Result<Program, ParseError> {
+        let start = self.start();
+        let mut name = None;
+        if self.current_token.op_type == OpType::PROGRAM {
+            self.eat(OpType::PROGRAM)?;
+            name = Some(self.variable()?.name);
+            self.eat(OpType::SEMI)?;
+        }
+        let uses = self.uses_clause()?;
+        self.definitions.push(self.used_definitions(&uses));
+        let block = self.block()?;
+        self.definitions.pop();
+        self.eat(OpType::DOT)?;
+        let units = std::mem::take(&mut self.units);
+        Ok(Program::new(name, uses, units, block, self.span_from(&start)))
+    }
+    /// uses_clause : USES ID (COMMA ID)* SEMI
+    ///
+    /// A unit named for the first time is read from its file as if its text
+    /// stood right after its name, so the switch directives it sets stay set
+    /// after it.
+    fn uses_clause(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut uses = Vec::new();
+        if self.current_token.op_type != OpType::USES {
+            return Ok(uses);
+        }
+        self.require(Feature::Units)?;
+        self.eat(OpType::USES)?;
+        loop {
+            let name = self.current_token.value.to_string();
+            if uses.iter().any(|used| same_name(used, &name)) {
+                return Err(self.invalid(format!("unit {} is used twice", name)));
+            }
+            if self.loading.iter().any(|loading| same_name(loading, &name)) {
+                let chain = self.loading.join(" uses ");
+                let message = format!("circular reference between units: {} uses {}", chain, name);
+                return Err(self.invalid(message));
+            }
+            let is_new = self.current_token.op_type == OpType::ID
+                && !self.units.iter().any(|unit| same_name(&unit.name, &name));
+            // the file has to be spliced in before the name is eaten, as
+            // eating it reads the token after it, and nothing may be peeked
+            // at past the name
+            let depth = self.lexer.include_depth();
+            if is_new {
+                self.lexer.include_unit(&name)?;
+            }
+            self.eat(OpType::ID)?;
+            if is_new {
+                let unit = self.unit(&name)?;
+                if self.lexer.include_depth() > depth {
+                    let token = &self.current_token.value;
+                    let message = format!("unexpected token {} after end of unit {}", token, name);
+                    return Err(self.invalid(message));
+                }
+                self.units.push(unit);
+            }
+            uses.push(name);
+            if self.current_token.op_type != OpType::COMMA {
+                break;
+            }
+            self.eat(OpType::COMMA)?;
+        }
+        self.eat(OpType::SEMI)?;
+        Ok(uses)
+    }
+    /// unit : UNIT ID SEMI
+    ///        INTERFACE uses_clause? declarations
+    ///        IMPLEMENTATION uses_clause? declarations
+    ///        (INITIALIZATION statement_list END | compound_statement | END) DOT
+    fn unit(&mut self, name: &str) -> Result<Unit, ParseError> {
+        let start = self.start();
+        self.eat(OpType::UNIT)?;
+        if !same_name(&self.current_token.value, name) {
+            let message =
+                format!("unit {} is named {} in its file", name, self.current_token.value);
+            return Err(self.invalid(message));
+        }
+        self.eat(OpType::ID)?;
+        self.eat(OpType::SEMI)?;
+        // a unit sees the constants and types of the units it uses, not those
+        // of the program or unit using it
+        let outer_definitions = std::mem::take(&mut self.definitions);
+        self.loading.push(String::from(name));
+        self.eat(OpType::INTERFACE)?;
+        let mut uses = self.uses_clause()?;
+        self.definitions.push(self.used_definitions(&uses));
+        self.definitions.push(Names::new());
+        self.in_interface = true;
+        let mut declarations = self.declarations()?;
+        self.in_interface = false;
+        let interface = declared_names(&declarations);
+        let interface_definitions = self.definitions.last().unwrap().clone();
+        self.eat(OpType::IMPLEMENTATION)?;
+        let implementation_uses = self.uses_clause()?;
+        let used_definitions = self.used_definitions(&implementation_uses);
+        self.definitions[0].extend(used_definitions);
+        uses.extend(implementation_uses);
+        declarations.append(&mut self.declarations()?);
+        let initialization_start = self.start();
+        let initialization = match self.current_token.op_type {
+            OpType::INITIALIZATION => {
+                self.eat(OpType::INITIALIZATION)?;
+                let children = self.statement_list()?;
+                self.eat(OpType::END)?;
+                Compound::new(children, self.span_from(&initialization_start))
+            }
+            OpType::BEGIN => self.compound_statement()?,
+            _ => {
+                self.eat(OpType::END)?;
+                Compound::new(Vec::new(), self.span_from(&initialization_start))
+            }
+        };
+        self.eat(OpType::DOT)?;
+        let span = self.span_from(&start);
+        self.definitions = outer_definitions;
+        self.loading.pop();
+        self.unit_definitions.insert(String::from(name), interface_definitions);
+        let block = Block::new(declarations, initialization, span);
+        Ok(Unit::new(name, uses, interface, block))
+    }
+    /// The constants and types declared by the interfaces of the units named,
+    /// those of the units named later hiding the others
+    fn used_definitions(&self, uses: &[String]) -> Names<Definition> {
+        let mut definitions = Names::new();
+        for name in uses {
+            definitions.extend(self.unit_definitions[name].clone());
+        }
+        definitions
+    }
+    /// block : declarations compound_statement
+    fn block(&mut self) -> Result<Block, ParseError> {
+        let start = self.start();
+        self.definitions.push(Names::new());
+        let declarations = self.declarations()?;
+        let compound_statement = self.compound_statement()?;
+        self.definitions.pop();
+        Ok(Block::new(declarations, compound_statement, self.span_from(&start)))
+    }
+    /// declarations : (LABEL label (COMMA label)* SEMI
+    ///                | CONST (const_declaration SEMI)+
+    ///                | TYPE (type_declaration SEMI)+
+    ///                | VAR (variable_declaration SEMI)+
+    ///                | procedure_declaration
+    ///                | function_declaration)*
+    fn declarations(&mut self) -> Result<Vec<Decl>, ParseError> {
+        let mut declarations = Vec::new();
+        loop {
+            // the identifiers of an enumerated type are declared as constants
+            // right after the declaration the type appears in
+            declarations.append(&mut self.enum_constants);
+            match self.current_token.op_type {
+                OpType::LABEL => {
+                    self.eat(OpType::LABEL)?;
+                    let span = self.start();
+                    declarations.push(Decl::Label(LabelDecl::new(&self.label()?, span)));
+                    while self.current_token.op_type == OpType::COMMA {
+                        self.eat(OpType::COMMA)?;
+                        let span = self.start();
+                        declarations.push(Decl::Label(LabelDecl::new(&self.label()?, span)));
+                    }
+                    self.eat(OpType::SEMI)?;
+                }
+                OpType::CONST => {
+                    self.eat(OpType::CONST)?;
+                    while self.declares(&[OpType::EQUAL, OpType::COLON]) {
+                        declarations.push(self.const_declaration()?);
+                        self.eat(OpType::SEMI)?;
+                    }
+                }
+                OpType::TYPE => {
+                    self.eat(OpType::TYPE)?;
+                    while self.declares(&[OpType::EQUAL]) {
+                        declarations.push(self.type_declaration()?);
+                        self.eat(OpType::SEMI)?;
+                    }
+                }
+                OpType::VAR => {
+                    self.eat(OpType::VAR)?;
+                    while self.declares(&[OpType::COMMA, OpType::COLON]) {
+                        declarations.append(&mut self.variable_declaration()?);
+                        self.eat(OpType::SEMI)?;
+                    }
+                }
+                OpType::PROCEDURE => declarations.push(self.procedure_declaration()?),
+                OpType::FUNCTION => declarations.push(self.function_declaration()?),
+                _ => break,
+            }
+        }
+        Ok(declarations)
+    }
+    /// Whether the current token starts another declaration of a CONST, TYPE
+    /// or VAR section, being an identifier. In an input typed in at a REPL,
+    /// the identifier has to be followed by one of `follows`, anything else
+    /// starting the statements after the section.
+    fn declares(&mut self, follows: &[OpType]) -> bool {
+        if self.current_token.op_type != OpType::ID {
+            return false;
+        }
+        !self.in_input || self.peek().is_some_and(|token| follows.contains(&token.op_type))
+    }
+    /// procedure_declaration : PROCEDURE ID (LPAREN formal_parameter_list RPAREN)? SEMI
+    ///                         ((block | FORWARD) SEMI)?
+    ///
+    /// where only the interface of a unit leaves out the block.
+    fn procedure_declaration(&mut self) -> Result<Decl, ParseError> {
+        let start = self.start();
+        self.eat(OpType::PROCEDURE)?;
+        let name = self.current_token.value.to_string();
+        self.eat(OpType::ID)?;
+        let mut params = Vec::new();
+        if self.current_token.op_type == OpType::LPAREN {
+            self.eat(OpType::LPAREN)?;
+            params = self.formal_parameter_list()?;
+            self.eat(OpType::RPAREN)?;
+        }
+        self.eat(OpType::SEMI)?;
+        if self.in_interface {
+            return Ok(Decl::Forward(ForwardDecl::new(&name, params, None, self.span_from(&start))));
+        }
+        if self.current_token.op_type == OpType::FORWARD {
+            self.eat(OpType::FORWARD)?;
+            self.eat(OpType::SEMI)?;
+            return Ok(Decl::Forward(ForwardDecl::new(&name, params, None, self.span_from(&start))));
+        }
+        let block = self.block()?;
+        self.eat(OpType::SEMI)?;
+        let span = self.span_from(&start);
+        Ok(Decl::Procedure(Rc::new(ProcedureDecl::new(&name, params, block, span))))
+    }
+    /// function_declaration : FUNCTION ID (LPAREN formal_parameter_list RPAREN)? COLON type_spec
+    ///                        SEMI ((block | FORWARD) SEMI)?
+    ///
+    /// where only the interface of a unit leaves out the block.
+    fn function_declaration(&mut self) -> Result<Decl, ParseError> {
+        let start = self.start();
+        self.eat(OpType::FUNCTION)?;
+        let name = self.current_token.value.to_string();
+        self.eat(OpType::ID)?;
+        let mut params = Vec::new();
+        if self.current_token.op_type == OpType::LPAREN {
+            self.eat(OpType::LPAREN)?;
+            params = self.formal_parameter_list()?;
+            self.eat(OpType::RPAREN)?;
+        }
+        self.eat(OpType::COLON)?;
+        let return_type = self.type_spec()?;
+        self.eat(OpType::SEMI)?;
+        if self.in_interface {
+            let span = self.span_from(&start);
+            return Ok(Decl::Forward(ForwardDecl::new(&name, params, Some(return_type), span)));
+        }
+        if self.current_token.op_type == OpType::FORWARD {
+            self.eat(OpType::FORWARD)?;
+            self.eat(OpType::SEMI)?;
+            let span = self.span_from(&start);
+            return Ok(Decl::Forward(ForwardDecl::new(&name, params, Some(return_type), span)));
+        }
+        let block = self.block()?;
+        self.eat(OpType::SEMI)?;
+        let span = self.span_from(&start);
+        Ok(Decl::Function(Rc::new(FunctionDecl::new(&name, params, return_type, block, span))))
+    }
+    /// formal_parameter_list : formal_parameters (SEMI formal_parameters)*
+    fn formal_parameter_list(&mut self) -> Result<Vec<VarDecl>, ParseError> {
+        let mut params = self.formal_parameters()?;
+        while self.current_token.op_type == OpType::SEMI {
+            self.eat(OpType::SEMI)?;
+            params.append(&mut self.formal_parameters()?);
+        }
+        Ok(params)
+    }
+    /// formal_parameters : CONST? ID (COMMA ID)* COLON type_spec
+    ///                   | routine_parameter
+    fn formal_parameters(&mut self) -> Result<Vec<VarDecl>, ParseError> {
+        if matches!(self.current_token.op_type, OpType::PROCEDURE | OpType::FUNCTION) {
+            return Ok(vec![self.routine_parameter()?]);
+        }
+        let is_const = self.current_token.op_type == OpType::CONST;
+        if is_const {
+            self.require(Feature::ConstParameters)?;
+            self.eat(OpType::CONST)?;
+        }
+        let mut var_nodes = vec![self.variable()?];
+        while self.current_token.op_type == OpType::COMMA {
+            self.eat(OpType::COMMA)?;
+            var_nodes.push(self.variable()?);
+        }
+        self.eat(OpType::COLON)?;
+        let type_node = self.type_spec()?;
+        Ok(var_nodes
+            .into_iter()
+            .map(|var_node| match is_const {
+                true => VarDecl::new_const(var_node, type_node.clone()),
+                false => VarDecl::new(var_node, type_node.clone()),
+            })
+            .collect())
+    }
+    /// routine_parameter : (PROCEDURE | FUNCTION) ID routine_heading
+    fn routine_parameter(&mut self) -> Result<VarDecl, ParseError> {
+        let is_function = self.current_token.op_type == OpType::FUNCTION;
+        self.eat(self.current_token.op_type)?;
+        let var_node = self.variable()?;
+        Ok(VarDecl::new(var_node, self.routine_heading(is_function)?))
+    }
+    /// routine_heading : (LPAREN formal_parameter_list RPAREN)? (COLON type_spec)?
+    ///
+    /// The type of the routines with the heading following PROCEDURE or FUNCTION.
+    /// The names of the parameters do not matter, only their types.
+    fn routine_heading(&mut self, is_function: bool) -> Result<Type, ParseError> {
+        let (mut params, mut names) = (Vec::new(), Vec::new());
+        if self.current_token.op_type == OpType::LPAREN {
+            self.eat(OpType::LPAREN)?;
+            let formal_params = self.formal_parameter_list()?;
+            for param in formal_params {
+                names.push((param.var_node.name, param.is_const));
+                params.push(param.type_node);
+            }
+            self.eat(OpType::RPAREN)?;
+        }
+        let mut result = None;
+        if is_function {
+            self.eat(OpType::COLON)?;
+            result = Some(Box::new(self.type_spec()?));
+        }
+        Ok(Type::Routine { params, names: ParamNames(names), result })
+    }
+    /// const_declaration : ID EQUAL constant
+    ///                   | ID COLON type_spec EQUAL typed_constant
+    fn const_declaration(&mut self) -> Result<Decl, ParseError> {
+        let name = self.current_token.value.to_string();
+        let start = self.start();
+        self.eat(OpType::ID)?;
+        if self.current_token.op_type == OpType::COLON {
+            let (feature, dialect) = (Feature::TypedConstants, self.dialect());
+            if !dialect.allows(feature) {
+                return Err(ParseError::Unsupported { feature, dialect, position: start.start });
+            }
+            self.eat(OpType::COLON)?;
+            let type_node = self.type_spec()?;
+            self.eat(OpType::EQUAL)?;
+            // a typed constant is really a variable, so it is not added to the
+            // constants which other constants may refer to
+            let initializer = self.typed_constant()?;
+            let span = self.span_from(&start);
+            return Ok(Decl::TypedConst(TypedConstDecl::new(&name, type_node, initializer, span)));
+        }
+        self.eat(OpType::EQUAL)?;
+        let constant = self.constant()?;
+        let definition = Definition::Constant(constant.value.clone());
+        self.definitions.last_mut().unwrap().insert(name.clone(), definition);
+        Ok(Decl::Const(ConstDecl::new(&name, constant, self.span_from(&start))))
+    }
+    /// typed_constant : constant
+    ///                | LPAREN typed_constant (COMMA typed_constant)* RPAREN
+    ///                | LPAREN ID COLON typed_constant (SEMI ID COLON typed_constant)* RPAREN
+    fn typed_constant(&mut self) -> Result<Initializer, ParseError> {
+        if self.current_token.op_type != OpType::LPAREN {
+            return Ok(Initializer::Value(self.constant()?));
+        }
+        // only the colon after a leading name tells the fields of a record
+        // from an array whose first element starts with a named constant
+        let is_record = self.peek().is_some_and(|token| token.op_type == OpType::ID)
+            && self.peek_nth(2).is_some_and(|token| token.op_type == OpType::COLON);
+        self.eat(OpType::LPAREN)?;
+        let initializer = if is_record {
+            self.field_constants()?
+        } else {
+            let mut items = vec![self.typed_constant()?];
+            while self.current_token.op_type == OpType::COMMA {
+                self.eat(OpType::COMMA)?;
+                items.push(self.typed_constant()?);
+            }
+            Initializer::List(items)
+        };
+        self.eat(OpType::RPAREN)?;
+        Ok(initializer)
+    }
+    /// field_constants : ID COLON typed_constant (SEMI ID COLON typed_constant)*
+    fn field_constants(&mut self) -> Result<Initializer, ParseError> {
+        let mut fields = Vec::new();
+        loop {
+            let name = self.current_token.value.to_string();
+            self.eat(OpType::ID)?;
+            self.eat(OpType::COLON)?;
+            fields.push((name, self.typed_constant()?));
+            if self.current_token.op_type != OpType::SEMI {
+                return Ok(Initializer::Fields(fields));
+            }
+            self.eat(OpType::SEMI)?;
+        }
+    }
+    /// type_declaration : ID EQUAL type_spec
+    fn type_declaration(&mut self) -> Result<Decl, ParseError> {
+        let (name, start) = (self.current_token.value.to_string(), self.start());
+        self.eat(OpType::ID)?;
+        self.eat(OpType::EQUAL)?;
+        let type_node = self.type_spec()?;
+        let definition = Definition::Type(type_node.clone());
+        self.definitions.last_mut().unwrap().insert(name.clone(), definition);
+        Ok(Decl::Type(TypeDecl::new(&name, type_node, self.span_from(&start))))
+    }
+    /// variable_declaration : ID (COMMA ID)* COLON type_spec
+    fn variable_declaration(&mut self) -> Result<Vec<Decl>, ParseError> {
+        let mut var_nodes = vec![self.variable()?];
+        while self.current_token.op_type == OpType::COMMA {
+            self.eat(OpType::COMMA)?;
+            var_nodes.push(self.variable()?);
+        }
+        self.eat(OpType::COLON)?;
+        let type_node = self.type_spec()?;
+        Ok(var_nodes
+            .into_iter()
+            .map(|var_node| Decl::Var(VarDecl::new(var_node, type_node.clone())))
+            .collect())
+    }
+    /// type_spec : INTEGER | REAL | BOOLEAN | CHAR | STRING | TEXT | ID
+    ///           | enumerated_type
+    ///           | subrange_type
+    ///           | array_type
+    ///           | set_type
+    ///           | file_type
+    ///           | record_type
+    ///           | pointer_type
+    ///           | (PROCEDURE | FUNCTION) routine_heading
+    fn type_spec(&mut self) -> Result<Type, ParseError> {
+        let type_node = match self.current_token.op_type {
+            OpType::INTEGER => Type::Integer,
+            OpType::REAL => Type::Real,
+            OpType::BOOLEAN => Type::Boolean,
+            OpType::CHAR => Type::Char,
+            OpType::STRING => {
+                self.require(Feature::StringType)?;
+                Type::String
+            }
+            OpType::TEXT => Type::Text,
+            OpType::ID if self.lookup_constant(&self.current_token.value).is_some() => {
+                return self.subrange_type()
+            }
+            OpType::ID if self.starts_type_function() => return self.subrange_type(),
+            OpType::ID => Type::Named(self.current_token.value.to_string()),
+            OpType::LPAREN => return self.enumerated_type(),
+            OpType::ARRAY => return self.array_type(false),
+            // packing changes nothing but making an array of characters a string
+            OpType::PACKED => {
+                self.eat(OpType::PACKED)?;
+                return match self.current_token.op_type {
+                    OpType::ARRAY => self.array_type(true),
+                    OpType::RECORD | OpType::SET => self.type_spec(),
+                    _ => Err(self.invalid("expected a structured type after PACKED")),
+                };
+            }
+            OpType::SET => {
+                self.eat(OpType::SET)?;
+                self.eat(OpType::OF)?;
+                return Ok(Type::Set(Box::new(self.type_spec()?)))
+            }
+            OpType::FILE => {
+                self.eat(OpType::FILE)?;
+                self.eat(OpType::OF)?;
+                return Ok(Type::File(Box::new(self.type_spec()?)))
+            }
+            OpType::RECORD => return self.record_type(),
+            OpType::PROCEDURE | OpType::FUNCTION => {
+                self.require(Feature::ProceduralTypes)?;
+                let is_function = self.current_token.op_type == OpType::FUNCTION;
+                self.eat(self.current_token.op_type)?;
+                return self.routine_heading(is_function)
+            }
+            OpType::CARET => {
+                self.eat(OpType::CARET)?;
+                return Ok(Type::Pointer(Box::new(self.type_spec()?)))
+            }
+            OpType::INTEGER_CONST | OpType::CHAR_CONST | OpType::PLUS | OpType::MINUS => {
+                return self.subrange_type()
+            }
+            _ => return Err(self.invalid(format!("unknown type {}", self.current_token.value)))
+        };
+        self.eat(self.current_token.op_type)?;
+        Ok(type_node)
+    }
+    /// enumerated_type : LPAREN ID (COMMA ID)* RPAREN
+    fn enumerated_type(&mut self) -> Result<Type, ParseError> {
+        self.eat(OpType::LPAREN)?;
+        let mut variables = vec![self.variable()?];
+        while self.current_token.op_type == OpType::COMMA {
+            self.eat(OpType::COMMA)?;
+            variables.push(self.variable()?);
+        }
+        self.eat(OpType::RPAREN)?;
+        let names: Rc<[String]> = variables.iter().map(|variable| variable.name.clone()).collect();
+        for (ordinal, variable) in variables.into_iter().enumerate() {
+            let value = Value::Enum { ordinal: ordinal as i32, names: names.clone() };
+            let definition = Definition::Constant(value.clone());
+            self.definitions.last_mut().unwrap().insert(variable.name.clone(), definition);
+            // the identifier stands for itself
+            let source = Expr::Var(Var::new(&variable.name, variable.span.clone()));
+            let constant = Constant::new(value, source);
+            let declaration = ConstDecl::new(&variable.name, constant, variable.span);
+            self.enum_constants.push(Decl::Const(declaration));
+        }
+        Ok(Type::Enum(names))
+    }
+    /// subrange_type : constant RANGE constant
+    fn subrange_type(&mut self) -> Result<Type, ParseError> {
+        let low = self.constant()?;
+        self.eat(OpType::RANGE)?;
+        let high = self.constant()?;
+        let (low_value, high_value) = (&low.value, &high.value);
+        if std::mem::discriminant(low_value) != std::mem::discriminant(high_value) {
+            let message =
+                format!("bounds {} and {} of a subrange differ in type", low_value, high_value);
+            return Err(self.invalid(message));
+        }
+        let invalid = |error: RuntimeError| self.invalid(error.to_string());
+        if high_value.ordinal().map_err(invalid)? < low_value.ordinal().map_err(invalid)? {
+            return Err(self.invalid(format!("empty subrange {}..{}", low_value, high_value)));
+        }
+        Ok(Type::Subrange { low, high })
+    }
+    /// set_type : SET OF type_spec
+    ///
+    /// file_type : FILE OF type_spec
+    ///
+    /// pointer_type : CARET type_spec
+    ///
+    /// record_type : RECORD field_list END
+    fn record_type(&mut self) -> Result<Type, ParseError> {
+        self.eat(OpType::RECORD)?;
+        let mut fields = Vec::new();
+        let mut variant_parts = Vec::new();
+        self.field_list(&mut fields, &mut variant_parts)?;
+        self.eat(OpType::END)?;
+        Ok(Type::Record { fields, variant_parts })
+    }
+    /// field_list : (field_declaration (SEMI field_declaration)*)? SEMI? variant_part?
+    ///
+    /// field_declaration : ID (COMMA ID)* COLON type_spec
+    ///
+    /// The fields of the variants are added to those of the record itself,
+    /// all of them sharing one namespace.
+    fn field_list(
+        &mut self,
+        fields: &mut Vec<(String, Type)>,
+        variant_parts: &mut Vec<VariantPart>,
+    ) -> Result<(), ParseError> {
+        while self.current_token.op_type == OpType::ID {
+            let mut names = vec![self.variable()?.name];
+            while self.current_token.op_type == OpType::COMMA {
+                self.eat(OpType::COMMA)?;
+                names.push(self.variable()?.name);
+            }
+            self.eat(OpType::COLON)?;
+            let type_node = self.type_spec()?;
+            for name in names {
+                self.add_field(fields, name, type_node.clone())?;
+            }
+            if self.current_token.op_type != OpType::SEMI {
+                break;
+            }
+            self.eat(OpType::SEMI)?;
+        }
+        if self.current_token.op_type == OpType::CASE {
+            self.variant_part(fields, variant_parts)?;
+        }
+        Ok(())
+    }
+    /// variant_part : CASE (ID COLON)? type_spec OF variant (SEMI variant)* SEMI?
+    ///
+    /// variant : constant (COMMA constant)* COLON LPAREN field_list RPAREN
+    fn variant_part(
+        &mut self,
+        fields: &mut Vec<(String, Type)>,
+        variant_parts: &mut Vec<VariantPart>,
+    ) -> Result<(), ParseError> {
+        self.eat(OpType::CASE)?;
+        let mut tag = None;
+        let tag_type;
+        if self.current_token.op_type == OpType::ID {
+            let name = self.current_token.value.to_string();
+            self.eat(OpType::ID)?;
+            // without a colon the name is the type of a tag the record does not keep
+            if self.current_token.op_type == OpType::COLON {
+                self.eat(OpType::COLON)?;
+                tag_type = self.type_spec()?;
+                self.add_field(fields, name.clone(), tag_type.clone())?;
+                tag = Some(name);
+            } else {
+                tag_type = Type::Named(name);
+            }
+        } else {
+            tag_type = self.type_spec()?;
+        }
+        self.eat(OpType::OF)?;
+        let mut variants = Vec::new();
+        loop {
+            let mut labels = vec![self.constant()?];
+            while self.current_token.op_type == OpType::COMMA {
+                self.eat(OpType::COMMA)?;
+                labels.push(self.constant()?);
+            }
+            self.eat(OpType::COLON)?;
+            self.eat(OpType::LPAREN)?;
+            let first = fields.len();
+            self.field_list(fields, variant_parts)?;
+            self.eat(OpType::RPAREN)?;
+            variants.push((labels, fields[first..].iter().map(|(name, _)| name.clone()).collect()));
+            if self.current_token.op_type != OpType::SEMI {
+                break;
+            }
+            self.eat(OpType::SEMI)?;
+            if matches!(self.current_token.op_type, OpType::END | OpType::RPAREN) {
+                break;
+            }
+        }
+        variant_parts.push(VariantPart { tag, tag_type: Box::new(tag_type), variants });
+        Ok(())
+    }
+    fn add_field(
+        &self,
+        fields: &mut Vec<(String, Type)>,
+        name: String,
+        type_node: Type,
+    ) -> Result<(), ParseError> {
+        if fields.iter().any(|(field, _)| same_name(field, &name)) {
+            return Err(self.invalid(format!("duplicate field {} in record", name)));
+        }
+        fields.push((name, type_node));
+        Ok(())
+    }
+    /// array_type : ARRAY LBRACKET type_spec (COMMA type_spec)* RBRACKET OF type_spec
+    ///
+    /// `ARRAY[1..2, 1..3] OF t` is short for `ARRAY[1..2] OF ARRAY[1..3] OF t`.
+    fn array_type(&mut self, packed: bool) -> Result<Type, ParseError> {
+        self.eat(OpType::ARRAY)?;
+        if self.current_token.op_type == OpType::OF {
+            self.require(Feature::DynamicArrays)?;
+            self.eat(OpType::OF)?;
+            return Ok(Type::DynamicArray(Box::new(self.type_spec()?)));
+        }
+        self.eat(OpType::LBRACKET)?;
+        let mut indices = vec![self.type_spec()?];
+        while self.current_token.op_type == OpType::COMMA {
+            self.eat(OpType::COMMA)?;
+            indices.push(self.type_spec()?);
+        }
+        self.eat(OpType::RBRACKET)?;
+        self.eat(OpType::OF)?;
+        let element = self.type_spec()?;
+        Ok(indices.into_iter().rev().fold(element, |element, index| {
+            Type::Array { index: Box::new(index), element: Box::new(element), packed }
+        }))
+    }
+    /// exit_statement : EXIT (LPAREN expr? RPAREN)?
+    fn exit_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        self.eat(OpType::EXIT)?;
+        let mut result = None;
+        if self.current_token.op_type == OpType::LPAREN {
+            self.eat(OpType::LPAREN)?;
+            if self.current_token.op_type != OpType::RPAREN {
+                result = Some(self.expr()?);
+            }
+            self.eat(OpType::RPAREN)?;
+        }
+        Ok(Stmt::Exit(Exit::new(result, self.span_from(&start))))
+    }
+    /// try_statement : TRY statement_list EXCEPT exception_handlers END
+    ///               | TRY statement_list FINALLY statement_list END
+    fn try_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        self.eat(OpType::TRY)?;
+        let body = self.statement_list()?;
+        if self.current_token.op_type == OpType::FINALLY {
+            self.eat(OpType::FINALLY)?;
+            let finally = self.statement_list()?;
+            self.eat(OpType::END)?;
+            return Ok(Stmt::TryFinally(TryFinally::new(body, finally, self.span_from(&start))));
+        }
+        self.eat(OpType::EXCEPT)?;
+        let (handlers, else_branch) = self.exception_handlers()?;
+        self.eat(OpType::END)?;
+        let span = self.span_from(&start);
+        Ok(Stmt::TryExcept(TryExcept::new(body, handlers, else_branch, span)))
+    }
+    /// exception_handlers : exception_handler (SEMI exception_handler)* SEMI?
+    ///                      (ELSE statement_list)?
+    ///                    | statement_list
+    fn exception_handlers(&mut self) -> Result<ExceptParts, ParseError> {
+        if !self.at_exception_handler() {
+            return Ok((Vec::new(), Some(self.statement_list()?)));
+        }
+        let mut handlers = vec![self.exception_handler()?];
+        while self.current_token.op_type == OpType::SEMI {
+            self.eat(OpType::SEMI)?;
+            if !self.at_exception_handler() {
+                break;
+            }
+            handlers.push(self.exception_handler()?);
+        }
+        let mut else_branch = None;
+        if self.current_token.op_type == OpType::ELSE {
+            self.eat(OpType::ELSE)?;
+            else_branch = Some(self.statement_list()?);
+        }
+        Ok((handlers, else_branch))
+    }
+    /// Whether an exception handler starts at the current token; ON is not
+    /// reserved, and only followed by a name where it starts one
+    fn at_exception_handler(&mut self) -> bool {
+        self.current_token.op_type == OpType::ID
+            && self.current_token.value.eq_ignore_ascii_case("ON")
+            && self.peek().is_some_and(|token| token.op_type == OpType::ID)
+    }
+    /// exception_handler : ON (ID COLON)? ID DO statement
+    fn exception_handler(&mut self) -> Result<ExceptionHandler, ParseError> {
+        self.eat(OpType::ID)?;
+        let mut class = self.current_token.value.to_string();
+        self.eat(OpType::ID)?;
+        let mut variable = None;
+        if self.current_token.op_type == OpType::COLON {
+            self.eat(OpType::COLON)?;
+            variable = Some(class);
+            class = self.current_token.value.to_string();
+            self.eat(OpType::ID)?;
+        }
+        self.eat(OpType::DO)?;
+        Ok(ExceptionHandler::new(variable, &class, self.statement()?))
+    }
+    /// raise_statement : RAISE expr?
+    fn raise_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        self.eat(OpType::RAISE)?;
+        let message = match self.current_token.op_type {
+            OpType::SEMI | OpType::END | OpType::ELSE | OpType::UNTIL | OpType::EXCEPT
+            | OpType::FINALLY => None,
+            _ => Some(self.expr()?),
+        };
+        Ok(Stmt::Raise(Raise::new(message, self.span_from(&start))))
+    }
+    /// compound_statement : BEGIN statement_list END
+    fn compound_statement(&mut self) -> Result<Compound, ParseError> {
+        let start = self.start();
+        self.eat(OpType::BEGIN)?;
+        let children = self.statement_list()?;
+        self.eat(OpType::END)?;
+        Ok(Compound::new(children, self.span_from(&start)))
+    }
+    /// statement_list : statement (SEMI statement)*
+    ///
+    /// A statement with a syntax error is skipped and the error kept, so that
+    /// the errors of the statements after it are found in the same run.
+    fn statement_list(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut children = vec![self.recovering_statement()?];
+        loop {
+            match self.current_token.op_type {
+                OpType::SEMI => self.eat(OpType::SEMI)?,
+                OpType::END | OpType::UNTIL | OpType::EXCEPT | OpType::FINALLY | OpType::EOF => {
+                    break
+                }
+                // the dot ending a whole program run as an input
+                OpType::DOT if self.in_input => break,
+                // a statement right after another one misses the semicolon
+                // between them, and is parsed as if it were there
+                op_type if starts_statement(op_type) => {
+                    self.errors.push(self.unexpected(OpType::SEMI))
+                }
+                _ => {
+                    self.errors.push(self.unexpected(OpType::SEMI));
+                    self.synchronize();
+                    continue;
+                }
+            }
+            children.push(self.recovering_statement()?);
+        }
+        Ok(children)
+    }
+    /// A statement, or an empty one in place of a statement with a syntax
+    /// error, which is kept for `parse` to report
+    fn recovering_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        let loop_depth = self.loop_depth;
+        match self.statement() {
+            Ok(node) => Ok(node),
+            Err(error) => {
+                // the loops the error was found in are left unfinished
+                self.loop_depth = loop_depth;
+                self.errors.push(error);
+                self.synchronize();
+                Ok(Stmt::NoOp(start.empty()))
+            }
+        }
+    }
+    /// statement : compound_statement
+    ///           | assignment_statement
+    ///           | proccall_statement
+    ///           | if_statement
+    ///           | while_statement
+    ///           | for_statement
+    ///           | repeat_statement
+    ///           | case_statement
+    ///           | with_statement
+    ///           | BREAK
+    ///           | CONTINUE
+    ///           | exit_statement
+    ///           | try_statement
+    ///           | raise_statement
+    ///           | GOTO label
+    ///           | label COLON statement
+    ///           | empty
+    fn statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        Ok(match self.current_token.op_type {
+            OpType::BEGIN => Stmt::Compound(self.compound_statement()?),
+            OpType::IF => self.if_statement()?,
+            OpType::WHILE => self.while_statement()?,
+            OpType::FOR => self.for_statement()?,
+            OpType::REPEAT => self.repeat_statement()?,
+            OpType::CASE => self.case_statement()?,
+            OpType::WITH => self.with_statement()?,
+            OpType::ID => match self.misplaced_keyword() {
+                Some(feature) => return Err(self.unsupported(feature)),
+                None => self.assignment_or_call_statement()?,
+            },
+            OpType::BREAK | OpType::CONTINUE => self.loop_control_statement()?,
+            OpType::EXIT => self.exit_statement()?,
+            OpType::TRY => self.try_statement()?,
+            OpType::RAISE => self.raise_statement()?,
+            OpType::GOTO => {
+                self.eat(OpType::GOTO)?;
+                let label = self.label()?;
+                Stmt::Goto(Goto::new(&label, self.span_from(&start)))
+            }
+            OpType::INTEGER_CONST => {
+                let label = self.label()?;
+                self.eat(OpType::COLON)?;
+                let statement = self.statement()?;
+                Stmt::Labeled(Labeled::new(&label, statement, self.span_from(&start)))
+            }
+            _ => Stmt::NoOp(start.empty()),
+        })
+    }
+    /// label : INTEGER_CONST
+    ///
+    /// Labels are told apart by their value, so `007` is the same label as `7`.
+    fn label(&mut self) -> Result<String, ParseError> {
+        let label = match self.current_token.value.parse::<u32>() {
+            Ok(label) if label <= 9999 => label.to_string(),
+            _ => {
+                let message = format!("label {} is out of range 0..9999", self.current_token.value);
+                return Err(self.invalid(message));
+            }
+        };
+        self.eat(OpType::INTEGER_CONST)?;
+        Ok(label)
+    }
+    /// BREAK and CONTINUE only make sense inside a loop
+    fn loop_control_statement(&mut self) -> Result<Stmt, ParseError> {
+        if self.loop_depth == 0 {
+            return Err(self.invalid(format!("{} outside of a loop", self.current_token.value)));
+        }
+        let span = self.start();
+        if self.current_token.op_type == OpType::BREAK {
+            self.eat(OpType::BREAK)?;
+            Ok(Stmt::Break(span))
+        } else {
+            self.eat(OpType::CONTINUE)?;
+            Ok(Stmt::Continue(span))
+        }
+    }
+    /// The extension whose keyword an identifier starting a statement is
+    /// spelled like, in a dialect lacking it, when the token after it could
+    /// not follow a name, as with the statements after TRY or the message
+    /// after RAISE
+    fn misplaced_keyword(&mut self) -> Option<Feature> {
+        let feature = keyword(&self.current_token.value).and_then(Feature::of_keyword)?;
+        let follows_name = matches!(
+            self.peek().map(|token| token.op_type),
+            None | Some(
+                OpType::ASSIGN
+                    | OpType::LBRACKET
+                    | OpType::DOT
+                    | OpType::CARET
+                    | OpType::LPAREN
+                    | OpType::SEMI
+                    | OpType::END
+                    | OpType::ELSE
+                    | OpType::UNTIL
+                    | OpType::EXCEPT
+                    | OpType::FINALLY
+                    | OpType::EOF
+            )
+        );
+        (!follows_name).then_some(feature)
+    }
+    /// Both statements start with an identifier, only the token after it tells them apart
+    fn assignment_or_call_statement(&mut self) -> Result<Stmt, ParseError> {
+        match self.peek().map(|token| token.op_type) {
+            Some(OpType::ASSIGN | OpType::LBRACKET | OpType::DOT | OpType::CARET) => {
+                self.assignment_statement()
+            }
+            _ => self.proccall_statement(),
+        }
+    }
+    /// assignment_statement : variable_access ASSIGN expr
+    fn assignment_statement(&mut self) -> Result<Stmt, ParseError> {
+        let variable = self.variable()?;
+        let start = variable.span.clone();
+        let left = self.variable_access(variable)?;
+        self.eat(OpType::ASSIGN)?;
+        let right = self.expr()?;
+        Ok(Stmt::Assign(Assign::new(left, right, self.span_from(&start))))
+    }
+    /// proccall_statement : ID (actual_parameters | write_parameters)?
+    ///
+    /// Only the arguments of WRITE, WRITELN and STR may carry a field width and precision.
+    fn proccall_statement(&mut self) -> Result<Stmt, ParseError> {
+        let Var { name, span: start } = self.variable()?;
+        let mut actual_params = Vec::new();
+        if self.current_token.op_type == OpType::LPAREN {
+            let formatted = ["WRITE", "WRITELN", "STR"];
+            if formatted.iter().any(|procedure| procedure.eq_ignore_ascii_case(&name)) {
+                actual_params = self.write_parameters()?;
+            } else {
+                actual_params = self.actual_parameters()?;
+            }
+        }
+        Ok(Stmt::ProcedureCall(ProcedureCall::new(&name, actual_params, self.span_from(&start))))
+    }
+    /// write_parameters : LPAREN (write_parameter (COMMA write_parameter)*)? RPAREN
+    fn write_parameters(&mut self) -> Result<Vec<Expr>, ParseError> {
+        let mut actual_params = Vec::new();
+        self.eat(OpType::LPAREN)?;
+        if self.current_token.op_type != OpType::RPAREN {
+            actual_params.push(self.write_parameter()?);
+            while self.current_token.op_type == OpType::COMMA {
+                self.eat(OpType::COMMA)?;
+                actual_params.push(self.write_parameter()?);
+            }
+        }
+        self.eat(OpType::RPAREN)?;
+        Ok(actual_params)
+    }
+    /// write_parameter : expr (COLON expr (COLON expr)?)?
+    fn write_parameter(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let expr = self.expr()?;
+        if self.current_token.op_type != OpType::COLON {
+            return Ok(expr);
+        }
+        self.eat(OpType::COLON)?;
+        let width = self.expr()?;
+        let mut precision = None;
+        if self.current_token.op_type == OpType::COLON {
+            self.eat(OpType::COLON)?;
+            precision = Some(self.expr()?);
+        }
+        let span = self.span_from(&start);
+        Ok(Expr::FormattedParam(FormattedParam::new(expr, width, precision, span)))
+    }
+    /// actual_parameters : LPAREN (expr (COMMA expr)*)? RPAREN
+    fn actual_parameters(&mut self) -> Result<Vec<Expr>, ParseError> {
+        let mut actual_params = Vec::new();
+        self.eat(OpType::LPAREN)?;
+        if self.current_token.op_type != OpType::RPAREN {
+            actual_params.push(self.expr()?);
+            while self.current_token.op_type == OpType::COMMA {
+                self.eat(OpType::COMMA)?;
+                actual_params.push(self.expr()?);
+            }
+        }
+        self.eat(OpType::RPAREN)?;
+        Ok(actual_params)
+    }
+    /// if_statement : IF expr THEN statement (ELSE statement)?
+    ///
+    /// An ELSE always belongs to the nearest IF, which falls out of the
+    /// recursion: the inner `if_statement` eats the ELSE before we see it.
+    fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        self.eat(OpType::IF)?;
+        let condition = self.expr()?;
+        self.eat(OpType::THEN)?;
+        let then_branch = self.statement()?;
+        let mut else_branch = None;
+        if self.current_token.op_type == OpType::ELSE {
+            self.eat(OpType::ELSE)?;
+            else_branch = Some(self.statement()?);
+        }
+        Ok(Stmt::If(If::new(condition, then_branch, else_branch, self.span_from(&start))))
+    }
+    /// while_statement : WHILE expr DO statement
+    fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        self.eat(OpType::WHILE)?;
+        let condition = self.expr()?;
+        self.eat(OpType::DO)?;
+        self.loop_depth += 1;
+        let body = self.statement()?;
+        self.loop_depth -= 1;
+        Ok(Stmt::While(While::new(condition, body, self.span_from(&start))))
+    }
+    /// for_statement : FOR variable ASSIGN expr (TO | DOWNTO) expr DO statement
+    fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.start();
+        self.eat(OpType::FOR)?;
+        let variable = self.variable()?;
+        self.eat(OpType::ASSIGN)?;
+        let start = self.expr()?;
+        let downto = self.current_token.op_type == OpType::DOWNTO;
+        if downto {
+            self.eat(OpType::DOWNTO)?;
+        } else {
+            self.eat(OpType::TO)?;
+        }
+        let end = self.expr()?;
+        self.eat(OpType::DO)?;
+        self.loop_depth += 1;
+        let body = self.statement()?;
+        self.loop_depth -= 1;
+        let span = self.span_from(&keyword);
+        Ok(Stmt::For(For::new(variable, start, end, downto, body, span)))
+    }
+    /// repeat_statement : REPEAT statement_list UNTIL expr
+    fn repeat_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        self.eat(OpType::REPEAT)?;
+        self.loop_depth += 1;
+        let children = self.statement_list()?;
+        self.loop_depth -= 1;
+        self.eat(OpType::UNTIL)?;
+        let condition = self.expr()?;
+        Ok(Stmt::Repeat(Repeat::new(children, condition, self.span_from(&start))))
+    }
+    /// with_statement : WITH variable_access (COMMA variable_access)* DO statement
+    ///
+    /// `WITH a, b DO s` is short for `WITH a DO WITH b DO s`.
+    fn with_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        self.eat(OpType::WITH)?;
+        let variable = self.variable()?;
+        let mut records = vec![self.variable_access(variable)?];
+        while self.current_token.op_type == OpType::COMMA {
+            self.eat(OpType::COMMA)?;
+            let variable = self.variable()?;
+            records.push(self.variable_access(variable)?);
+        }
+        self.eat(OpType::DO)?;
+        let mut node = self.statement()?;
+        let span = self.span_from(&start);
+        for record in records.into_iter().rev() {
+            node = Stmt::With(With::new(record, node, span.clone()));
+        }
+        Ok(node)
+    }
+    /// case_statement : CASE expr OF case_arm (SEMI case_arm)* SEMI? (ELSE statement_list)? END
+    fn case_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        self.eat(OpType::CASE)?;
+        let selector = self.expr()?;
+        self.eat(OpType::OF)?;
+        let mut arms = vec![self.case_arm()?];
+        while self.current_token.op_type == OpType::SEMI {
+            self.eat(OpType::SEMI)?;
+            if self.current_token.op_type == OpType::ELSE ||
+                self.current_token.op_type == OpType::END {
+                break;
+            }
+            arms.push(self.case_arm()?);
+        }
+        let mut else_branch = None;
+        if self.current_token.op_type == OpType::ELSE {
+            self.eat(OpType::ELSE)?;
+            else_branch = Some(self.statement_list()?);
+        }
+        self.eat(OpType::END)?;
+        Ok(Stmt::Case(Case::new(selector, arms, else_branch, self.span_from(&start))))
+    }
+    /// case_arm : case_label (COMMA case_label)* COLON statement
+    fn case_arm(&mut self) -> Result<CaseArm, ParseError> {
+        let mut labels = vec![self.case_label()?];
+        while self.current_token.op_type == OpType::COMMA {
+            self.eat(OpType::COMMA)?;
+            labels.push(self.case_label()?);
+        }
+        self.eat(OpType::COLON)?;
+        Ok(CaseArm::new(labels, self.statement()?))
+    }
+    /// case_label : constant (RANGE constant)?
+    ///
+    /// The labels are folded only to check that they are constants, and kept
+    /// as they are written, the interpreter computing them as it does any
+    /// other expression.
+    fn case_label(&mut self) -> Result<ValueRange, ParseError> {
+        let low = self.expr()?;
+        self.fold(&low)?;
+        let mut high = None;
+        if self.current_token.op_type == OpType::RANGE {
+            self.eat(OpType::RANGE)?;
+            let node = self.expr()?;
+            self.fold(&node)?;
+            high = Some(node);
+        }
+        Ok(ValueRange::new(low, high))
+    }
+    /// constant : expr
+    ///
+    /// where the expression is made only of literals, constants declared
+    /// before, operators and calls of the built-in functions; a constant is
+    /// folded into its value as soon as it is parsed.
+    fn constant(&mut self) -> Result<Constant, ParseError> {
+        let node = self.expr()?;
+        Ok(Constant::new(self.fold(&node)?, node))
+    }
+    /// The value of a constant expression, computed by the same operations
+    /// the interpreter applies at runtime
+    ///
+    /// An error those operations give, like a division by zero, is one of the
+    /// constant.
+    fn fold(&self, node: &Expr) -> Result<Value, ParseError> {
+        let invalid = |error: RuntimeError| self.invalid(error.to_string());
+        match node {
+            Expr::Literal(literal) => Ok(literal.value.clone()),
+            Expr::Var(var) => match self.lookup_constant(&var.name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(self.invalid(format!("{} is not a constant", var.name))),
+            },
+            Expr::BinOp(bin_op) => {
+                let (left, right) = (self.fold(&bin_op.left)?, self.fold(&bin_op.right)?);
+                binary_operation(bin_op.op_type, &left, &right, self.dialect()).map_err(invalid)
+            }
+            Expr::UnaryOp(unary_op) => {
+                unary_operation(unary_op.op_type, &self.fold(&unary_op.expr)?).map_err(invalid)
+            }
+            Expr::SetConstructor(set_constructor) => self.fold_set(set_constructor),
+            Expr::FunctionCall(call) => self.fold_call(call),
+            Expr::Index(_) | Expr::Field(_) | Expr::Deref(_) | Expr::FormattedParam(_) => {
+                Err(self.invalid("a constant cannot refer to a variable"))
+            }
+        }
+    }
+    /// The value of a set constructor made of constants
+    fn fold_set(&self, set_constructor: &SetConstructor) -> Result<Value, ParseError> {
+        let invalid = |error: RuntimeError| self.invalid(error.to_string());
+        let mut set = Set::new(None);
+        for element in &set_constructor.elements {
+            let low = self.fold(&element.low)?;
+            let inserted = match &element.high {
+                Some(high) => insert_range(&mut set, &low, &self.fold(high)?),
+                None => set.insert(&low),
+            };
+            inserted.map_err(invalid)?;
+        }
+        Ok(Value::Set(set))
+    }
+    /// The value of a built-in function called with constants
+    fn fold_call(&self, call: &FunctionCall) -> Result<Value, ParseError> {
+        let invalid = |error: RuntimeError| self.invalid(error.to_string());
+        if is_type_function(&call.name) {
+            let message = format!("the argument of {} in a constant must be a type", call.name);
+            return Err(self.invalid(message));
+        }
+        let Some(function) = builtin_function(&call.name) else {
+            return Err(self.invalid(format!("{} cannot be called in a constant", call.name)));
+        };
+        let args: Vec<Value> =
+            call.actual_params.iter().map(|arg| self.fold(arg)).collect::<Result<_, _>>()?;
+        match function.param_count {
+            usize::MAX if args.is_empty() => {
+                return Err(self.invalid(format!("{} expects at least 1 argument", call.name)));
+            }
+            usize::MAX => {}
+            count if count != args.len() => {
+                return Err(self.invalid(format!(
+                    "{} expects {} arguments but got {}",
+                    call.name, count, args.len()
+                )));
+            }
+            _ => {}
+        }
+        (function.apply)(&args).map_err(invalid)
+    }
+    fn lookup_constant(&self, name: &str) -> Option<&Value> {
+        match self.definitions.iter().rev().find_map(|definitions| definitions.get(name)) {
+            Some(Definition::Constant(value)) => Some(value),
+            _ => None,
+        }
+    }
+    /// Whether the current token calls LOW, HIGH or SIZEOF, rather than naming
+    /// a constant or type declared with that name
+    fn starts_type_function(&self) -> bool {
+        let name: &str = &self.current_token.value;
+        self.current_token.op_type == OpType::ID
+            && is_type_function(name)
+            && self.definitions.iter().all(|definitions| !definitions.contains_key(name))
+    }
+    /// type_argument : INTEGER | REAL | BOOLEAN | CHAR | STRING | TEXT | ID
+    ///
+    /// where the ID names a type declared before; the argument of LOW, HIGH
+    /// or SIZEOF when it is a type, or `None` when it is not
+    fn type_argument(&mut self) -> Result<Option<Type>, ParseError> {
+        let type_node = match self.current_token.op_type {
+            OpType::INTEGER | OpType::REAL | OpType::BOOLEAN | OpType::CHAR | OpType::STRING
+            | OpType::TEXT => self.type_spec()?,
+            OpType::ID if self.lookup_type(&self.current_token.value).is_some() => {
+                self.type_spec()?
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(type_node))
+    }
+    /// A type with the names of the types declared before in it replaced by
+    /// what they stand for, except for the types pointers refer to
+    fn resolve_type(&self, type_node: &Type) -> Result<Type, ParseError> {
+        let resolve = |type_node: &Type| self.resolve_type(type_node).map(Box::new);
+        Ok(match type_node {
+            Type::Named(name) => match self.lookup_type(name) {
+                Some(type_node) => self.resolve_type(type_node)?,
+                None => return Err(self.invalid(format!("{} is not a type", name))),
+            },
+            Type::Array { index, element, packed } => {
+                Type::Array { index: resolve(index)?, element: resolve(element)?, packed: *packed }
+            }
+            Type::DynamicArray(element) => Type::DynamicArray(resolve(element)?),
+            Type::Set(element) => Type::Set(resolve(element)?),
+            Type::File(component) => Type::File(resolve(component)?),
+            Type::Record { fields, variant_parts } => Type::Record {
+                fields: fields
+                    .iter()
+                    .map(|(name, type_node)| Ok((name.clone(), self.resolve_type(type_node)?)))
+                    .collect::<Result<_, ParseError>>()?,
+                variant_parts: variant_parts.clone(),
+            },
+            _ => type_node.clone(),
+        })
+    }
+    fn lookup_type(&self, name: &str) -> Option<&Type> {
+        match self.definitions.iter().rev().find_map(|definitions| definitions.get(name)) {
+            Some(Definition::Type(type_node)) => Some(type_node),
+            _ => None,
+        }
+    }
+    /// variable : ID
+    fn variable(&mut self) -> Result<Var, ParseError> {
+        let node = Var::new(&self.current_token.value, self.start());
+        self.eat(OpType::ID)?;
+        Ok(node)
+    }
+    /// variable_access : variable (LBRACKET expr (COMMA expr)* RBRACKET | DOT ID | CARET)*
+    fn variable_access(&mut self, variable: Var) -> Result<Expr, ParseError> {
+        let start = variable.span.clone();
+        let mut node = Expr::Var(variable);
+        loop {
+            match self.current_token.op_type {
+                OpType::LBRACKET => {
+                    self.eat(OpType::LBRACKET)?;
+                    let mut indices = vec![self.expr()?];
+                    while self.current_token.op_type == OpType::COMMA {
+                        self.eat(OpType::COMMA)?;
+                        indices.push(self.expr()?);
+                    }
+                    self.eat(OpType::RBRACKET)?;
+                    let span = self.span_from(&start);
+                    for index in indices {
+                        node = Expr::Index(Index::new(node, index, span.clone()));
+                    }
+                }
+                OpType::DOT => {
+                    self.eat(OpType::DOT)?;
+                    let name = self.current_token.value.to_string();
+                    self.eat(OpType::ID)?;
+                    node = Expr::Field(Field::new(node, &name, self.span_from(&start)));
+                }
+                OpType::CARET => {
+                    self.eat(OpType::CARET)?;
+                    node = Expr::Deref(Deref::new(node, self.span_from(&start)));
+                }
+                _ => return Ok(node),
+            }
+        }
+    }
+
+    /// factor : (PLUS | MINUS | NOT) factor
+    ///        | INTEGER_CONST
+    ///        | REAL_CONST
+    ///        | CHAR_CONST
+    ///        | STRING_CONST
+    ///        | TRUE
+    ///        | FALSE
+    ///        | NIL
+    ///        | LPAREN expr RPAREN
+    ///        | set_constructor
+    ///        | variable_access
+    ///        | ID actual_parameters
+    ///
+    /// A function without parameters is called with a bare `variable`, which is
+    /// told apart from a real variable only at runtime.
+    fn factor(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        Ok(match self.current_token.op_type {
+        OpType::PLUS | OpType::MINUS | OpType::NOT => {
+            let op_type = self.current_token.op_type;
+            self.eat(op_type)?;
+            let expr = self.factor()?;
+            Expr::UnaryOp(UnaryOp::new(op_type, expr, self.span_from(&start)))
+        },
+        OpType::TRUE | OpType::FALSE => {
+            let value = self.current_token.op_type == OpType::TRUE;
+            self.eat(self.current_token.op_type)?;
+            Expr::Literal(Literal::new(Value::Boolean(value), self.span_from(&start)))
+        },
+        OpType::INTEGER_CONST => {
+            let value = self.current_token.value.parse::<i32>().unwrap();
+            self.eat(OpType::INTEGER_CONST)?;
+            Expr::Literal(Literal::new(Value::Integer(value), self.span_from(&start)))
+        },
+        OpType::CHAR_CONST => {
+            let value = self.current_token.value.chars().next().unwrap();
+            self.eat(OpType::CHAR_CONST)?;
+            Expr::Literal(Literal::new(Value::Char(value), self.span_from(&start)))
+        },
+        OpType::STRING_CONST => {
+            let value = self.current_token.value.to_string();
+            self.eat(OpType::STRING_CONST)?;
+            Expr::Literal(Literal::new(Value::String(value), self.span_from(&start)))
+        },
+        OpType::REAL_CONST => {
+            let value = self.current_token.value.parse::<f64>().unwrap();
+            self.eat(OpType::REAL_CONST)?;
+            Expr::Literal(Literal::new(Value::Real(value), self.span_from(&start)))
+        },
+        OpType::LPAREN => {
+            self.eat(OpType::LPAREN)?;
+            let res = self.expr()?;
+            self.eat(OpType::RPAREN)?;
+            res
+        },
+        OpType::NIL => {
+            self.eat(OpType::NIL)?;
+            Expr::Literal(Literal::new(Value::Pointer(None), self.span_from(&start)))
+        },
+        OpType::LBRACKET => self.set_constructor()?,
+        OpType::ID => {
+            let variable = self.variable()?;
+            if self.current_token.op_type == OpType::LPAREN && is_type_function(&variable.name) {
+                self.type_function_call(variable)?
+            } else if self.current_token.op_type == OpType::LPAREN {
+                let params = self.actual_parameters()?;
+                let span = self.span_from(&variable.span);
+                Expr::FunctionCall(FunctionCall::new(&variable.name, params, span))
+            } else {
+                self.variable_access(variable)?
+            }
+        },
+        OpType::EOF => return Err(self.invalid("expected an expression but the source ends")),
+        _ => {
+            let message = format!("expected an expression but found {}", self.current_token.value);
+            return Err(self.invalid(message));
+        }
+        })
+    }
+    /// type_function_call : LPAREN (type_argument | expr) RPAREN
+    ///
+    /// The call of LOW, HIGH or SIZEOF is replaced by its value when it is
+    /// given a type.
+    fn type_function_call(&mut self, function: Var) -> Result<Expr, ParseError> {
+        self.eat(OpType::LPAREN)?;
+        let name = &function.name;
+        Ok(match self.type_argument()? {
+            Some(type_node) => {
+                // the function is applied to what the names in the type stand for
+                let resolved = self.resolve_type(&type_node)?;
+                let value = type_function(name, &resolved, None)
+                    .map_err(|error| self.invalid(error.to_string()))?;
+                self.eat(OpType::RPAREN)?;
+                let span = self.span_from(&function.span);
+                Expr::Literal(Literal::of_call(value, name, type_node, span))
+            }
+            None => {
+                let argument = self.expr()?;
+                self.eat(OpType::RPAREN)?;
+                let span = self.span_from(&function.span);
+                Expr::FunctionCall(FunctionCall::new(name, vec![argument], span))
+            }
+        })
+    }
+    /// set_constructor : LBRACKET (set_element (COMMA set_element)*)? RBRACKET
+    ///
+    /// set_element : expr (RANGE expr)?
+    fn set_constructor(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        self.eat(OpType::LBRACKET)?;
+        let mut elements = Vec::new();
+        if self.current_token.op_type != OpType::RBRACKET {
+            elements.push(self.set_element()?);
+            while self.current_token.op_type == OpType::COMMA {
+                self.eat(OpType::COMMA)?;
+                elements.push(self.set_element()?);
+            }
+        }
+        self.eat(OpType::RBRACKET)?;
+        Ok(Expr::SetConstructor(SetConstructor::new(elements, self.span_from(&start))))
+    }
+    fn set_element(&mut self) -> Result<ValueRange, ParseError> {
+        let low = self.expr()?;
+        let mut high = None;
+        if self.current_token.op_type == OpType::RANGE {
+            self.eat(OpType::RANGE)?;
+            high = Some(self.expr()?);
+        }
+        Ok(ValueRange::new(low, high))
+    }
+    /// term : factor ((MUL | INTEGER_DIV | MOD | FLOAT_DIV | AND) factor)*
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let mut node = self.factor()?;
+        while self.current_token.op_type == OpType::MUL ||
+            self.current_token.op_type == OpType::INTEGER_DIV ||
+            self.current_token.op_type == OpType::MOD ||
+            self.current_token.op_type == OpType::FLOAT_DIV ||
+            self.current_token.op_type == OpType::AND {
+
+            let op_type = self.current_token.op_type;
+            self.eat(op_type)?;
+            let right = self.factor()?;
+            // we construct the tree from bottom to top
+            node = Expr::BinOp(BinOp::new(op_type, node, right, self.span_from(&start)));
+        }
+        Ok(node)
+    }
+    /// simple_expr : term ((PLUS | MINUS | OR) term)*
+    fn simple_expr(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let mut node = self.term()?;
+        while self.current_token.op_type == OpType::PLUS ||
+            self.current_token.op_type == OpType::MINUS ||
+            self.current_token.op_type == OpType::OR {
+
+            let op_type = self.current_token.op_type;
+            self.eat(op_type)?;
+            let right = self.term()?;
+            // we construct the tree from bottom to top
+            node = Expr::BinOp(BinOp::new(op_type, node, right, self.span_from(&start)));
+        }
+        Ok(node)
+    }
+
+    /// expr : simple_expr (relational_operator simple_expr)?
+    ///
+    /// relational_operator : EQUAL | NOT_EQUAL | LESS | LESS_EQUAL | GREATER | GREATER_EQUAL | IN
+    ///
+    /// The relational operators bind loosest of all, so `a < b AND c < d` needs parentheses.
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let node = self.simple_expr()?;
+        Ok(match self.current_token.op_type {
+            OpType::EQUAL | OpType::NOT_EQUAL | OpType::LESS | OpType::LESS_EQUAL |
+            OpType::GREATER | OpType::GREATER_EQUAL | OpType::IN => {
+                let op_type = self.current_token.op_type;
+                self.eat(op_type)?;
+                let right = self.simple_expr()?;
+                Expr::BinOp(BinOp::new(op_type, node, right, self.span_from(&start)))
+            }
+            _ => node,
+        })
+    }
+
+    /// The tree of the program, or every syntax error found in it, in the
+    /// order of the source
+    pub fn parse(&mut self) -> Result<Program, Vec<ParseError>> {
+        let result = self.program_to_end();
+        self.finish(result)
+    }
+    /// The tree of a program whose source is only the statements of its
+    /// body, without a declaration, a BEGIN or an END
+    pub fn parse_statements(&mut self) -> Result<Program, Vec<ParseError>> {
+        let result = self.statements_to_end();
+        self.finish(result)
+    }
+    /// The tree of an input typed in at a REPL, read from `lexer`, which
+    /// takes the place of the source parsed before. The constants and types
+    /// declared by the inputs parsed before stay known, unless the input
+    /// is rejected, which declares nothing.
+    pub fn parse_input(&mut self, lexer: Lexer) -> Result<Block, Vec<ParseError>> {
+        self.start_input(lexer);
+        self.before_input = self.definitions[0].clone();
+        self.in_input = true;
+        let result = self.input_to_end();
+        self.in_input = false;
+        let result = self.finish(result);
+        if result.is_err() {
+            self.forget_input();
+        }
+        result
+    }
+    /// The tree of an input typed in at a REPL which is a single expression,
+    /// read from `lexer`, where the constants declared by the inputs parsed
+    /// before are known
+    pub fn parse_input_expression(&mut self, lexer: Lexer) -> Result<Expr, Vec<ParseError>> {
+        self.start_input(lexer);
+        let result = self.expression_to_end();
+        self.finish(result)
+    }
+    /// Forget the constants and types declared by the input parsed last,
+    /// which could not be run
+    pub fn forget_input(&mut self) {
+        self.definitions = vec![self.before_input.clone()];
+    }
+    /// Read the next input from `lexer`, in the scope of the inputs before
+    fn start_input(&mut self, lexer: Lexer) {
+        self.lexer = lexer;
+        // what an input rejected half way through leaves behind
+        self.lookahead.clear();
+        self.enum_constants.clear();
+        self.loop_depth = 0;
+        self.definitions.truncate(1);
+        if self.definitions.is_empty() {
+            self.definitions.push(Names::new());
+        }
+    }
+    /// The tree of a source which is a single expression
+    pub fn parse_expression(&mut self) -> Result<Expr, Vec<ParseError>> {
+        let result = self.expression_to_end();
+        self.finish(result)
+    }
+    /// The result of parsing a source along with the errors kept on the way
+    fn finish<T>(&mut self, result: Result<T, ParseError>) -> Result<T, Vec<ParseError>> {
+        let mut errors = std::mem::take(&mut self.errors);
+        match result {
+            Ok(node) if errors.is_empty() => Ok(node),
+            Ok(_) => Err(errors),
+            Err(error) => {
+                errors.push(error);
+                Err(errors)
+            }
+        }
+    }
+    fn program_to_end(&mut self) -> Result<Program, ParseError> {
+        self.current_token = self.lexer.get_next_token()?;
+        let node = self.program()?;
+        self.end_of("program")?;
+        Ok(node)
+    }
+    fn statements_to_end(&mut self) -> Result<Program, ParseError> {
+        self.current_token = self.lexer.get_next_token()?;
+        let start = self.start();
+        self.definitions.push(Names::new());
+        let children = self.statement_list()?;
+        self.definitions.pop();
+        self.end_of("statement")?;
+        let span = self.span_from(&start);
+        let compound = Compound::new(children, span.clone());
+        let block = Block::new(Vec::new(), compound, span.clone());
+        Ok(Program::new(None, Vec::new(), Vec::new(), block, span))
+    }
+    /// input : (PROGRAM variable SEMI)? declarations statement_list? DOT?
+    ///
+    /// The input is parsed as the block of a program without its BEGIN and
+    /// END, in the scope of the inputs parsed before it. A whole program is
+    /// an input too, its body being a compound statement.
+    fn input_to_end(&mut self) -> Result<Block, ParseError> {
+        self.current_token = self.lexer.get_next_token()?;
+        if self.current_token.op_type == OpType::PROGRAM {
+            self.eat(OpType::PROGRAM)?;
+            self.variable()?;
+            self.eat(OpType::SEMI)?;
+        }
+        let start = self.start();
+        let declarations = self.declarations()?;
+        let statements_start = self.start();
+        let children = match self.current_token.op_type {
+            OpType::EOF => Vec::new(),
+            _ => self.statement_list()?,
+        };
+        let compound = Compound::new(children, self.span_from(&statements_start));
+        if self.current_token.op_type == OpType::DOT {
+            self.eat(OpType::DOT)?;
+        }
+        self.end_of("statement")?;
+        Ok(Block::new(declarations, compound, self.span_from(&start)))
+    }
+    fn expression_to_end(&mut self) -> Result<Expr, ParseError> {
+        self.current_token = self.lexer.get_next_token()?;
+        self.definitions.push(Names::new());
+        let node = self.expr()?;
+        self.definitions.pop();
+        self.end_of("expression")?;
+        Ok(node)
+    }
+    /// Fail unless the source ends after what was parsed
+    fn end_of(&self, what: &str) -> Result<(), ParseError> {
+        if self.current_token.op_type != OpType::EOF {
+            let token = &self.current_token.value;
+            return Err(self.invalid(format!("unexpected token {} after end of {}", token, what)));
+        }
+        Ok(())
+    }
+    pub fn dialect(&self) -> Dialect {
+        self.lexer.dialect()
+    }
+    /// The text of the program and of the files read so far
+    pub fn sources(&self) -> &Sources {
+        self.lexer.sources()
+    }
+    /// Where the first directive of the program is, leaving out those of the
+    /// files and units it includes
+    pub fn first_directive(&self) -> Option<&Position> {
+        self.lexer.first_directive()
+    }
+    /// Whether the last directive for a switch before a position of the
+    /// program parsed turned it on, `None` when no directive sets it there
+    pub fn switch_at(&self, switch: char, position: &Position) -> Option<bool> {
+        self.lexer.directives().switch_at(switch, position)
+    }
+}
+
+/// Whether a token can start a statement other than the empty one
+fn starts_statement(op_type: OpType) -> bool {
+    matches!(
+        op_type,
+        OpType::BEGIN
+            | OpType::IF
+            | OpType::WHILE
+            | OpType::FOR
+            | OpType::REPEAT
+            | OpType::CASE
+            | OpType::WITH
+            | OpType::ID
+            | OpType::BREAK
+            | OpType::CONTINUE
+            | OpType::EXIT
+            | OpType::TRY
+            | OpType::RAISE
+            | OpType::GOTO
+            | OpType::INTEGER_CONST
+    )
+}
+
+/// The names declared by the declarations of a block
+fn declared_names(declarations: &[Decl]) -> HashSet<String> {
+    declarations
+        .iter()
+        .filter_map(|declaration| match declaration {
+            Decl::Const(decl) => Some(decl.name.clone()),
+            Decl::TypedConst(decl) => Some(decl.name.clone()),
+            Decl::Type(decl) => Some(decl.name.clone()),
+            Decl::Var(decl) => Some(decl.var_node.name.clone()),
+            Decl::Forward(decl) => Some(decl.name.clone()),
+            Decl::Label(_) | Decl::Procedure(_) | Decl::Function(_) => None,
+        })
+        .collect()
+}
@@ -0,0 +1,759 @@
+use std::rc::Rc;
+
+use crate::ast::{
+    Assign, BinOp, Block, Case, Compound, ConstDecl, Decl, Deref, Exit, Expr, Field, For,
+    FormattedParam, ForwardDecl, FunctionCall, FunctionDecl, Goto, If, Index, Initializer,
+    LabelDecl, Labeled, Literal, ProcedureCall, ProcedureDecl, Program, Raise, Repeat,
+    SetConstructor, Stmt, TryExcept, TryFinally, TypeDecl, TypedConstDecl, UnaryOp, ValueRange,
+    Var, VarDecl, While, With,
+};
+use crate::token::{OpType, Span};
+use crate::types::{Type, VariantPart};
+use crate::value::Value;
+use crate::visitor::Visitor;
+
+/// The source of a program printed from its tree: one statement or
+/// declaration to a line, indented by how deeply it is nested, keywords in
+/// upper case and only the parentheses the operators need.
+///
+/// The comments are dropped, and so are the compiler directives, the tree
+/// keeping the source they select, which the callers refuse to lay out.
+/// The constants are printed as they were written, not as the values they
+/// were folded into. Printing the tree of the source printed gives the same
+/// source again.
+pub fn print(node: &Program) -> String {
+    let mut printer = Printer::default();
+    printer.visit_program(node);
+    printer.text.push('\n');
+    printer.text
+}
+
+/// A parameter of a routine: its name, its type and whether it is CONST
+type Param<'a> = (String, &'a Type, bool);
+
+#[derive(Default)]
+struct Printer {
+    text: String,
+    /// how many levels the lines written are indented by
+    depth: usize,
+    /// whether the block written is that of a routine, whose own routines
+    /// are indented below its heading
+    in_routine: bool,
+}
+
+impl Printer {
+    /// Start a line, indented by the depth
+    fn line(&mut self) {
+        if !self.text.is_empty() {
+            self.text.push('\n');
+        }
+        self.text.push_str(&"  ".repeat(self.depth));
+    }
+
+    /// Write a statement list, one statement to a line, leaving out the
+    /// empty statements
+    fn statements(&mut self, nodes: &[Stmt]) {
+        let nodes: Vec<&Stmt> =
+            nodes.iter().filter(|node| !matches!(node, Stmt::NoOp(_))).collect();
+        for (i, node) in nodes.iter().enumerate() {
+            self.line();
+            self.dispatch_stmt(node);
+            if i + 1 < nodes.len() {
+                self.text.push(';');
+            }
+        }
+    }
+
+    /// Write the statement following a THEN, ELSE or DO: a compound
+    /// statement starting on the same line, any other on a line of its own
+    /// indented below, and wrapped in BEGIN and END when `closed` asks for
+    /// it. Whether the statement ends with an END is returned.
+    fn branch(&mut self, node: &Stmt, closed: bool) -> bool {
+        match node {
+            Stmt::Compound(node) => {
+                self.text.push(' ');
+                self.visit_compound(node);
+                true
+            }
+            Stmt::NoOp(_) => false,
+            node if closed => {
+                self.text.push_str(" BEGIN");
+                self.depth += 1;
+                self.line();
+                self.dispatch_stmt(node);
+                self.depth -= 1;
+                self.line();
+                self.text.push_str("END");
+                true
+            }
+            node => {
+                self.depth += 1;
+                self.line();
+                self.dispatch_stmt(node);
+                self.depth -= 1;
+                false
+            }
+        }
+    }
+
+    /// Write the heading of a procedure or function, without the semicolon
+    fn heading(&mut self, name: &str, params: &[VarDecl], return_type: Option<&Type>) {
+        let kind = if return_type.is_some() { "FUNCTION" } else { "PROCEDURE" };
+        let params: Vec<Param> = params
+            .iter()
+            .map(|param| (param.var_node.name.clone(), &param.type_node, param.is_const))
+            .collect();
+        let signature = self.signature(&params, return_type);
+        self.text.push_str(&format!("{} {}{}", kind, name, signature));
+    }
+
+    /// The parameters of a routine and the type of its result, the
+    /// parameters of the same type after one another sharing it
+    fn signature(&mut self, params: &[Param], result: Option<&Type>) -> String {
+        let mut text = String::new();
+        if !params.is_empty() {
+            let groups = groups(params, |(_, a, a_const), (_, b, b_const)| {
+                a == b && a_const == b_const
+            });
+            let groups: Vec<String> = groups
+                .iter()
+                .map(|group| {
+                    let names: Vec<&str> = group.iter().map(|(name, _, _)| &name[..]).collect();
+                    let (_, type_node, is_const) = group[0];
+                    let kind = if is_const { "CONST " } else { "" };
+                    format!("{}{}: {}", kind, names.join(", "), self.type_text(type_node))
+                })
+                .collect();
+            text.push_str(&format!("({})", groups.join("; ")));
+        }
+        if let Some(result) = result {
+            text.push_str(&format!(": {}", self.type_text(result)));
+        }
+        text
+    }
+
+    /// The source of a type, a record laid out on lines of its own
+    fn type_text(&mut self, node: &Type) -> String {
+        match node {
+            Type::File(component) => format!("FILE OF {}", self.type_text(component)),
+            Type::Subrange { low, high } => {
+                let low = self.dispatch_expr(&low.source);
+                format!("{}..{}", low, self.dispatch_expr(&high.source))
+            }
+            Type::Array { index, element, packed } => {
+                let packed = if *packed { "PACKED " } else { "" };
+                let (index, element) = (self.type_text(index), self.type_text(element));
+                format!("{}ARRAY[{}] OF {}", packed, index, element)
+            }
+            Type::DynamicArray(element) => format!("ARRAY OF {}", self.type_text(element)),
+            Type::Set(element) => format!("SET OF {}", self.type_text(element)),
+            Type::Record { fields, variant_parts } => {
+                let mut printer =
+                    Printer { text: String::from("RECORD"), depth: self.depth + 1, ..*self };
+                let names: Vec<&str> = fields.iter().map(|(name, _)| &name[..]).collect();
+                printer.fields(fields, &names, variant_parts);
+                printer.depth -= 1;
+                printer.line();
+                printer.text.push_str("END");
+                printer.text
+            }
+            Type::Pointer(target) => format!("^{}", self.type_text(target)),
+            Type::Routine { params, names, result } => {
+                let kind = if result.is_some() { "FUNCTION" } else { "PROCEDURE" };
+                let params: Vec<Param> = params
+                    .iter()
+                    .zip(&names.0)
+                    .map(|(type_node, (name, is_const))| (name.clone(), type_node, *is_const))
+                    .collect();
+                format!("{}{}", kind, self.signature(&params, result.as_deref()))
+            }
+            _ => node.to_string(),
+        }
+    }
+
+    /// Write the fields of a record or of a variant which are named in
+    /// `names`, `parts` being the variant parts nested in them, each after
+    /// those nested in its variants, the variant part of the fields last
+    fn fields(&mut self, fields: &[(String, Type)], names: &[&str], parts: &[VariantPart]) {
+        let part = parts.last();
+        let in_part = part.map(part_names).unwrap_or_default();
+        let fixed: Vec<&(String, Type)> = fields
+            .iter()
+            .filter(|(name, _)| names.contains(&&name[..]) && !in_part.contains(&&name[..]))
+            .collect();
+        for (i, (name, type_node)) in fixed.iter().enumerate() {
+            self.line();
+            let type_node = self.type_text(type_node);
+            self.text.push_str(&format!("{}: {}", name, type_node));
+            if i + 1 < fixed.len() || part.is_some() {
+                self.text.push(';');
+            }
+        }
+        let Some(part) = part else {
+            return;
+        };
+        self.line();
+        let tag_type = self.type_text(&part.tag_type);
+        let tag = match &part.tag {
+            Some(tag) => format!("{}: {}", tag, tag_type),
+            None => tag_type,
+        };
+        self.text.push_str(&format!("CASE {} OF", tag));
+        self.depth += 1;
+        let mut nested = &parts[..parts.len() - 1];
+        for (i, (labels, variant)) in part.variants.iter().enumerate() {
+            let variant: Vec<&str> = variant.iter().map(|name| &name[..]).collect();
+            // the parts nested in a variant come before those of the next one
+            let count = nested
+                .iter()
+                .take_while(|part| part_names(part).iter().all(|name| variant.contains(name)))
+                .count();
+            let labels: Vec<String> =
+                labels.iter().map(|label| self.dispatch_expr(&label.source)).collect();
+            self.line();
+            self.text.push_str(&format!("{}: (", labels.join(", ")));
+            if !variant.is_empty() {
+                self.depth += 1;
+                self.fields(fields, &variant, &nested[..count]);
+                self.depth -= 1;
+                self.line();
+            }
+            self.text.push(')');
+            if i + 1 < part.variants.len() {
+                self.text.push(';');
+            }
+            nested = &nested[count..];
+        }
+        self.depth -= 1;
+    }
+
+    /// The source of the value of a typed constant
+    fn initializer(&mut self, node: &Initializer) -> String {
+        match node {
+            Initializer::Value(constant) => self.dispatch_expr(&constant.source),
+            Initializer::List(elements) => {
+                let elements: Vec<String> =
+                    elements.iter().map(|element| self.initializer(element)).collect();
+                format!("({})", elements.join(", "))
+            }
+            Initializer::Fields(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, self.initializer(value)))
+                    .collect();
+                format!("({})", fields.join("; "))
+            }
+        }
+    }
+
+    /// The source of the operand of an operator, in parentheses when the
+    /// operator binds tighter than the operand does
+    fn operand(&mut self, node: &Expr, parenthesized: impl Fn(OpType) -> bool) -> String {
+        let text = self.dispatch_expr(node);
+        match node {
+            Expr::BinOp(operand) if parenthesized(operand.op_type) => format!("({})", text),
+            _ => text,
+        }
+    }
+}
+
+/// The source of a value as a constant, a character which cannot be
+/// written between quotes being written as a call of CHR
+fn literal(value: &Value) -> String {
+    match value {
+        Value::Char(ch) if ch.is_control() => format!("CHR({})", *ch as u32),
+        Value::Char(ch) => quote(&ch.to_string()),
+        Value::String(text) if text.is_empty() => quote(""),
+        Value::String(text) => {
+            let mut parts = Vec::new();
+            let mut rest = &text[..];
+            while let Some(ch) = rest.chars().next() {
+                let end = rest.find(|other: char| other.is_control() != ch.is_control());
+                let (part, after) = rest.split_at(end.unwrap_or(rest.len()));
+                match ch.is_control() {
+                    true => parts.extend(part.chars().map(|ch| format!("CHR({})", ch as u32))),
+                    false => parts.push(quote(part)),
+                }
+                rest = after;
+            }
+            parts.join(" + ")
+        }
+        Value::Real(number) => format!("{:?}", number),
+        Value::Set(set) => {
+            // the members following one another are written as a range
+            let ordinal = |member: &Value| member.ordinal().unwrap_or_default();
+            let mut runs: Vec<(Value, Value)> = Vec::new();
+            for member in set.members() {
+                match runs.last_mut() {
+                    Some((_, last)) if ordinal(last) + 1 == ordinal(&member) => *last = member,
+                    _ => runs.push((member.clone(), member)),
+                }
+            }
+            let runs: Vec<String> = runs
+                .iter()
+                .map(|(first, last)| match ordinal(last) - ordinal(first) {
+                    0 => literal(first),
+                    1 => format!("{}, {}", literal(first), literal(last)),
+                    _ => format!("{}..{}", literal(first), literal(last)),
+                })
+                .collect();
+            format!("[{}]", runs.join(", "))
+        }
+        value => value.to_string(),
+    }
+}
+
+/// The string literal of a text
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// The runs of the items next to one another which `same` puts together
+fn groups<T>(items: &[T], same: impl Fn(&T, &T) -> bool) -> Vec<&[T]> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    for i in 1..=items.len() {
+        if i == items.len() || !same(&items[start], &items[i]) {
+            groups.push(&items[start..i]);
+            start = i;
+        }
+    }
+    groups
+}
+
+/// The names of the fields in a variant part, the tag included
+fn part_names(part: &VariantPart) -> Vec<&str> {
+    let variants = part.variants.iter().flat_map(|(_, names)| names.iter());
+    part.tag.iter().chain(variants).map(|name| &name[..]).collect()
+}
+
+/// How tightly an operator binds its operands, relational operators the least
+fn precedence(op: OpType) -> u8 {
+    match op {
+        OpType::EQUAL
+        | OpType::NOT_EQUAL
+        | OpType::LESS
+        | OpType::LESS_EQUAL
+        | OpType::GREATER
+        | OpType::GREATER_EQUAL
+        | OpType::IN => 0,
+        OpType::PLUS | OpType::MINUS | OpType::OR => 1,
+        _ => 2,
+    }
+}
+
+/// The source of an operator
+fn operator(op: OpType) -> &'static str {
+    match op {
+        OpType::PLUS => "+",
+        OpType::MINUS => "-",
+        OpType::MUL => "*",
+        OpType::FLOAT_DIV => "/",
+        OpType::INTEGER_DIV => "DIV",
+        OpType::MOD => "MOD",
+        OpType::AND => "AND",
+        OpType::OR => "OR",
+        OpType::NOT => "NOT",
+        OpType::IN => "IN",
+        OpType::EQUAL => "=",
+        OpType::NOT_EQUAL => "<>",
+        OpType::LESS => "<",
+        OpType::LESS_EQUAL => "<=",
+        OpType::GREATER => ">",
+        OpType::GREATER_EQUAL => ">=",
+        _ => panic!("{:?} is not an operator", op),
+    }
+}
+
+/// Whether an ELSE written after a statement would be taken as the ELSE of
+/// an IF the statement ends with
+fn ends_open(node: &Stmt) -> bool {
+    match node {
+        Stmt::If(node) => node.else_branch.as_ref().is_none_or(|node| ends_open(node)),
+        Stmt::While(node) => ends_open(&node.body),
+        Stmt::For(node) => ends_open(&node.body),
+        Stmt::With(node) => ends_open(&node.body),
+        Stmt::Labeled(node) => ends_open(&node.statement),
+        _ => false,
+    }
+}
+
+/// Whether a constant is one of the identifiers of an enumerated type,
+/// which the type declares itself
+fn is_enum_identifier(node: &ConstDecl) -> bool {
+    match &node.constant.value {
+        Value::Enum { ordinal, names } => names[*ordinal as usize] == node.name,
+        _ => false,
+    }
+}
+
+impl Visitor for Printer {
+    type StmtOutput = ();
+    type ExprOutput = String;
+    type DeclOutput = ();
+
+    fn visit_program(&mut self, node: &Program) {
+        if let Some(name) = &node.name {
+            self.line();
+            self.text.push_str(&format!("PROGRAM {};", name));
+        }
+        if !node.uses.is_empty() {
+            self.line();
+            self.text.push_str(&format!("USES {};", node.uses.join(", ")));
+        }
+        self.visit_block(&node.block);
+        self.text.push('.');
+    }
+    fn visit_block(&mut self, node: &Block) {
+        let mut section = None;
+        for declaration in &node.declarations {
+            let keyword = match declaration {
+                Decl::Const(node) if is_enum_identifier(node) => continue,
+                Decl::Const(_) | Decl::TypedConst(_) => Some("CONST"),
+                Decl::Type(_) => Some("TYPE"),
+                Decl::Var(_) => Some("VAR"),
+                _ => None,
+            };
+            if keyword != section
+                && let Some(keyword) = keyword
+            {
+                self.line();
+                self.text.push_str(keyword);
+            }
+            section = keyword;
+            // the declarations of a section are indented below its keyword,
+            // and the routines of a routine below its heading
+            let routine = matches!(
+                declaration,
+                Decl::Forward(_) | Decl::Procedure(_) | Decl::Function(_)
+            );
+            let indented = section.is_some() || routine && self.in_routine;
+            self.depth += usize::from(indented);
+            self.dispatch_decl(declaration);
+            self.depth -= usize::from(indented);
+        }
+        self.line();
+        self.visit_compound(&node.compound_statement);
+    }
+
+    fn visit_compound(&mut self, node: &Compound) {
+        self.text.push_str("BEGIN");
+        self.depth += 1;
+        self.statements(&node.children);
+        self.depth -= 1;
+        self.line();
+        self.text.push_str("END");
+    }
+    fn visit_assign(&mut self, node: &Assign) {
+        let (left, right) = (self.dispatch_expr(&node.left), self.dispatch_expr(&node.right));
+        self.text.push_str(&format!("{} := {}", left, right));
+    }
+    fn visit_procedure_call(&mut self, node: &ProcedureCall) {
+        self.text.push_str(&node.name);
+        if !node.actual_params.is_empty() {
+            let params: Vec<String> =
+                node.actual_params.iter().map(|param| self.dispatch_expr(param)).collect();
+            self.text.push_str(&format!("({})", params.join(", ")));
+        }
+    }
+    fn visit_if(&mut self, node: &If) {
+        let condition = self.dispatch_expr(&node.condition);
+        self.text.push_str(&format!("IF {} THEN", condition));
+        let Some(else_branch) = &node.else_branch else {
+            self.branch(&node.then_branch, false);
+            return;
+        };
+        // the ELSE would belong to an IF the THEN branch ends with
+        let closed = ends_open(&node.then_branch);
+        match self.branch(&node.then_branch, closed) {
+            true => self.text.push(' '),
+            false => self.line(),
+        }
+        self.text.push_str("ELSE");
+        match &**else_branch {
+            Stmt::If(node) => {
+                self.text.push(' ');
+                self.visit_if(node);
+            }
+            node => {
+                self.branch(node, false);
+            }
+        }
+    }
+    fn visit_while(&mut self, node: &While) {
+        let condition = self.dispatch_expr(&node.condition);
+        self.text.push_str(&format!("WHILE {} DO", condition));
+        self.branch(&node.body, false);
+    }
+    fn visit_for(&mut self, node: &For) {
+        let variable = self.visit_var(&node.variable);
+        let (start, end) = (self.dispatch_expr(&node.start), self.dispatch_expr(&node.end));
+        let direction = if node.downto { "DOWNTO" } else { "TO" };
+        self.text.push_str(&format!("FOR {} := {} {} {} DO", variable, start, direction, end));
+        self.branch(&node.body, false);
+    }
+    fn visit_repeat(&mut self, node: &Repeat) {
+        self.text.push_str("REPEAT");
+        self.depth += 1;
+        self.statements(&node.children);
+        self.depth -= 1;
+        self.line();
+        let condition = self.dispatch_expr(&node.condition);
+        self.text.push_str(&format!("UNTIL {}", condition));
+    }
+    fn visit_case(&mut self, node: &Case) {
+        let selector = self.dispatch_expr(&node.selector);
+        self.text.push_str(&format!("CASE {} OF", selector));
+        self.depth += 1;
+        for (i, arm) in node.arms.iter().enumerate() {
+            let labels: Vec<String> = arm.labels.iter().map(|label| self.range(label)).collect();
+            self.line();
+            self.text.push_str(&format!("{}:", labels.join(", ")));
+            if !matches!(arm.body, Stmt::NoOp(_)) {
+                self.text.push(' ');
+                self.dispatch_stmt(&arm.body);
+            }
+            // a semicolon before the ELSE keeps it from an IF the arm ends with
+            if i + 1 < node.arms.len() || node.else_branch.is_some() {
+                self.text.push(';');
+            }
+        }
+        self.depth -= 1;
+        if let Some(else_branch) = &node.else_branch {
+            self.line();
+            self.text.push_str("ELSE");
+            self.depth += 1;
+            self.statements(else_branch);
+            self.depth -= 1;
+        }
+        self.line();
+        self.text.push_str("END");
+    }
+    fn visit_with(&mut self, node: &With) {
+        // the records of nested WITH statements are named by a single one
+        let mut records = vec![self.dispatch_expr(&node.record)];
+        let mut body = &*node.body;
+        while let Stmt::With(node) = body {
+            records.push(self.dispatch_expr(&node.record));
+            body = &node.body;
+        }
+        self.text.push_str(&format!("WITH {} DO", records.join(", ")));
+        self.branch(body, false);
+    }
+    fn visit_break(&mut self, _span: &Span) {
+        self.text.push_str("BREAK");
+    }
+    fn visit_continue(&mut self, _span: &Span) {
+        self.text.push_str("CONTINUE");
+    }
+    fn visit_exit(&mut self, node: &Exit) {
+        self.text.push_str("EXIT");
+        if let Some(result) = &node.result {
+            let result = self.dispatch_expr(result);
+            self.text.push_str(&format!("({})", result));
+        }
+    }
+    fn visit_goto(&mut self, node: &Goto) {
+        self.text.push_str(&format!("GOTO {}", node.label));
+    }
+    fn visit_labeled(&mut self, node: &Labeled) {
+        self.text.push_str(&format!("{}:", node.label));
+        if !matches!(*node.statement, Stmt::NoOp(_)) {
+            self.text.push(' ');
+            self.dispatch_stmt(&node.statement);
+        }
+    }
+    fn visit_try_except(&mut self, node: &TryExcept) {
+        self.text.push_str("TRY");
+        self.depth += 1;
+        self.statements(&node.body);
+        self.depth -= 1;
+        self.line();
+        self.text.push_str("EXCEPT");
+        self.depth += 1;
+        for (i, handler) in node.handlers.iter().enumerate() {
+            self.line();
+            self.text.push_str("ON ");
+            if let Some(variable) = &handler.variable {
+                self.text.push_str(&format!("{}: ", variable));
+            }
+            self.text.push_str(&format!("{} DO", handler.class));
+            self.branch(&handler.body, false);
+            if i + 1 < node.handlers.len() || node.else_branch.is_some() {
+                self.text.push(';');
+            }
+        }
+        match &node.else_branch {
+            Some(else_branch) if node.handlers.is_empty() => self.statements(else_branch),
+            Some(else_branch) => {
+                self.depth -= 1;
+                self.line();
+                self.text.push_str("ELSE");
+                self.depth += 1;
+                self.statements(else_branch);
+            }
+            None => {}
+        }
+        self.depth -= 1;
+        self.line();
+        self.text.push_str("END");
+    }
+    fn visit_try_finally(&mut self, node: &TryFinally) {
+        self.text.push_str("TRY");
+        self.depth += 1;
+        self.statements(&node.body);
+        self.depth -= 1;
+        self.line();
+        self.text.push_str("FINALLY");
+        self.depth += 1;
+        self.statements(&node.finally);
+        self.depth -= 1;
+        self.line();
+        self.text.push_str("END");
+    }
+    fn visit_raise(&mut self, node: &Raise) {
+        self.text.push_str("RAISE");
+        if let Some(message) = &node.message {
+            let message = self.dispatch_expr(message);
+            self.text.push_str(&format!(" {}", message));
+        }
+    }
+    fn visit_no_op(&mut self, _span: &Span) {}
+
+    fn visit_bin_op(&mut self, node: &BinOp) -> String {
+        let level = precedence(node.op_type);
+        // the relational operators do not chain, and the others group to the left
+        let left = self.operand(&node.left, |op| precedence(op) < level.max(1));
+        let right = self.operand(&node.right, |op| precedence(op) <= level);
+        format!("{} {} {}", left, operator(node.op_type), right)
+    }
+    fn visit_literal(&mut self, node: &Literal) -> String {
+        if let Some((function, type_node)) = &node.call {
+            return format!("{}({})", function, self.type_text(type_node));
+        }
+        match &node.value {
+            // the literal of the smallest integer would overflow before its sign is applied
+            Value::Integer(i32::MIN) => String::from("LOW(INTEGER)"),
+            Value::String(text) if text.chars().any(char::is_control) => {
+                format!("({})", literal(&node.value))
+            }
+            value => literal(value),
+        }
+    }
+    fn visit_unary_op(&mut self, node: &UnaryOp) -> String {
+        let operand = self.operand(&node.expr, |_| true);
+        match node.op_type {
+            OpType::NOT => format!("NOT {}", operand),
+            // a sign before another one is spaced out from it
+            op if operand.starts_with(['-', '+']) => format!("{} {}", operator(op), operand),
+            op => format!("{}{}", operator(op), operand),
+        }
+    }
+    fn visit_var(&mut self, node: &Var) -> String {
+        node.name.clone()
+    }
+    fn visit_index(&mut self, node: &Index) -> String {
+        // `a[i][j]` is written `a[i, j]`, as it is parsed
+        let mut indices = vec![self.dispatch_expr(&node.index)];
+        let mut array = &*node.array;
+        while let Expr::Index(node) = array {
+            indices.push(self.dispatch_expr(&node.index));
+            array = &node.array;
+        }
+        indices.reverse();
+        format!("{}[{}]", self.selected(array), indices.join(", "))
+    }
+    fn visit_field(&mut self, node: &Field) -> String {
+        format!("{}.{}", self.selected(&node.record), node.name)
+    }
+    fn visit_deref(&mut self, node: &Deref) -> String {
+        format!("{}^", self.selected(&node.pointer))
+    }
+    fn visit_set_constructor(&mut self, node: &SetConstructor) -> String {
+        let elements: Vec<String> =
+            node.elements.iter().map(|element| self.range(element)).collect();
+        format!("[{}]", elements.join(", "))
+    }
+    fn visit_function_call(&mut self, node: &FunctionCall) -> String {
+        let params: Vec<String> =
+            node.actual_params.iter().map(|param| self.dispatch_expr(param)).collect();
+        format!("{}({})", node.name, params.join(", "))
+    }
+    fn visit_formatted_param(&mut self, node: &FormattedParam) -> String {
+        let (expr, width) = (self.dispatch_expr(&node.expr), self.dispatch_expr(&node.width));
+        let mut text = format!("{}:{}", expr, width);
+        if let Some(precision) = &node.precision {
+            text.push_str(&format!(":{}", self.dispatch_expr(precision)));
+        }
+        text
+    }
+
+    fn visit_label_decl(&mut self, node: &LabelDecl) {
+        self.line();
+        self.text.push_str(&format!("LABEL {};", node.label));
+    }
+    fn visit_const_decl(&mut self, node: &ConstDecl) {
+        self.line();
+        let value = self.dispatch_expr(&node.constant.source);
+        self.text.push_str(&format!("{} = {};", node.name, value));
+    }
+    fn visit_typed_const_decl(&mut self, node: &TypedConstDecl) {
+        self.line();
+        let type_node = self.type_text(&node.type_node);
+        let value = self.initializer(&node.initializer);
+        self.text.push_str(&format!("{}: {} = {};", node.name, type_node, value));
+    }
+    fn visit_type_decl(&mut self, node: &TypeDecl) {
+        self.line();
+        let type_node = self.type_text(&node.type_node);
+        self.text.push_str(&format!("{} = {};", node.name, type_node));
+    }
+    fn visit_var_decl(&mut self, node: &VarDecl) {
+        self.line();
+        let type_node = self.type_text(&node.type_node);
+        self.text.push_str(&format!("{}: {};", node.var_node.name, type_node));
+    }
+    fn visit_forward_decl(&mut self, node: &ForwardDecl) {
+        self.line();
+        self.heading(&node.name, &node.params, node.return_type.as_ref());
+        self.text.push_str("; FORWARD;");
+    }
+    fn visit_procedure_decl(&mut self, node: &Rc<ProcedureDecl>) {
+        self.line();
+        self.heading(&node.name, &node.params, None);
+        self.routine_block(&node.block);
+    }
+    fn visit_function_decl(&mut self, node: &Rc<FunctionDecl>) {
+        self.line();
+        self.heading(&node.name, &node.params, Some(&node.return_type));
+        self.routine_block(&node.block);
+    }
+}
+
+impl Printer {
+    /// Write the block of a routine after its heading
+    fn routine_block(&mut self, node: &Block) {
+        self.text.push(';');
+        let in_routine = std::mem::replace(&mut self.in_routine, true);
+        self.visit_block(node);
+        self.in_routine = in_routine;
+        self.text.push(';');
+    }
+    /// The source of a CASE label or an element of a set constructor
+    fn range(&mut self, node: &ValueRange) -> String {
+        let low = self.dispatch_expr(&node.low);
+        match &node.high {
+            Some(high) => format!("{}..{}", low, self.dispatch_expr(high)),
+            None => low,
+        }
+    }
+    /// The source of the variable an index, a field or a dereference
+    /// selects from, in parentheses when it is the result of an operator
+    fn selected(&mut self, node: &Expr) -> String {
+        let text = self.dispatch_expr(node);
+        match node {
+            Expr::BinOp(_) | Expr::UnaryOp(_) => format!("({})", text),
+            _ => text,
+        }
+    }
+}
@@ -0,0 +1,29 @@
+/// The generator of the numbers RANDOM gives, a SplitMix64 one, which gives
+/// the same numbers again when started from the same seed
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random { state: seed }
+    }
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// A real number at least 0 and less than 1
+    pub fn real(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+    /// An integer at least 0 and less than `limit`, or 0 when `limit` is not positive
+    pub fn below(&mut self, limit: i32) -> i32 {
+        if limit <= 0 {
+            return 0;
+        }
+        (((self.next() >> 32) * limit as u64) >> 32) as i32
+    }
+}
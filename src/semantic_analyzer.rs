@@ -0,0 +1,1403 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::ast::{
+    Assign, BinOp, Block, Case, Compound, ConstDecl, Decl, Deref, ExceptionHandler, Exit, Expr,
+    Field, For, FormattedParam, ForwardDecl, FunctionCall, FunctionDecl, Goto, If, Index,
+    LabelDecl, Labeled, Literal, ProcedureCall, ProcedureDecl, Program, Raise, Repeat,
+    SetConstructor, Stmt, TryExcept, TryFinally, TypeDecl, TypedConstDecl, UnaryOp, Var, VarDecl,
+    While, With, node_address,
+};
+use crate::builtin::{
+    builtin_function, is_standard_procedure, is_type_function, result_type, standard_function,
+    variable_params,
+};
+use crate::dialect::{Dialect, Feature};
+use crate::error::SemanticError;
+use crate::names::same_name;
+use crate::symbol::{IT, Overload, RESULT, ScopedSymbolTable, Symbol};
+use crate::token::{OpType, Span, keyword};
+use crate::types::{EXCEPTION, Type};
+use crate::visitor::Visitor;
+
+/// The names and types of the parameters of a routine, and the type of its
+/// result if it is a function
+type Signature = (Vec<(String, Type)>, Option<Type>);
+
+/// A routine declared FORWARD and not defined yet
+struct ForwardRoutine {
+    name: String,
+    /// the name the interpreter knows the routine by, see `Overload::name`
+    runtime_name: String,
+    params: Vec<(String, Type)>,
+    return_type: Option<Type>,
+}
+
+impl ForwardRoutine {
+    /// Whether the heading of a definition is the same as this one, naming
+    /// the parameters alike up to case
+    fn repeated_by(&self, (params, return_type): &Signature) -> bool {
+        self.return_type == *return_type
+            && self.params.len() == params.len()
+            && self.params.iter().zip(params).all(|((name, type_node), (param, param_type))| {
+                same_name(name, param) && type_node == param_type
+            })
+    }
+}
+
+/// Walks the tree before it is run and rejects programs that use names
+/// which are not declared in any enclosing scope.
+#[derive(Default)]
+pub struct SemanticAnalyzer {
+    current_scope: Option<Box<ScopedSymbolTable>>,
+    /// the names pointer types point to, checked once the declarations of
+    /// the block they appear in are all known
+    pointer_targets: Vec<String>,
+    /// the fields of the records opened by the WITH statements we are inside,
+    /// innermost last, along with whether the record is a CONST parameter
+    with_fields: Vec<(Vec<(String, Type)>, bool)>,
+    /// the routines declared FORWARD in the block being analyzed which it
+    /// has yet to define
+    forward_routines: Vec<ForwardRoutine>,
+    /// the result types of the routines being analyzed, innermost last,
+    /// `None` for a procedure
+    return_types: Vec<Option<Type>>,
+    /// the dialect of the program, which may allow declaring routines of the
+    /// same name with different parameters
+    dialect: Dialect,
+    /// the names overloaded routines are known by at run time, by the
+    /// address of their declarations and of the calls to them
+    routine_names: HashMap<usize, String>,
+    /// the addresses of the names standing for a routine rather than calling
+    /// it, being passed for a procedural parameter or assigned to a variable
+    routine_values: HashSet<usize>,
+    /// the addresses of the file variables given as the first argument of
+    /// READ, READLN, WRITE or WRITELN, which is read or written instead of
+    /// the standard input or output
+    file_arguments: HashSet<usize>,
+    /// the addresses of the packed arrays of characters written by WRITE,
+    /// WRITELN or STR or compared, which are taken as strings there
+    packed_strings: HashSet<usize>,
+    /// the types of the arguments of LOW, HIGH and SIZEOF, by their addresses
+    type_arguments: HashMap<usize, Type>,
+    /// the number of exception handlers we are inside, where a bare RAISE
+    /// raises again the exception being handled
+    handler_depth: usize,
+    /// the control variables of the FOR statements we are inside, which
+    /// their bodies may not change
+    loop_variables: Vec<String>,
+    /// the global scope as the inputs checked before the current one left
+    /// it, which `forget_input` goes back to
+    before_input: Option<Box<ScopedSymbolTable>>,
+}
+
+impl SemanticAnalyzer {
+    pub fn new() -> SemanticAnalyzer {
+        SemanticAnalyzer {
+            current_scope: None,
+            pointer_targets: Vec::new(),
+            with_fields: Vec::new(),
+            forward_routines: Vec::new(),
+            return_types: Vec::new(),
+            dialect: Dialect::default(),
+            routine_names: HashMap::new(),
+            routine_values: HashSet::new(),
+            file_arguments: HashSet::new(),
+            packed_strings: HashSet::new(),
+            type_arguments: HashMap::new(),
+            handler_depth: 0,
+            loop_variables: Vec::new(),
+            before_input: None,
+        }
+    }
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+    }
+    /// Check a program along with the units it uses, without running it
+    pub fn analyze(&mut self, program: &Program) -> Result<(), SemanticError> {
+        self.visit_program(program)
+    }
+    /// Check an expression standing on its own, where only the predefined
+    /// names are known
+    pub fn analyze_expression(&mut self, node: &Expr) -> Result<(), SemanticError> {
+        self.enter_scope("global");
+        let result = self.visit_expr(node);
+        self.leave_scope();
+        result
+    }
+    /// Check an input typed in at a REPL in the global scope the inputs
+    /// checked before it left, adding what it declares to the scope, unless
+    /// the input is rejected, which declares nothing
+    pub fn analyze_input(&mut self, block: &Block) -> Result<(), SemanticError> {
+        if self.current_scope.is_none() {
+            self.enter_scope("global");
+        }
+        self.before_input = self.current_scope.clone();
+        let result = self.visit_block(block);
+        if result.is_err() {
+            self.forget_input();
+        }
+        result
+    }
+    /// Check an input typed in at a REPL which is a single expression, in the
+    /// global scope the inputs checked before it left, returning its type
+    /// as far as it can be told before running it
+    pub fn analyze_input_expression(&mut self, node: &Expr) -> Result<Option<Type>, SemanticError> {
+        if self.current_scope.is_none() {
+            self.enter_scope("global");
+        }
+        let result = self.visit_expr(node).and_then(|_| self.expr_type(node));
+        if result.is_err() {
+            self.discard_analysis();
+        }
+        result
+    }
+    /// The type of an expression in the global scope of the inputs checked
+    /// so far, as far as it can be told before running it, which is checked
+    /// without being kept for running it
+    pub fn input_expression_type(&mut self, node: &Expr) -> Result<Option<Type>, SemanticError> {
+        let result = self.analyze_input_expression(node);
+        self.discard_analysis();
+        result
+    }
+    /// Declare `IT` in the global scope of the inputs as a variable of the
+    /// type of the expression whose value it holds
+    pub fn declare_it(&mut self, type_node: Type) {
+        self.scope().replace(IT, Symbol::Var(type_node));
+    }
+    /// The names declared in the global scope of the inputs checked so far
+    pub fn input_names(&self) -> Vec<String> {
+        let scope = self.current_scope.as_ref();
+        scope.map(|scope| scope.names().cloned().collect()).unwrap_or_default()
+    }
+    /// Forget the declarations of the input checked last, which could not be run
+    pub fn forget_input(&mut self) {
+        self.current_scope = self.before_input.take();
+        self.discard_analysis();
+    }
+    /// Drop what the analysis of an input rejected half way through leaves behind
+    fn discard_analysis(&mut self) {
+        self.routine_names.clear();
+        self.routine_values.clear();
+        self.file_arguments.clear();
+        self.packed_strings.clear();
+        self.type_arguments.clear();
+        self.pointer_targets.clear();
+        self.with_fields.clear();
+        self.forward_routines.clear();
+        self.return_types.clear();
+        self.handler_depth = 0;
+        self.loop_variables.clear();
+    }
+    /// The names the overloaded routines and the calls resolved to them are
+    /// known by at run time, by the address of their nodes, along with the
+    /// addresses of the names standing for a routine as a value
+    pub fn take_routine_names(&mut self) -> (HashMap<usize, String>, HashSet<usize>) {
+        (std::mem::take(&mut self.routine_names), std::mem::take(&mut self.routine_values))
+    }
+    /// The addresses of the file variables read or written by READ, READLN,
+    /// WRITE and WRITELN
+    pub fn take_file_arguments(&mut self) -> HashSet<usize> {
+        std::mem::take(&mut self.file_arguments)
+    }
+    /// The addresses of the packed arrays of characters written or compared
+    /// as strings
+    pub fn take_packed_strings(&mut self) -> HashSet<usize> {
+        std::mem::take(&mut self.packed_strings)
+    }
+    /// The types of the arguments of LOW, HIGH and SIZEOF, which they work on
+    /// rather than on the values
+    pub fn take_type_arguments(&mut self) -> HashMap<usize, Type> {
+        std::mem::take(&mut self.type_arguments)
+    }
+
+    fn scope(&mut self) -> &mut ScopedSymbolTable {
+        self.current_scope.as_mut().unwrap()
+    }
+    fn enter_scope(&mut self, scope_name: &str) {
+        let enclosing_scope = self.current_scope.take();
+        self.current_scope = Some(Box::new(ScopedSymbolTable::new(scope_name, enclosing_scope)));
+    }
+    fn leave_scope(&mut self) {
+        self.current_scope = self.current_scope.take().unwrap().enclosing_scope;
+    }
+    /// Let the program or unit about to be analyzed see the interfaces of
+    /// the units it uses, and only those
+    fn use_units(&mut self, uses: &[String]) {
+        let mut scope = self.current_scope.as_deref_mut();
+        while let Some(unit) = scope {
+            unit.used = uses.iter().any(|name| same_name(name, &unit.scope_name));
+            scope = unit.enclosing_scope.as_deref_mut();
+        }
+    }
+
+    fn visit_decl(&mut self, node: &Decl) -> Result<(), SemanticError> {
+        self.dispatch_decl(node).map_err(|error| error.at(node.span()))
+    }
+    fn visit(&mut self, node: &Stmt) -> Result<(), SemanticError> {
+        self.dispatch_stmt(node).map_err(|error| error.at(node.span()))
+    }
+    fn visit_expr(&mut self, node: &Expr) -> Result<(), SemanticError> {
+        self.dispatch_expr(node).map_err(|error| error.at(node.span()))
+    }
+
+    /// `ON e: Exception DO body`, where `e` is only known inside `body`
+    fn visit_exception_handler(
+        &mut self,
+        handler: &ExceptionHandler,
+    ) -> Result<(), SemanticError> {
+        if !handler.class.eq_ignore_ascii_case(EXCEPTION) {
+            return Err(SemanticError::new(format!("unknown exception class {}", handler.class)));
+        }
+        let Some(variable) = &handler.variable else {
+            return self.visit(&handler.body);
+        };
+        self.enter_scope("ON");
+        self.scope().insert(variable, Symbol::Var(Type::exception()))?;
+        self.visit(&handler.body)?;
+        self.leave_scope();
+        Ok(())
+    }
+    /// Reject calling a standard routine the dialect of the program lacks
+    fn check_extension(&self, name: &str) -> Result<(), SemanticError> {
+        let feature = match name.to_uppercase().as_str() {
+            "SEEK" | "FILEPOS" | "FILESIZE" => Feature::FilePositioning,
+            "SETLENGTH" => Feature::DynamicArrays,
+            _ => return Ok(()),
+        };
+        if !self.dialect.allows(feature) {
+            return Err(SemanticError::new(format!(
+                "{} {}",
+                feature, self.dialect.rejection(feature)
+            )));
+        }
+        Ok(())
+    }
+    /// Note whether the first argument of READ, READLN, WRITE or WRITELN is
+    /// the file to read or write
+    fn visit_file_argument(
+        &mut self,
+        name: &str,
+        actual_params: &[Expr],
+    ) -> Result<(), SemanticError> {
+        let transfers_text = ["READ", "READLN", "WRITE", "WRITELN"]
+            .iter()
+            .any(|procedure| procedure.eq_ignore_ascii_case(name));
+        let Some(first) = actual_params.first().filter(|_| transfers_text) else {
+            return Ok(());
+        };
+        let type_node = match self.expr_type(first)? {
+            Some(type_node) => Some(self.resolve_type(&type_node)?),
+            None => None,
+        };
+        if let Some(Type::Text | Type::File(_)) = type_node {
+            self.file_arguments.insert(node_address(first));
+        }
+        Ok(())
+    }
+    /// Reject writing enumerated values as text, by their names, unless the
+    /// dialect of the program allows it
+    fn check_written_values(
+        &mut self,
+        name: &str,
+        actual_params: &[Expr],
+    ) -> Result<(), SemanticError> {
+        let writes_text = ["WRITE", "WRITELN", "STR"]
+            .iter()
+            .any(|procedure| procedure.eq_ignore_ascii_case(name));
+        if !writes_text {
+            return Ok(());
+        }
+        for param in actual_params {
+            let expr = match param {
+                Expr::FormattedParam(param) => param.expr.as_ref(),
+                param => param,
+            };
+            self.note_packed_string(expr)?;
+            if self.dialect.allows(Feature::EnumNames) {
+                continue;
+            }
+            let type_node = match self.expr_type(expr)? {
+                Some(type_node) => Some(self.resolve_type(&type_node)?),
+                None => None,
+            };
+            match type_node {
+                // a typed file is written the values as they are stored
+                Some(Type::File(_)) => return Ok(()),
+                Some(type_node) if matches!(type_node.host_type(), Type::Enum(_)) => {
+                    let feature = Feature::EnumNames;
+                    return Err(SemanticError::new(format!(
+                        "{} {}",
+                        feature, self.dialect.rejection(feature)
+                    )));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    /// Note an expression whose type is a packed array of characters, which
+    /// is written and compared as the string of its characters
+    fn note_packed_string(&mut self, node: &Expr) -> Result<(), SemanticError> {
+        let type_node = match self.expr_type(node)? {
+            Some(type_node) => self.resolve_type(&type_node)?,
+            None => return Ok(()),
+        };
+        if let Type::Array { element, packed: true, .. } = type_node
+            && element.host_type() == Type::Char
+        {
+            self.packed_strings.insert(node_address(node));
+        }
+        Ok(())
+    }
+    /// The canonical descriptor of a type, with the names of declared types
+    /// replaced by what they stand for
+    fn resolve_type(&mut self, type_node: &Type) -> Result<Type, SemanticError> {
+        Ok(match type_node {
+            Type::Named(name) => match self.scope().lookup(name) {
+                Some(Symbol::Type(type_node)) => type_node.clone(),
+                Some(_) => return Err(SemanticError::new(format!("{} is not a type", name))),
+                None => return Err(SemanticError::new(format!("undeclared type {}", name))),
+            },
+            Type::Array { index, element, packed } => {
+                let index = self.resolve_type(index)?;
+                if !index.is_ordinal() {
+                    return Err(SemanticError::new("array index type must be ordinal"));
+                }
+                let element = self.resolve_type(element)?;
+                Type::Array { index: Box::new(index), element: Box::new(element), packed: *packed }
+            }
+            Type::DynamicArray(element) => {
+                Type::DynamicArray(Box::new(self.resolve_type(element)?))
+            }
+            Type::Set(element) => {
+                let element = self.resolve_type(element)?;
+                if !element.is_ordinal() {
+                    return Err(SemanticError::new("set element type must be ordinal"));
+                }
+                Type::Set(Box::new(element))
+            }
+            Type::File(component) => {
+                let component = self.resolve_type(component)?;
+                if component.file_size().is_none() {
+                    return Err(SemanticError::new(
+                        "the components of a file cannot be or hold pointers, \
+                         routines, files or dynamic arrays"
+                    ));
+                }
+                Type::File(Box::new(component))
+            }
+            Type::Record { fields, variant_parts } => Type::Record {
+                fields: fields
+                    .iter()
+                    .map(|(name, type_node)| Ok((name.clone(), self.resolve_type(type_node)?)))
+                    .collect::<Result<_, SemanticError>>()?,
+                variant_parts: variant_parts.clone(),
+            },
+            Type::Routine { params, names, result } => Type::Routine {
+                params: params
+                    .iter()
+                    .map(|param| self.resolve_type(param))
+                    .collect::<Result<_, _>>()?,
+                names: names.clone(),
+                result: match result {
+                    Some(result) => Some(Box::new(self.resolve_type(result)?)),
+                    None => None,
+                },
+            },
+            Type::Pointer(target) => {
+                match target.as_ref() {
+                    Type::Named(name) => self.pointer_targets.push(name.clone()),
+                    target => {
+                        self.resolve_type(target)?;
+                    }
+                }
+                type_node.clone()
+            }
+            _ => type_node.clone(),
+        })
+    }
+    /// The names and resolved types of the parameters of a routine, along
+    /// with the resolved result type of a function
+    fn signature(
+        &mut self,
+        params: &[VarDecl],
+        return_type: Option<&Type>,
+    ) -> Result<Signature, SemanticError> {
+        let params = params
+            .iter()
+            .map(|param| Ok((param.var_node.name.clone(), self.resolve_type(&param.type_node)?)))
+            .collect::<Result<_, SemanticError>>()?;
+        let return_type = match return_type {
+            Some(return_type) => Some(self.resolve_type(return_type)?),
+            None => None,
+        };
+        Ok((params, return_type))
+    }
+    fn visit_routine_decl(
+        &mut self,
+        name: &str,
+        address: usize,
+        params: &[VarDecl],
+        return_type: Option<&Type>,
+        block: &Block,
+    ) -> Result<(), SemanticError> {
+        let signature = self.signature(params, return_type)?;
+        // a routine declared FORWARD in this block is already known, its
+        // definition has to repeat the same heading; when overloading, a
+        // different heading declares another routine instead
+        let overloading = self.dialect.allows(Feature::Overloading);
+        let forward = self.forward_routines.iter().position(|routine| {
+            same_name(&routine.name, name) && (!overloading || routine.repeated_by(&signature))
+        });
+        match forward {
+            Some(i) => {
+                let routine = self.forward_routines.remove(i);
+                if !routine.repeated_by(&signature) {
+                    return Err(SemanticError::new(format!(
+                        "heading of {} differs from its forward declaration",
+                        name
+                    )));
+                }
+                if routine.runtime_name != name {
+                    self.routine_names.insert(address, routine.runtime_name);
+                }
+            }
+            // the routine itself belongs to the enclosing scope, so that it
+            // can be called by its siblings as well as recursively
+            None => {
+                let param_types = signature.0.into_iter().map(|(_, type_node)| type_node).collect();
+                self.declare_routine(name, address, param_types, &signature.1)?;
+            }
+        }
+        self.enter_scope(name);
+        for param in params {
+            self.visit_var_decl(param)?;
+        }
+        // a function may also set its result through the implicit Result
+        // variable, unless a parameter is named so
+        if let Some(return_type) = &signature.1
+            && self.dialect.allows(Feature::ResultVariable)
+            && self.scope().lookup_local_mut(RESULT).is_none()
+        {
+            self.scope().insert(RESULT, Symbol::Var(return_type.clone()))?;
+        }
+        self.return_types.push(signature.1);
+        self.visit_block(block)?;
+        self.return_types.pop();
+        self.leave_scope();
+        Ok(())
+    }
+    /// Declare a routine in the current scope, returning the name the
+    /// interpreter knows it by
+    fn declare_routine(
+        &mut self,
+        name: &str,
+        address: usize,
+        params: Vec<Type>,
+        return_type: &Option<Type>,
+    ) -> Result<String, SemanticError> {
+        let dialect = self.dialect;
+        let Some(symbol) = self.scope().lookup_local_mut(name).filter(|symbol| {
+            matches!(
+                symbol,
+                Symbol::Procedure { .. } | Symbol::Function { .. } | Symbol::Overloads(_)
+            )
+        }) else {
+            self.scope().insert(name, Symbol::routine(params, return_type.clone()))?;
+            return Ok(String::from(name));
+        };
+        if !dialect.allows(Feature::Overloading) {
+            let rejection = dialect.rejection(Feature::Overloading);
+            return Err(SemanticError::new(format!(
+                "routine {} is declared twice; overloading a routine {}",
+                name, rejection
+            )));
+        }
+        // the routine declared first keeps its name, the later ones are
+        // told apart by their number
+        if !matches!(symbol, Symbol::Overloads(_)) {
+            let first = match std::mem::replace(symbol, Symbol::Overloads(Vec::new())) {
+                Symbol::Procedure { params } => {
+                    Overload { name: String::from(name), params, return_type: None }
+                }
+                Symbol::Function { params, return_type } => {
+                    Overload { name: String::from(name), params, return_type: Some(return_type) }
+                }
+                _ => unreachable!(),
+            };
+            let Symbol::Overloads(overloads) = symbol else { unreachable!() };
+            overloads.push(first);
+        }
+        let Symbol::Overloads(overloads) = symbol else { unreachable!() };
+        if overloads.iter().any(|overload| overload.params == params) {
+            return Err(SemanticError::new(format!(
+                "{} is already declared with the same parameter types",
+                name
+            )));
+        }
+        let runtime_name = format!("{}#{}", name, overloads.len() + 1);
+        let return_type = return_type.clone();
+        overloads.push(Overload { name: runtime_name.clone(), params, return_type });
+        self.routine_names.insert(address, runtime_name.clone());
+        Ok(runtime_name)
+    }
+    /// Choose the overload a call is to, being the one whose parameters take
+    /// the arguments with the fewest conversions, and record it for the interpreter
+    fn resolve_overload(
+        &mut self,
+        name: &str,
+        address: usize,
+        is_function: bool,
+        actual_params: &[Expr],
+    ) -> Result<Overload, SemanticError> {
+        let Some(Symbol::Overloads(overloads)) = self.scope().lookup(name) else {
+            unreachable!()
+        };
+        let overloads = overloads.clone();
+        let arg_types = actual_params
+            .iter()
+            .map(|param| self.expr_type(param))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut best = Vec::new();
+        let mut best_cost = u32::MAX;
+        for overload in &overloads {
+            if overload.return_type.is_some() != is_function
+                || overload.params.len() != arg_types.len()
+            {
+                continue;
+            }
+            // an argument whose type cannot be told fits any parameter
+            let cost: Option<u32> = overload
+                .params
+                .iter()
+                .zip(&arg_types)
+                .map(|(param, arg)| arg.as_ref().map_or(Some(0), |arg| conversion_cost(param, arg)))
+                .sum();
+            match cost {
+                Some(cost) if cost < best_cost => {
+                    best_cost = cost;
+                    best = vec![overload];
+                }
+                Some(cost) if cost == best_cost => best.push(overload),
+                _ => {}
+            }
+        }
+        let overload = match best[..] {
+            [overload] => overload.clone(),
+            [] => {
+                let message = format!("no overload of {} takes these arguments", name);
+                return Err(SemanticError::new(message));
+            }
+            _ => return Err(SemanticError::new(format!(
+                "ambiguous call to {}, as {} of its overloads take these \
+                 arguments equally well",
+                name, best.len()
+            ))),
+        };
+        self.routine_names.insert(address, overload.name.clone());
+        Ok(overload)
+    }
+    /// The type of an expression, as far as it can be told before running it
+    fn expr_type(&mut self, node: &Expr) -> Result<Option<Type>, SemanticError> {
+        self.node_type(node).map_err(|error| error.at(node.span()))
+    }
+    fn node_type(&mut self, node: &Expr) -> Result<Option<Type>, SemanticError> {
+        Ok(match node {
+            Expr::Literal(literal) => Type::of_value(&literal.value),
+            Expr::Var(var) => {
+                if let Some(type_node) = self.with_field(&var.name) {
+                    return Ok(Some(type_node.clone()));
+                }
+                match self.scope().lookup(&var.name) {
+                    // naming a variable holding a function calls it
+                    Some(Symbol::Var(Type::Routine { result: Some(return_type), .. })) => {
+                        Some(*return_type.clone())
+                    }
+                    Some(Symbol::Var(type_node) | Symbol::ConstParam(type_node)) => {
+                        Some(type_node.clone())
+                    }
+                    Some(Symbol::Const(value)) => Type::of_value(value),
+                    Some(Symbol::Function { return_type, .. }) => Some(return_type.clone()),
+                    Some(Symbol::Overloads(_)) => {
+                        self.resolve_overload(&var.name, node_address(var), true, &[])?.return_type
+                    }
+                    Some(_) => None,
+                    None => result_type(&var.name, &[]),
+                }
+            }
+            Expr::Index(_) | Expr::Field(_) | Expr::Deref(_) => Some(self.access_type(node)?),
+            Expr::FunctionCall(call) => match self.scope().lookup(&call.name) {
+                Some(Symbol::Function { return_type, .. }) => Some(return_type.clone()),
+                Some(Symbol::Var(Type::Routine { result: Some(return_type), .. })) => {
+                    Some(*return_type.clone())
+                }
+                Some(Symbol::Overloads(_)) => {
+                    let address = node_address(call);
+                    let overload =
+                        self.resolve_overload(&call.name, address, true, &call.actual_params)?;
+                    overload.return_type
+                }
+                Some(_) => None,
+                // LOW and HIGH give a bound of the type of their argument
+                None if is_type_function(&call.name)
+                    && !call.name.eq_ignore_ascii_case("SIZEOF") =>
+                {
+                    let Some(argument) = call.actual_params.first() else { return Ok(None) };
+                    let Some(type_node) = self.expr_type(argument)? else { return Ok(None) };
+                    match self.resolve_type(&type_node)? {
+                        Type::Array { index, .. } => Some(index.host_type()),
+                        Type::DynamicArray(_) => Some(Type::Integer),
+                        type_node if type_node.is_ordinal() => Some(type_node.host_type()),
+                        _ => None,
+                    }
+                }
+                None => {
+                    let args = call
+                        .actual_params
+                        .iter()
+                        .map(|param| self.expr_type(param))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    result_type(&call.name, &args)
+                }
+            },
+            Expr::BinOp(bin_op) => match bin_op.op_type {
+                OpType::EQUAL | OpType::NOT_EQUAL | OpType::LESS | OpType::LESS_EQUAL
+                | OpType::GREATER | OpType::GREATER_EQUAL | OpType::IN | OpType::AND
+                | OpType::OR => Some(Type::Boolean),
+                OpType::FLOAT_DIV => Some(Type::Real),
+                OpType::INTEGER_DIV | OpType::MOD => Some(Type::Integer),
+                _ => {
+                    let Some(left) = self.expr_type(&bin_op.left)? else { return Ok(None) };
+                    let Some(right) = self.expr_type(&bin_op.right)? else { return Ok(None) };
+                    match (left.host_type(), right.host_type()) {
+                        (Type::Integer, Type::Integer) => Some(Type::Integer),
+                        (Type::Integer | Type::Real, Type::Integer | Type::Real) => {
+                            Some(Type::Real)
+                        }
+                        (Type::String | Type::Char, Type::String | Type::Char) => {
+                            Some(Type::String)
+                        }
+                        // the operations on sets
+                        _ => None,
+                    }
+                }
+            },
+            Expr::UnaryOp(unary_op) => {
+                self.expr_type(&unary_op.expr)?.map(|type_node| type_node.host_type())
+            }
+            // a set is of the type of its first element, the empty set of none in particular
+            Expr::SetConstructor(set) => match set.elements.first() {
+                Some(element) => self
+                    .expr_type(&element.low)?
+                    .map(|type_node| Type::Set(Box::new(type_node.host_type()))),
+                None => None,
+            },
+            Expr::FormattedParam(_) => None,
+        })
+    }
+    /// The type of the result of the function whose name is assigned to
+    fn result_type(&mut self, node: &Expr) -> Option<Type> {
+        let Expr::Var(var) = node else { return None };
+        match self.scope().lookup(&var.name) {
+            Some(Symbol::Function { return_type, .. }) => Some(return_type.clone()),
+            _ => None,
+        }
+    }
+    /// Whether an expression gives a real where an integer is expected, which
+    /// TRUNC or ROUND has to convert first; an integer where a real is
+    /// expected is widened to it instead
+    fn narrows(&mut self, target: &Type, node: &Expr) -> Result<bool, SemanticError> {
+        let source = self.expr_type(node)?.map(|type_node| type_node.host_type());
+        Ok(target.host_type() == Type::Integer && source == Some(Type::Real))
+    }
+    /// The declared type of what is assigned to, or `None` for the result of a function
+    fn target_type(&mut self, node: &Expr) -> Result<Option<Type>, SemanticError> {
+        let Expr::Var(var) = node else {
+            return Ok(Some(self.access_type(node)?));
+        };
+        if let Some(type_node) = self.with_field(&var.name) {
+            return Ok(Some(type_node.clone()));
+        }
+        Ok(match self.scope().lookup(&var.name) {
+            Some(Symbol::Var(type_node)) => Some(type_node.clone()),
+            _ => None,
+        })
+    }
+    /// The type of the field `name` of the innermost record opened by WITH having one
+    fn with_field(&self, name: &str) -> Option<&Type> {
+        self.with_fields
+            .iter()
+            .rev()
+            .find_map(|(fields, _)| fields.iter().find(|(field, _)| same_name(field, name)))
+            .map(|(_, type_node)| type_node)
+    }
+    /// Whether a name stands for a CONST parameter or for a field of one opened by WITH
+    fn is_const_param(&mut self, name: &str) -> bool {
+        for (fields, is_const) in self.with_fields.iter().rev() {
+            if fields.iter().any(|(field, _)| same_name(field, name)) {
+                return *is_const;
+            }
+        }
+        matches!(self.scope().lookup(name), Some(Symbol::ConstParam(_)))
+    }
+    /// The name of the CONST parameter a variable access is part of, if any;
+    /// what a pointer points to is never part of the pointer itself
+    fn const_param_root<'a>(&mut self, node: &'a Expr) -> Option<&'a str> {
+        match node {
+            Expr::Var(var) => self.is_const_param(&var.name).then_some(var.name.as_str()),
+            Expr::Index(index) => self.const_param_root(&index.array),
+            Expr::Field(field) => self.const_param_root(&field.record),
+            _ => None,
+        }
+    }
+    fn check_not_const_param(&mut self, node: &Expr) -> Result<(), SemanticError> {
+        match self.const_param_root(node) {
+            Some(name) => {
+                Err(SemanticError::new(format!("cannot assign to CONST parameter {}", name)))
+            }
+            None => Ok(()),
+        }
+    }
+    /// The type of a variable access, as needed to know which fields a WITH opens
+    fn access_type(&mut self, node: &Expr) -> Result<Type, SemanticError> {
+        Ok(match node {
+            Expr::Var(var) => {
+                if let Some(type_node) = self.with_field(&var.name) {
+                    return Ok(type_node.clone());
+                }
+                match self.scope().lookup(&var.name) {
+                    Some(Symbol::Var(type_node) | Symbol::ConstParam(type_node)) => {
+                        type_node.clone()
+                    }
+                    _ => return Err(SemanticError::new(format!("{} is not a variable", var.name))),
+                }
+            }
+            Expr::Index(index) => match self.access_type(&index.array)? {
+                Type::Array { element, .. } | Type::DynamicArray(element) => *element,
+                _ => return Err(SemanticError::new("only arrays can be indexed")),
+            },
+            Expr::Field(field) => {
+                let Type::Record { fields, .. } = self.access_type(&field.record)? else {
+                    return Err(SemanticError::new("only records have fields"));
+                };
+                match fields.into_iter().find(|(name, _)| same_name(name, &field.name)) {
+                    Some((_, type_node)) => type_node,
+                    None => {
+                        let message = format!("no field {} in record", field.name);
+                        return Err(SemanticError::new(message));
+                    }
+                }
+            }
+            Expr::Deref(deref) => match self.access_type(&deref.pointer)? {
+                Type::Pointer(target) => self.resolve_type(&target)?,
+                _ => return Err(SemanticError::new("only pointers can be dereferenced")),
+            },
+            _ => return Err(SemanticError::new("expected a variable")),
+        })
+    }
+    /// A variable assigned to as a whole, by an assignment, a FOR statement
+    /// or a standard procedure it is passed to by reference
+    fn visit_target(&mut self, node: &Var) -> Result<(), SemanticError> {
+        if self.is_const_param(&node.name) {
+            let message = format!("cannot assign to CONST parameter {}", node.name);
+            return Err(SemanticError::new(message));
+        }
+        if self.with_field(&node.name).is_some() {
+            return Ok(());
+        }
+        if self.loop_variables.iter().any(|variable| same_name(variable, &node.name)) {
+            let message =
+                format!("illegal assignment to FOR loop control variable {}", node.name);
+            return Err(SemanticError::new(message));
+        }
+        match self.scope().lookup(&node.name) {
+            // assigning to a function's name sets its result
+            Some(Symbol::Var(_) | Symbol::Function { .. } | Symbol::Overloads(_)) => {}
+            Some(Symbol::Const(_)) => {
+                return Err(SemanticError::new(format!("cannot assign to constant {}", node.name)))
+            }
+            Some(_) => return Err(SemanticError::new(format!("cannot assign to {}", node.name))),
+            None => {
+                return Err(SemanticError::new(format!("undeclared identifier {}", node.name)));
+            }
+        }
+        Ok(())
+    }
+    /// An argument passed by reference, which the procedure may assign to
+    fn visit_variable_param(&mut self, name: &str, node: &Expr) -> Result<(), SemanticError> {
+        match node {
+            Expr::Var(var) => self.visit_target(var),
+            Expr::Index(_) | Expr::Field(_) | Expr::Deref(_) => {
+                self.check_not_const_param(node)?;
+                self.visit_expr(node)
+            }
+            _ => Err(SemanticError::new(format!("{} expects a variable as argument", name))),
+        }
+    }
+    fn check_arguments(
+        &mut self,
+        name: &str,
+        params: &[Type],
+        actual_params: &[Expr],
+    ) -> Result<(), SemanticError> {
+        check_argument_count(name, params.len(), actual_params)?;
+        for (param, actual_param) in params.iter().zip(actual_params) {
+            match param {
+                Type::Routine { .. } => self.visit_routine_argument(param, actual_param)?,
+                _ => {
+                    self.visit_expr(actual_param)?;
+                    if self.narrows(param, actual_param)? {
+                        return Err(SemanticError::new(format!(
+                            "cannot pass a value of type REAL to {} for a \
+                             parameter of type INTEGER without TRUNC or ROUND",
+                            name
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    /// An argument for a procedural parameter, or a value assigned to a variable
+    /// of a procedure type, which has to name a routine, or a variable holding
+    /// one, with the same parameter and result types
+    fn visit_routine_argument(&mut self, param: &Type, node: &Expr) -> Result<(), SemanticError> {
+        let Type::Routine { params, result, .. } = param else {
+            unreachable!()
+        };
+        let Expr::Var(var) = node else {
+            return Err(SemanticError::new("expected the name of a routine"));
+        };
+        self.routine_values.insert(node_address(var));
+        if let Some(Symbol::Overloads(overloads)) = self.scope().lookup(&var.name) {
+            // the overload passed is the one with the very heading of the parameter
+            let result = result.as_deref();
+            let overload = overloads.iter().find(|overload| {
+                overload.params == *params && overload.return_type.as_ref() == result
+            });
+            match overload {
+                Some(overload) => {
+                    let name = overload.name.clone();
+                    self.routine_names.insert(node_address(var), name);
+                }
+                None => {
+                    return Err(SemanticError::new(format!(
+                        "no overload of {} matches the heading of the procedure type",
+                        var.name
+                    )));
+                }
+            }
+            return Ok(());
+        }
+        match self.scope().lookup(&var.name) {
+            Some(Symbol::Procedure { params: routine_params })
+                if result.is_none() && routine_params == params => Ok(()),
+            Some(Symbol::Function { params: routine_params, return_type })
+                if result.as_deref() == Some(return_type) && routine_params == params => Ok(()),
+            Some(Symbol::Var(Type::Routine {
+                params: routine_params,
+                result: routine_result,
+                ..
+            }))
+                if routine_result == result && routine_params == params => Ok(()),
+            Some(
+                Symbol::Procedure { .. }
+                | Symbol::Function { .. }
+                | Symbol::Var(Type::Routine { .. }),
+            ) => Err(SemanticError::new(format!(
+                "{} does not match the heading of the procedure type",
+                var.name
+            ))),
+            _ => Err(SemanticError::new(format!("{} is not a routine", var.name))),
+        }
+    }
+}
+
+impl Visitor for SemanticAnalyzer {
+    type StmtOutput = Result<(), SemanticError>;
+    type ExprOutput = Result<(), SemanticError>;
+    type DeclOutput = Result<(), SemanticError>;
+
+    fn visit_program(&mut self, program: &Program) -> Result<(), SemanticError> {
+        // the scope of each unit stays once the unit is analyzed, for the
+        // program and the later units to see its interface
+        for unit in &program.units {
+            self.use_units(&unit.uses);
+            self.enter_scope(&unit.name);
+            self.visit_block(&unit.block)?;
+            let interface = unit.interface.iter().map(|name| (name.clone(), ())).collect();
+            self.scope().interface = Some(interface);
+        }
+        self.use_units(&program.uses);
+        self.enter_scope("global");
+        self.visit_block(&program.block)?;
+        for _ in 0..=program.units.len() {
+            self.leave_scope();
+        }
+        Ok(())
+    }
+    fn visit_block(&mut self, block: &Block) -> Result<(), SemanticError> {
+        let pointer_targets = self.pointer_targets.len();
+        let forward_routines = std::mem::take(&mut self.forward_routines);
+        for declaration in &block.declarations {
+            self.visit_decl(declaration)?;
+        }
+        for name in self.pointer_targets.split_off(pointer_targets) {
+            self.resolve_type(&Type::Named(name)).map_err(|error| error.at(&block.span))?;
+        }
+        if let Some(routine) = self.forward_routines.first() {
+            let message = format!("forward routine {} is never defined", routine.name);
+            return Err(SemanticError::new(message).at(&block.span));
+        }
+        self.forward_routines = forward_routines;
+        for child in &block.compound_statement.children {
+            self.visit(child)?;
+        }
+        Ok(())
+    }
+
+    fn visit_compound(&mut self, node: &Compound) -> Result<(), SemanticError> {
+        for child in &node.children {
+            self.visit(child)?;
+        }
+        Ok(())
+    }
+    fn visit_assign(&mut self, node: &Assign) -> Result<(), SemanticError> {
+        match &node.left {
+            Expr::Var(left) => self.visit_target(left)?,
+            left => {
+                self.check_not_const_param(left)?;
+                self.visit_expr(left)?
+            }
+        }
+        match self.target_type(&node.left)? {
+            // a routine assigned to a variable of a procedure type is named, not called
+            Some(type_node @ Type::Routine { .. }) => {
+                self.visit_routine_argument(&type_node, &node.right)
+            }
+            target => {
+                self.visit_expr(&node.right)?;
+                let Some(target) = target.or_else(|| self.result_type(&node.left)) else {
+                    return Ok(());
+                };
+                if self.narrows(&target, &node.right)? {
+                    return Err(SemanticError::new(
+                        "cannot assign a value of type REAL to a variable of type INTEGER \
+                         without TRUNC or ROUND",
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+    fn visit_procedure_call(&mut self, call: &ProcedureCall) -> Result<(), SemanticError> {
+        match self.scope().lookup(&call.name) {
+            // a variable of a procedure type is called like the routine it holds
+            Some(
+                Symbol::Procedure { params }
+                | Symbol::Var(Type::Routine { params, result: None, .. }),
+            ) => {
+                let params = params.clone();
+                self.check_arguments(&call.name, &params, &call.actual_params)?
+            }
+            Some(Symbol::Overloads(_)) => {
+                let address = node_address(call);
+                let overload =
+                    self.resolve_overload(&call.name, address, false, &call.actual_params)?;
+                self.check_arguments(&call.name, &overload.params, &call.actual_params)?
+            }
+            // the standard procedures check their own arguments when called
+            None if is_standard_procedure(&call.name) => {
+                self.check_extension(&call.name)?;
+                let positions = variable_params(&call.name);
+                let variables = call.actual_params.iter().skip(positions.start);
+                for param in variables.take(positions.len()) {
+                    self.visit_variable_param(&call.name, param)?;
+                }
+                for param in &call.actual_params {
+                    self.visit_expr(param)?;
+                }
+                self.visit_file_argument(&call.name, &call.actual_params)?;
+                self.check_written_values(&call.name, &call.actual_params)?;
+            }
+            Some(_) => {
+                return Err(SemanticError::new(format!("{} is not a procedure", call.name)));
+            }
+            // BREAK, EXIT and the like are names in the dialects lacking them
+            None if let Some(feature) = keyword(&call.name).and_then(Feature::of_keyword) => {
+                let rejection = self.dialect.rejection(feature);
+                return Err(SemanticError::new(format!("{} {}", feature, rejection)));
+            }
+            None => return Err(SemanticError::new(format!("unknown procedure {}", call.name))),
+        }
+        Ok(())
+    }
+    fn visit_if(&mut self, node: &If) -> Result<(), SemanticError> {
+        self.visit_expr(&node.condition)?;
+        self.visit(&node.then_branch)?;
+        if let Some(else_branch) = &node.else_branch {
+            self.visit(else_branch)?;
+        }
+        Ok(())
+    }
+    fn visit_while(&mut self, node: &While) -> Result<(), SemanticError> {
+        self.visit_expr(&node.condition)?;
+        self.visit(&node.body)
+    }
+    fn visit_for(&mut self, node: &For) -> Result<(), SemanticError> {
+        self.visit_target(&node.variable)?;
+        self.visit_expr(&node.start)?;
+        self.visit_expr(&node.end)?;
+        self.loop_variables.push(node.variable.name.clone());
+        self.visit(&node.body)?;
+        self.loop_variables.pop();
+        Ok(())
+    }
+    fn visit_repeat(&mut self, node: &Repeat) -> Result<(), SemanticError> {
+        for child in &node.children {
+            self.visit(child)?;
+        }
+        self.visit_expr(&node.condition)
+    }
+    fn visit_case(&mut self, node: &Case) -> Result<(), SemanticError> {
+        self.visit_expr(&node.selector)?;
+        for arm in &node.arms {
+            for label in &arm.labels {
+                self.visit_expr(&label.low)?;
+                if let Some(high) = &label.high {
+                    self.visit_expr(high)?;
+                }
+            }
+            self.visit(&arm.body)?;
+        }
+        for child in node.else_branch.iter().flatten() {
+            self.visit(child)?;
+        }
+        Ok(())
+    }
+    fn visit_with(&mut self, node: &With) -> Result<(), SemanticError> {
+        self.visit_expr(&node.record)?;
+        let Type::Record { fields, .. } = self.access_type(&node.record)? else {
+            return Err(SemanticError::new("WITH expects a record"))
+        };
+        let is_const = self.const_param_root(&node.record).is_some();
+        self.with_fields.push((fields, is_const));
+        self.visit(&node.body)?;
+        self.with_fields.pop();
+        Ok(())
+    }
+    fn visit_break(&mut self, _: &Span) -> Result<(), SemanticError> {
+        Ok(())
+    }
+    fn visit_continue(&mut self, _: &Span) -> Result<(), SemanticError> {
+        Ok(())
+    }
+    fn visit_exit(&mut self, node: &Exit) -> Result<(), SemanticError> {
+        if let Some(result) = &node.result {
+            if !matches!(self.return_types.last(), Some(Some(_))) {
+                let message = "EXIT can only return a value from a function";
+                return Err(SemanticError::new(message));
+            }
+            self.visit_expr(result)?;
+        }
+        Ok(())
+    }
+    fn visit_goto(&mut self, node: &Goto) -> Result<(), SemanticError> {
+        // only the labels of the running routine's own block can be jumped to
+        let label = self.scope().lookup_local_mut(&node.label);
+        if !matches!(label, Some(Symbol::Label { .. })) {
+            return Err(SemanticError::new(format!("undeclared label {}", node.label)));
+        }
+        Ok(())
+    }
+    fn visit_labeled(&mut self, node: &Labeled) -> Result<(), SemanticError> {
+        match self.scope().lookup_local_mut(&node.label) {
+            Some(Symbol::Label { defined }) if !*defined => *defined = true,
+            Some(Symbol::Label { .. }) => {
+                let message = format!("label {} is defined twice", node.label);
+                return Err(SemanticError::new(message));
+            }
+            _ => {
+                let message = format!("undeclared label {}", node.label);
+                return Err(SemanticError::new(message));
+            }
+        }
+        self.visit(&node.statement)
+    }
+    fn visit_try_except(&mut self, node: &TryExcept) -> Result<(), SemanticError> {
+        for child in &node.body {
+            self.visit(child)?;
+        }
+        self.handler_depth += 1;
+        for handler in &node.handlers {
+            self.visit_exception_handler(handler)?;
+        }
+        for child in node.else_branch.iter().flatten() {
+            self.visit(child)?;
+        }
+        self.handler_depth -= 1;
+        Ok(())
+    }
+    fn visit_try_finally(&mut self, node: &TryFinally) -> Result<(), SemanticError> {
+        for child in node.body.iter().chain(&node.finally) {
+            self.visit(child)?;
+        }
+        Ok(())
+    }
+    fn visit_raise(&mut self, node: &Raise) -> Result<(), SemanticError> {
+        match &node.message {
+            Some(message) => {
+                self.visit_expr(message)?;
+                if self.expr_type(message)?.is_some_and(|type_node| {
+                    !matches!(type_node.host_type(), Type::String | Type::Char)
+                }) {
+                    return Err(SemanticError::new("RAISE expects a message string"));
+                }
+                Ok(())
+            }
+            None if self.handler_depth == 0 => Err(SemanticError::new(
+                "RAISE without a message can only be used in an exception handler",
+            )),
+            None => Ok(()),
+        }
+    }
+    fn visit_no_op(&mut self, _: &Span) -> Result<(), SemanticError> {
+        Ok(())
+    }
+
+    fn visit_bin_op(&mut self, node: &BinOp) -> Result<(), SemanticError> {
+        self.visit_expr(&node.left)?;
+        self.visit_expr(&node.right)?;
+        if let OpType::EQUAL | OpType::NOT_EQUAL | OpType::LESS | OpType::LESS_EQUAL
+        | OpType::GREATER | OpType::GREATER_EQUAL = node.op_type
+        {
+            self.note_packed_string(&node.left)?;
+            self.note_packed_string(&node.right)?;
+        }
+        Ok(())
+    }
+    fn visit_literal(&mut self, _: &Literal) -> Result<(), SemanticError> {
+        Ok(())
+    }
+    fn visit_unary_op(&mut self, node: &UnaryOp) -> Result<(), SemanticError> {
+        self.visit_expr(&node.expr)
+    }
+    fn visit_var(&mut self, node: &Var) -> Result<(), SemanticError> {
+        if self.with_field(&node.name).is_some() {
+            return Ok(());
+        }
+        match self.scope().lookup(&node.name) {
+            // a function without parameters is called by just naming it, and
+            // so is a variable holding one
+            Some(Symbol::Var(Type::Routine { params, result, .. }))
+                if result.is_none() || !params.is_empty() =>
+            {
+                return Err(SemanticError::new(format!(
+                    "{} holds a routine, which has to be called",
+                    node.name
+                )));
+            }
+            Some(Symbol::Var(_) | Symbol::ConstParam(_) | Symbol::Const(_)) => {}
+            Some(Symbol::Function { params, .. }) if params.is_empty() => {}
+            Some(Symbol::Overloads(_)) => {
+                self.resolve_overload(&node.name, node_address(node), true, &[])?;
+            }
+            Some(_) => return Err(SemanticError::new(format!("{} is not a variable", node.name))),
+            None if standard_function(&node.name).is_some_and(|counts| counts.contains(&0)) => {}
+            None => {
+                return Err(SemanticError::new(format!("undeclared identifier {}", node.name)));
+            }
+        }
+        Ok(())
+    }
+    fn visit_index(&mut self, node: &Index) -> Result<(), SemanticError> {
+        self.visit_expr(&node.array)?;
+        self.visit_expr(&node.index)
+    }
+    fn visit_field(&mut self, node: &Field) -> Result<(), SemanticError> {
+        self.visit_expr(&node.record)?;
+        let Some(record) = self.expr_type(&node.record)? else {
+            return Ok(());
+        };
+        match self.resolve_type(&record)? {
+            Type::Record { fields, .. }
+                if fields.iter().any(|(field, _)| same_name(field, &node.name)) => Ok(()),
+            Type::Record { .. } => {
+                Err(SemanticError::new(format!("no field {} in record", node.name)))
+            }
+            _ => Err(SemanticError::new("only records have fields")),
+        }
+    }
+    fn visit_deref(&mut self, node: &Deref) -> Result<(), SemanticError> {
+        self.visit_expr(&node.pointer)
+    }
+    fn visit_set_constructor(&mut self, node: &SetConstructor) -> Result<(), SemanticError> {
+        for element in &node.elements {
+            self.visit_expr(&element.low)?;
+            if let Some(high) = &element.high {
+                self.visit_expr(high)?;
+            }
+        }
+        Ok(())
+    }
+    fn visit_function_call(&mut self, call: &FunctionCall) -> Result<(), SemanticError> {
+        match self.scope().lookup(&call.name) {
+            Some(
+                Symbol::Function { params, .. }
+                | Symbol::Var(Type::Routine { params, result: Some(_), .. }),
+            ) => {
+                let params = params.clone();
+                self.check_arguments(&call.name, &params, &call.actual_params)?
+            }
+            Some(Symbol::Overloads(_)) => {
+                let address = node_address(call);
+                let overload =
+                    self.resolve_overload(&call.name, address, true, &call.actual_params)?;
+                self.check_arguments(&call.name, &overload.params, &call.actual_params)?
+            }
+            Some(_) => {
+                return Err(SemanticError::new(format!("{} is not a function", call.name)));
+            }
+            None if standard_function(&call.name).is_some() => {
+                self.check_extension(&call.name)?;
+                let param_counts = standard_function(&call.name).unwrap();
+                if !param_counts.contains(&call.actual_params.len()) {
+                    return Err(SemanticError::new(format!(
+                        "{} expects {} to {} arguments but got {}",
+                        call.name, param_counts.start(), param_counts.end(),
+                        call.actual_params.len()
+                    )));
+                }
+                for param in &call.actual_params {
+                    self.visit_expr(param)?;
+                }
+                if is_type_function(&call.name) {
+                    let argument = &call.actual_params[0];
+                    let Some(type_node) = self.expr_type(argument)? else {
+                        return Err(SemanticError::new(format!(
+                            "cannot tell the type of the argument of {}",
+                            call.name
+                        )))
+                    };
+                    let type_node = self.resolve_type(&type_node)?;
+                    let has_bounds = type_node.is_ordinal()
+                        || matches!(type_node, Type::Array { .. } | Type::DynamicArray(_));
+                    if !has_bounds && !call.name.eq_ignore_ascii_case("SIZEOF") {
+                        return Err(SemanticError::new(format!(
+                            "{} expects an ordinal or array type",
+                            call.name
+                        )));
+                    }
+                    self.type_arguments.insert(node_address(argument), type_node);
+                }
+            }
+            None => match builtin_function(&call.name) {
+                Some(function) if function.param_count == usize::MAX => {
+                    if call.actual_params.is_empty() {
+                        let message = format!("{} expects at least 1 argument", call.name);
+                        return Err(SemanticError::new(message));
+                    }
+                    for param in &call.actual_params {
+                        self.visit_expr(param)?;
+                    }
+                }
+                Some(function) => {
+                    let count = function.param_count;
+                    check_argument_count(&call.name, count, &call.actual_params)?;
+                    for param in &call.actual_params {
+                        self.visit_expr(param)?;
+                    }
+                }
+                None => {
+                    return Err(SemanticError::new(format!("unknown function {}", call.name)));
+                }
+            },
+        }
+        Ok(())
+    }
+    fn visit_formatted_param(&mut self, node: &FormattedParam) -> Result<(), SemanticError> {
+        self.visit_expr(&node.expr)?;
+        self.visit_expr(&node.width)?;
+        if let Some(precision) = &node.precision {
+            self.visit_expr(precision)?;
+        }
+        Ok(())
+    }
+
+    fn visit_label_decl(&mut self, node: &LabelDecl) -> Result<(), SemanticError> {
+        self.scope().insert(&node.label, Symbol::Label { defined: false })
+    }
+    fn visit_const_decl(&mut self, node: &ConstDecl) -> Result<(), SemanticError> {
+        self.scope().insert(&node.name, Symbol::Const(node.constant.value.clone()))
+    }
+    fn visit_typed_const_decl(&mut self, node: &TypedConstDecl) -> Result<(), SemanticError> {
+        // a typed constant may be assigned to just like a variable
+        let type_node = self.resolve_type(&node.type_node)?;
+        self.scope().insert(&node.name, Symbol::Var(type_node))
+    }
+    fn visit_type_decl(&mut self, node: &TypeDecl) -> Result<(), SemanticError> {
+        let type_node = self.resolve_type(&node.type_node)?;
+        self.scope().insert(&node.name, Symbol::Type(type_node))
+    }
+    fn visit_var_decl(&mut self, node: &VarDecl) -> Result<(), SemanticError> {
+        let symbol = match self.resolve_type(&node.type_node)? {
+            type_node if node.is_const => Symbol::ConstParam(type_node),
+            type_node => Symbol::Var(type_node),
+        };
+        self.scope().insert(&node.var_node.name, symbol)
+    }
+    fn visit_forward_decl(&mut self, node: &ForwardDecl) -> Result<(), SemanticError> {
+        let (params, return_type) = self.signature(&node.params, node.return_type.as_ref())?;
+        let param_types = params.iter().map(|(_, type_node)| type_node.clone()).collect();
+        let address = node_address(node);
+        let runtime_name = self.declare_routine(&node.name, address, param_types, &return_type)?;
+        let name = node.name.clone();
+        let routine = ForwardRoutine { name, runtime_name, params, return_type };
+        self.forward_routines.push(routine);
+        Ok(())
+    }
+    fn visit_procedure_decl(&mut self, node: &Rc<ProcedureDecl>) -> Result<(), SemanticError> {
+        let address = node_address(node.as_ref());
+        self.visit_routine_decl(&node.name, address, &node.params, None, &node.block)
+    }
+    fn visit_function_decl(&mut self, node: &Rc<FunctionDecl>) -> Result<(), SemanticError> {
+        let (params, return_type) = (&node.params, Some(&node.return_type));
+        let address = node_address(node.as_ref());
+        self.visit_routine_decl(&node.name, address, params, return_type, &node.block)
+    }
+}
+
+fn check_argument_count(
+    name: &str,
+    param_count: usize,
+    actual_params: &[Expr],
+) -> Result<(), SemanticError> {
+    if param_count != actual_params.len() {
+        return Err(SemanticError::new(format!(
+            "{} expects {} arguments but got {}",
+            name, param_count, actual_params.len()
+        )));
+    }
+    Ok(())
+}
+
+/// How far an argument of type `arg` is from a parameter of type `param`, being 0
+/// for the same type, or `None` when the argument cannot be passed for the parameter
+fn conversion_cost(param: &Type, arg: &Type) -> Option<u32> {
+    match (param.host_type(), arg.host_type()) {
+        (param, arg) if param == arg => Some(0),
+        (Type::Real, Type::Integer) | (Type::String, Type::Char) => Some(1),
+        (Type::Set(element), Type::Set(arg_element))
+            if element.host_type() == arg_element.host_type() =>
+        {
+            Some(0)
+        }
+        (Type::DynamicArray(element), Type::Array { element: arg_element, .. })
+            if element == arg_element =>
+        {
+            Some(1)
+        }
+        _ => None,
+    }
+}
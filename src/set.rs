@@ -0,0 +1,153 @@
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+
+use crate::error::RuntimeError;
+use crate::value::Value;
+
+/// The largest ordinal a set can hold
+pub const MAX_ORDINAL: i32 = 255;
+
+/// A set of ordinal values, stored as a bitset over the ordinals `0..=MAX_ORDINAL`
+#[derive(Clone, Debug)]
+pub struct Set {
+    bits: [u64; 4],
+    /// a value of the element type, used to turn ordinals back into values;
+    /// unknown for the empty set `[]` until it is stored in a set variable
+    element: Option<Box<Value>>,
+}
+
+impl Set {
+    pub fn new(element: Option<Value>) -> Set {
+        Set { bits: [0; 4], element: element.map(Box::new) }
+    }
+    pub fn insert(&mut self, value: &Value) -> Result<(), RuntimeError> {
+        let ordinal = value.ordinal()?;
+        if !(0..=MAX_ORDINAL).contains(&ordinal) {
+            return Err(RuntimeError::new(format!(
+                "set element {} out of range 0..{}",
+                value, MAX_ORDINAL
+            )));
+        }
+        if let Some(element) = &self.element
+            && std::mem::discriminant(element.as_ref()) != std::mem::discriminant(value)
+        {
+            return Err(RuntimeError::new(format!(
+                "cannot put a {} into a set of {}",
+                value.type_name(), element.type_name()
+            )));
+        }
+        self.element.get_or_insert_with(|| Box::new(value.clone()));
+        self.bits[ordinal as usize / 64] |= 1 << (ordinal % 64);
+        Ok(())
+    }
+    pub fn contains(&self, value: &Value) -> Result<bool, RuntimeError> {
+        if let Some(element) = &self.element
+            && std::mem::discriminant(element.as_ref()) != std::mem::discriminant(value)
+        {
+            return Err(RuntimeError::new(format!(
+                "cannot look for a {} in a set of {}",
+                value.type_name(), element.type_name()
+            )));
+        }
+        Ok(self.has_ordinal(value.ordinal()?))
+    }
+    pub fn union(&self, other: &Set) -> Result<Set, RuntimeError> {
+        self.combine(other, |left, right| left | right)
+    }
+    pub fn intersection(&self, other: &Set) -> Result<Set, RuntimeError> {
+        self.combine(other, |left, right| left & right)
+    }
+    pub fn difference(&self, other: &Set) -> Result<Set, RuntimeError> {
+        self.combine(other, |left, right| left & !right)
+    }
+    pub fn is_subset(&self, other: &Set) -> Result<bool, RuntimeError> {
+        self.check_compatible(other)?;
+        Ok(self.bits.iter().zip(&other.bits).all(|(left, right)| left & !right == 0))
+    }
+    /// Convert the set for storing in a set variable which currently holds `target`
+    pub fn converted_like(self, target: &Set) -> Result<Set, RuntimeError> {
+        self.check_compatible(target)?;
+        Ok(Set { bits: self.bits, element: target.element.clone() })
+    }
+
+    /// The members as the 32 bytes of a bitset, as a set is stored in a typed file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.bits.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+    /// The set `to_bytes` gave `bytes` for, of elements of the type of `element`
+    pub fn from_bytes(bytes: &[u8], element: Value) -> Set {
+        Set {
+            bits: std::array::from_fn(|i| {
+                u64::from_le_bytes(bytes[i * 8..i * 8 + 8].try_into().unwrap())
+            }),
+            element: Some(Box::new(element)),
+        }
+    }
+    fn combine(&self, other: &Set, op: impl Fn(u64, u64) -> u64) -> Result<Set, RuntimeError> {
+        self.check_compatible(other)?;
+        Ok(Set {
+            bits: std::array::from_fn(|i| op(self.bits[i], other.bits[i])),
+            element: self.element.clone().or_else(|| other.element.clone()),
+        })
+    }
+    fn check_compatible(&self, other: &Set) -> Result<(), RuntimeError> {
+        if let (Some(left), Some(right)) = (&self.element, &other.element)
+            && std::mem::discriminant(left.as_ref()) != std::mem::discriminant(right.as_ref())
+        {
+            return Err(RuntimeError::new(format!(
+                "incompatible sets of {} and of {}",
+                left.type_name(), right.type_name()
+            )));
+        }
+        Ok(())
+    }
+    fn has_ordinal(&self, ordinal: i32) -> bool {
+        (0..=MAX_ORDINAL).contains(&ordinal)
+            && self.bits[ordinal as usize / 64] & (1 << (ordinal % 64)) != 0
+    }
+    fn ordinals(&self) -> impl Iterator<Item = i32> + '_ {
+        (0..=MAX_ORDINAL).filter(|&ordinal| self.has_ordinal(ordinal))
+    }
+    /// The members in ascending order, as integers when the type of the
+    /// elements is not known
+    pub fn members(&self) -> impl Iterator<Item = Value> + '_ {
+        self.ordinals().map(|ordinal| {
+            let element = self.element.as_ref();
+            let member = element.and_then(|element| element.try_with_ordinal(ordinal));
+            member.unwrap_or(Value::Integer(ordinal))
+        })
+    }
+}
+
+/// A set is written as the list of its members
+impl Serialize for Set {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.members())
+    }
+}
+
+/// Sets are equal when they have the same members, whatever their element type
+impl PartialEq for Set {
+    fn eq(&self, other: &Set) -> bool {
+        self.bits == other.bits
+    }
+}
+
+impl fmt::Display for Set {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        if let Some(element) = &self.element {
+            for (i, ordinal) in self.ordinals().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                match element.try_with_ordinal(ordinal) {
+                    Some(value) => write!(f, "{}", value)?,
+                    None => write!(f, "{}", ordinal)?,
+                }
+            }
+        }
+        write!(f, "]")
+    }
+}
@@ -0,0 +1,118 @@
+use crate::error::SemanticError;
+use crate::names::Names;
+use crate::types::Type;
+use crate::value::Value;
+
+/// The variable standing for the result of the function it is used in
+pub const RESULT: &str = "Result";
+/// The variable the REPL keeps the value of the last expression typed in in
+pub const IT: &str = "it";
+
+#[derive(Clone)]
+pub enum Symbol {
+    /// a variable along with its type, resolved when declared
+    Var(Type),
+    /// a parameter declared CONST, which is read like a variable but never assigned to
+    ConstParam(Type),
+    /// a constant along with its value
+    Const(Value),
+    /// a declared type, standing for the canonical descriptor resolved at its declaration
+    Type(Type),
+    /// a routine along with the resolved types of its parameters
+    Procedure { params: Vec<Type> },
+    Function { params: Vec<Type>, return_type: Type },
+    /// a routine declared several times with different parameters, in the
+    /// dialect allowing overloading
+    Overloads(Vec<Overload>),
+    /// a label declared in a LABEL section, `defined` once a statement carries it
+    Label { defined: bool },
+}
+
+impl Symbol {
+    /// The symbol of a procedure, or of a function when it has a result type
+    pub fn routine(params: Vec<Type>, return_type: Option<Type>) -> Symbol {
+        match return_type {
+            Some(return_type) => Symbol::Function { params, return_type },
+            None => Symbol::Procedure { params },
+        }
+    }
+}
+
+/// One of the routines sharing an overloaded name
+#[derive(Clone)]
+pub struct Overload {
+    /// the name the interpreter knows this routine by, which is the shared
+    /// name for the first one declared
+    pub name: String,
+    pub params: Vec<Type>,
+    pub return_type: Option<Type>,
+}
+
+/// The symbols declared by one program, unit, procedure or function.
+///
+/// Lookups that miss fall back to the enclosing scope, so a routine sees the
+/// names of the routines it is nested in but never the other way round. The
+/// scopes of the units enclose that of the program, each enclosing the units
+/// loaded after it.
+#[derive(Clone)]
+pub struct ScopedSymbolTable {
+    pub scope_name: String,
+    symbols: Names<Symbol>,
+    pub enclosing_scope: Option<Box<ScopedSymbolTable>>,
+    /// for a unit analyzed already, the names declared by its interface,
+    /// which are all the program and the later units see of it
+    pub interface: Option<Names<()>>,
+    /// for a unit analyzed already, whether the program or unit being
+    /// analyzed uses it, seeing none of its names otherwise
+    pub used: bool,
+}
+
+impl ScopedSymbolTable {
+    pub fn new(
+        scope_name: &str,
+        enclosing_scope: Option<Box<ScopedSymbolTable>>,
+    ) -> ScopedSymbolTable {
+        ScopedSymbolTable {
+            scope_name: String::from(scope_name),
+            symbols: Names::new(),
+            enclosing_scope,
+            interface: None,
+            used: false,
+        }
+    }
+    pub fn insert(&mut self, name: &str, symbol: Symbol) -> Result<(), SemanticError> {
+        if self.symbols.contains_key(name) {
+            return Err(SemanticError::new(format!(
+                "duplicate identifier {} in scope {}",
+                name, self.scope_name
+            )));
+        }
+        self.symbols.insert(String::from(name), symbol);
+        Ok(())
+    }
+    /// Declare a name, replacing what it was declared as in this scope before
+    pub fn replace(&mut self, name: &str, symbol: Symbol) {
+        self.symbols.insert(String::from(name), symbol);
+    }
+    /// The names declared in this scope itself, as they were spelled
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.symbols.keys()
+    }
+    /// Find a symbol declared in this scope itself, ignoring the enclosing ones
+    pub fn lookup_local_mut(&mut self, name: &str) -> Option<&mut Symbol> {
+        self.symbols.get_mut(name)
+    }
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        match self.symbols.get(name) {
+            Some(symbol) if self.shows(name) => Some(symbol),
+            _ => self.enclosing_scope.as_ref()?.lookup(name),
+        }
+    }
+    /// Whether a name declared in this scope is seen from the scopes it encloses
+    fn shows(&self, name: &str) -> bool {
+        match &self.interface {
+            Some(interface) => self.used && interface.contains_key(name),
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(PartialEq, Clone, Copy, Debug, Serialize)]
+pub enum OpType {
+    INTEGER_CONST,
+    REAL_CONST,
+    CHAR_CONST,
+    STRING_CONST,
+    TRUE,
+    FALSE,
+    PLUS,
+    MINUS,
+    MUL,
+    FLOAT_DIV,
+    LPAREN,
+    RPAREN,
+    LBRACKET,
+    RBRACKET,
+    CARET,
+    ID,
+    ASSIGN,
+    SEMI,
+    COLON,
+    COMMA,
+    DOT,
+    RANGE,
+    EQUAL,
+    NOT_EQUAL,
+    LESS,
+    LESS_EQUAL,
+    GREATER,
+    GREATER_EQUAL,
+    // reserved keywords
+    PROGRAM,
+    BEGIN,
+    END,
+    IF,
+    THEN,
+    ELSE,
+    WHILE,
+    DO,
+    FOR,
+    TO,
+    DOWNTO,
+    REPEAT,
+    UNTIL,
+    CASE,
+    OF,
+    BREAK,
+    CONTINUE,
+    EXIT,
+    LABEL,
+    GOTO,
+    WITH,
+    FORWARD,
+    PACKED,
+    TRY,
+    EXCEPT,
+    FINALLY,
+    RAISE,
+    UNIT,
+    INTERFACE,
+    IMPLEMENTATION,
+    USES,
+    INITIALIZATION,
+    PROCEDURE,
+    FUNCTION,
+    CONST,
+    VAR,
+    TYPE,
+    INTEGER,
+    REAL,
+    BOOLEAN,
+    CHAR,
+    STRING,
+    TEXT,
+    FILE,
+    ARRAY,
+    SET,
+    RECORD,
+    NIL,
+    INTEGER_DIV,
+    MOD,
+    AND,
+    OR,
+    NOT,
+    IN,
+    EOF,
+}
+
+/// The reserved words along with the tokens they are read as
+const KEYWORDS: [(&str, OpType); 56] = [
+    ("PROGRAM", OpType::PROGRAM),
+    ("BEGIN", OpType::BEGIN),
+    ("END", OpType::END),
+    ("IF", OpType::IF),
+    ("THEN", OpType::THEN),
+    ("ELSE", OpType::ELSE),
+    ("WHILE", OpType::WHILE),
+    ("DO", OpType::DO),
+    ("FOR", OpType::FOR),
+    ("TO", OpType::TO),
+    ("DOWNTO", OpType::DOWNTO),
+    ("REPEAT", OpType::REPEAT),
+    ("UNTIL", OpType::UNTIL),
+    ("CASE", OpType::CASE),
+    ("OF", OpType::OF),
+    ("BREAK", OpType::BREAK),
+    ("CONTINUE", OpType::CONTINUE),
+    ("EXIT", OpType::EXIT),
+    ("LABEL", OpType::LABEL),
+    ("GOTO", OpType::GOTO),
+    ("WITH", OpType::WITH),
+    ("FORWARD", OpType::FORWARD),
+    ("PACKED", OpType::PACKED),
+    ("TRY", OpType::TRY),
+    ("EXCEPT", OpType::EXCEPT),
+    ("FINALLY", OpType::FINALLY),
+    ("RAISE", OpType::RAISE),
+    ("UNIT", OpType::UNIT),
+    ("INTERFACE", OpType::INTERFACE),
+    ("IMPLEMENTATION", OpType::IMPLEMENTATION),
+    ("USES", OpType::USES),
+    ("INITIALIZATION", OpType::INITIALIZATION),
+    ("PROCEDURE", OpType::PROCEDURE),
+    ("FUNCTION", OpType::FUNCTION),
+    ("CONST", OpType::CONST),
+    ("VAR", OpType::VAR),
+    ("TYPE", OpType::TYPE),
+    ("INTEGER", OpType::INTEGER),
+    ("REAL", OpType::REAL),
+    ("BOOLEAN", OpType::BOOLEAN),
+    ("CHAR", OpType::CHAR),
+    ("STRING", OpType::STRING),
+    ("TEXT", OpType::TEXT),
+    ("FILE", OpType::FILE),
+    ("ARRAY", OpType::ARRAY),
+    ("SET", OpType::SET),
+    ("RECORD", OpType::RECORD),
+    ("NIL", OpType::NIL),
+    ("TRUE", OpType::TRUE),
+    ("FALSE", OpType::FALSE),
+    ("DIV", OpType::INTEGER_DIV),
+    ("MOD", OpType::MOD),
+    ("AND", OpType::AND),
+    ("OR", OpType::OR),
+    ("NOT", OpType::NOT),
+    ("IN", OpType::IN),
+];
+
+/// The reserved words, in upper case
+pub fn keywords() -> impl Iterator<Item = &'static str> {
+    KEYWORDS.iter().map(|(keyword, _)| *keyword)
+}
+
+/// The token a word is read as if it is reserved, keywords being case insensitive
+pub fn keyword(word: &str) -> Option<OpType> {
+    let entry = KEYWORDS.iter().find(|(keyword, _)| keyword.eq_ignore_ascii_case(word));
+    entry.map(|(_, op_type)| *op_type)
+}
+
+#[derive(Serialize)]
+pub struct Token {
+    pub op_type: OpType,
+    /// the text of the token, shared with the other tokens of the same value
+    pub value: Rc<str>,
+    /// the source the token was read from
+    pub span: Span,
+}
+
+impl Token {
+    pub fn new(op_type: OpType, value: Rc<str>, span: Span) -> Token {
+        Token { op_type, value, span }
+    }
+}
+
+/// Where a token starts, for telling the order of tokens in the stream and
+/// for pointing diagnostics at the file they come from
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Position {
+    /// how many bytes come before it in the token stream, counting those
+    /// spliced in by `{$I}`
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    /// the file it lies in, `None` for a program given as text
+    pub file: Option<Rc<str>>,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)?;
+        if let Some(file) = &self.file {
+            write!(f, " of {}", file)?;
+        }
+        Ok(())
+    }
+}
+
+/// The stretch of source a token or a node of the tree was read from
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Span {
+    /// where it starts
+    pub start: Position,
+    /// the bytes it covers in the text of the file it starts in
+    pub bytes: Range<usize>,
+}
+
+impl Span {
+    /// The span from the start of this one to the end of `last`, or just this
+    /// one when `last` lies in another file
+    pub fn to(&self, last: &Span) -> Span {
+        if last.start.file != self.start.file || last.bytes.end < self.bytes.start {
+            return self.clone();
+        }
+        Span { start: self.start.clone(), bytes: self.bytes.start..last.bytes.end }
+    }
+    /// The empty span right where this one starts, for a node read from no source
+    pub fn empty(&self) -> Span {
+        Span { start: self.start.clone(), bytes: self.bytes.start..self.bytes.start }
+    }
+}
@@ -0,0 +1,611 @@
+use std::fmt;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::ast::{Constant, Initializer};
+use crate::error::RuntimeError;
+use crate::names::same_name;
+use crate::set::{self, Set};
+use crate::token::Position;
+use crate::value::{Selector, Value};
+
+/// Arrays are allocated up front, so their index types have to stay small
+pub const MAX_ARRAY_LENGTH: i64 = 1 << 24;
+
+/// `CASE tag: tag_type OF labels: (fields); ...`, the variants of a record,
+/// whose fields overlap so that only those of one variant are in use at a time
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct VariantPart {
+    /// the field telling which variant is active, or `None` when the record
+    /// does not keep it and the active variant is the one last stored to
+    pub tag: Option<String>,
+    /// the type of the tag, named by the declaration even when the record
+    /// does not keep the tag
+    pub tag_type: Box<Type>,
+    /// the labels selecting each variant along with the names of its fields,
+    /// those of the variant parts nested in it included
+    pub variants: Vec<(Vec<Constant>, Vec<String>)>,
+}
+
+impl VariantPart {
+    /// The index of the variant holding a field
+    pub fn variant_of(&self, field: &str) -> Option<usize> {
+        self.variants.iter().position(|(_, names)| names.iter().any(|name| same_name(name, field)))
+    }
+    /// The index of the variant selected by a value of the tag
+    fn selected_by(&self, tag: &Value) -> Option<usize> {
+        self.variants.iter().position(|(labels, _)| {
+            labels.iter().any(|label| label.value.ordinal().ok() == tag.ordinal().ok())
+        })
+    }
+}
+
+/// The type of a variable, parameter or function result
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Type {
+    Integer,
+    Real,
+    Boolean,
+    Char,
+    String,
+    /// A file of lines of text, read and written as READ and WRITE do the
+    /// standard input and output
+    Text,
+    /// `FILE OF component`, a file of values of the component type, each
+    /// stored in the same number of bytes
+    File(Box<Type>),
+    /// `(first, second, ...)`, an ordinal type of the listed identifiers
+    Enum(Rc<[String]>),
+    /// `low..high`, a range of the values of an ordinal type
+    Subrange { low: Constant, high: Constant },
+    /// `ARRAY[index] OF element`, indexed by the values of the ordinal type `index`,
+    /// `packed` when declared PACKED
+    Array { index: Box<Type>, element: Box<Type>, packed: bool },
+    /// `ARRAY OF element`, an array indexed from 0 whose length is set by SETLENGTH;
+    /// as the type of a parameter, it takes an array of any bounds, reindexed from 0
+    DynamicArray(Box<Type>),
+    /// `SET OF element`, where the ordinals of `element` lie within `0..=set::MAX_ORDINAL`
+    Set(Box<Type>),
+    /// `RECORD fields END`, the fields in the order they are declared, those
+    /// of the variants included
+    Record { fields: Vec<(String, Type)>, variant_parts: Vec<VariantPart> },
+    /// `^target`, where the target is left unresolved so that a pointer can
+    /// refer to a type declared after it, like the record containing it
+    Pointer(Box<Type>),
+    /// `PROCEDURE(params)` or `FUNCTION(params): result`, the type of a
+    /// procedural parameter or of a variable holding a routine
+    Routine { params: Vec<Type>, names: ParamNames, result: Option<Box<Type>> },
+    /// A type declared in a TYPE section, referred to by its name
+    Named(String),
+}
+
+/// The names the parameters of a procedural type are declared with, and
+/// whether each is CONST, which are printed back but do not tell two
+/// procedural types apart
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ParamNames(pub Vec<(String, bool)>);
+
+impl PartialEq for ParamNames {
+    fn eq(&self, _: &ParamNames) -> bool {
+        true
+    }
+}
+
+/// A value found outside of the range of the type it is stored as
+#[derive(Clone, Debug, PartialEq)]
+pub struct RangeError {
+    pub value: Value,
+    pub low: Value,
+    pub high: Value,
+    /// where the statement running into the error starts, when known
+    pub position: Option<Position>,
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "range check error")?;
+        if let Some(position) = &self.position {
+            write!(f, " at {}", position)?;
+        }
+        write!(f, ": {} is not in {}..{}", self.value, self.low, self.high)
+    }
+}
+
+/// A type as it would be written in a declaration, with the types it is
+/// made of spelled out rather than named
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Integer => write!(f, "INTEGER"),
+            Type::Real => write!(f, "REAL"),
+            Type::Boolean => write!(f, "BOOLEAN"),
+            Type::Char => write!(f, "CHAR"),
+            Type::String => write!(f, "STRING"),
+            Type::Text => write!(f, "TEXT"),
+            Type::File(component) => write!(f, "FILE OF {}", component),
+            Type::Enum(names) => write!(f, "({})", names.join(", ")),
+            Type::Subrange { low, high } => {
+                write!(f, "{}..{}", literal(&low.value), literal(&high.value))
+            }
+            Type::Array { index, element, packed } => {
+                if *packed {
+                    write!(f, "PACKED ")?;
+                }
+                write!(f, "ARRAY[{}] OF {}", index, element)
+            }
+            Type::DynamicArray(element) => write!(f, "ARRAY OF {}", element),
+            Type::Set(element) => write!(f, "SET OF {}", element),
+            Type::Record { fields, .. } => {
+                write!(f, "RECORD")?;
+                for (i, (name, type_node)) in fields.iter().enumerate() {
+                    let separator = if i > 0 { ";" } else { "" };
+                    write!(f, "{} {}: {}", separator, name, type_node)?;
+                }
+                write!(f, " END")
+            }
+            Type::Pointer(target) => write!(f, "^{}", target),
+            Type::Routine { params, result, .. } => {
+                write!(f, "{}", if result.is_some() { "FUNCTION" } else { "PROCEDURE" })?;
+                if !params.is_empty() {
+                    let params: Vec<String> = params.iter().map(Type::to_string).collect();
+                    write!(f, "({})", params.join("; "))?;
+                }
+                match result {
+                    Some(result) => write!(f, ": {}", result),
+                    None => Ok(()),
+                }
+            }
+            Type::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A bound of a subrange as it is written in its declaration, a character
+/// being quoted
+fn literal(value: &Value) -> String {
+    match value {
+        Value::Char(ch) => format!("'{}'", ch.to_string().replace('\'', "''")),
+        value => value.to_string(),
+    }
+}
+
+/// The class of the exceptions, which is the only one there is
+pub const EXCEPTION: &str = "Exception";
+
+impl Type {
+    /// The type of the variable an exception handler binds the exception to,
+    /// a record holding the message of the exception
+    pub fn exception() -> Type {
+        Type::Record {
+            fields: vec![(String::from("Message"), Type::String)],
+            variant_parts: Vec::new(),
+        }
+    }
+    /// The smallest and the largest value of an ordinal type
+    pub fn bounds(&self) -> (Value, Value) {
+        match self {
+            Type::Integer => (Value::Integer(i32::MIN), Value::Integer(i32::MAX)),
+            Type::Boolean => (Value::Boolean(false), Value::Boolean(true)),
+            Type::Char => (Value::Char('\0'), Value::Char('\u{ff}')),
+            Type::Enum(names) => (
+                Value::Enum { ordinal: 0, names: names.clone() },
+                Value::Enum { ordinal: names.len() as i32 - 1, names: names.clone() },
+            ),
+            Type::Subrange { low, high } => (low.value.clone(), high.value.clone()),
+            _ => panic!("expected an ordinal type"),
+        }
+    }
+    /// The type of a simple value, `None` for a structured one
+    pub fn of_value(value: &Value) -> Option<Type> {
+        match value {
+            Value::Integer(_) => Some(Type::Integer),
+            Value::Real(_) => Some(Type::Real),
+            Value::Boolean(_) => Some(Type::Boolean),
+            Value::Char(_) => Some(Type::Char),
+            Value::String(_) => Some(Type::String),
+            Value::Enum { names, .. } => Some(Type::Enum(names.clone())),
+            _ => None,
+        }
+    }
+    /// The type a subrange is a range of, or the type itself for any other
+    pub fn host_type(&self) -> Type {
+        match self {
+            Type::Subrange { low, .. } => Type::of_value(&low.value).unwrap(),
+            _ => self.clone(),
+        }
+    }
+    pub fn is_ordinal(&self) -> bool {
+        matches!(
+            self,
+            Type::Integer | Type::Boolean | Type::Char | Type::Enum(_) | Type::Subrange { .. }
+        )
+    }
+    /// The type of the element of an array or of the field of a record
+    pub fn select(&self, selector: &Selector) -> &Type {
+        match (self, selector) {
+            (Type::Array { element, .. } | Type::DynamicArray(element), Selector::Index(_)) => {
+                element
+            }
+            (Type::Record { fields, .. }, Selector::Field(name)) => {
+                match fields.iter().find(|(field, _)| same_name(field, name)) {
+                    Some((_, type_node)) => type_node,
+                    None => panic!("no field {} in record", name),
+                }
+            }
+            (_, Selector::Index(_)) => panic!("expected an array type"),
+            (_, Selector::Field(_)) => panic!("expected a record type"),
+        }
+    }
+    /// The value a variable of this type starts out with
+    pub fn default_value(&self) -> Result<Value, RuntimeError> {
+        Ok(match self {
+            Type::Integer => Value::Integer(0),
+            Type::Real => Value::Real(0.0),
+            Type::Boolean => Value::Boolean(false),
+            Type::Char => Value::Char('\0'),
+            Type::String => Value::String(String::new()),
+            Type::Text | Type::File(_) => Value::File(None),
+            Type::Enum(names) => Value::Enum { ordinal: 0, names: names.clone() },
+            Type::Subrange { low, .. } => low.value.clone(),
+            Type::Array { index, element, .. } => {
+                let (low, high) = index.bounds();
+                let length = high.ordinal()? as i64 - low.ordinal()? as i64 + 1;
+                if length > MAX_ARRAY_LENGTH {
+                    return Err(RuntimeError::new(format!(
+                        "array of {} elements is too large",
+                        length
+                    )));
+                }
+                Value::Array {
+                    low: low.ordinal()?,
+                    elements: vec![element.default_value()?; length as usize],
+                }
+            }
+            Type::DynamicArray(_) => Value::Array { low: 0, elements: Vec::new() },
+            Type::Set(element) => {
+                let (low, high) = element.bounds();
+                if low.ordinal()? < 0 || high.ordinal()? > set::MAX_ORDINAL {
+                    return Err(RuntimeError::new(format!(
+                        "set elements must lie within 0..{}",
+                        set::MAX_ORDINAL
+                    )));
+                }
+                Value::Set(Set::new(Some(low)))
+            }
+            Type::Record { fields, .. } => Value::Record(
+                fields
+                    .iter()
+                    .map(|(name, type_node)| Ok((name.clone(), type_node.default_value()?)))
+                    .collect::<Result<_, RuntimeError>>()?,
+            ),
+            Type::Pointer(_) => Value::Pointer(None),
+            Type::Routine { .. } => Value::Routine(None),
+            Type::Named(name) => panic!("unresolved type {}", name),
+        })
+    }
+    /// The number of bytes SIZEOF gives for a value of this type, which is
+    /// also the number a typed file stores it in
+    pub fn size(&self) -> usize {
+        match self {
+            Type::Integer => 4,
+            Type::Real => 8,
+            Type::Boolean | Type::Char => 1,
+            Type::Enum(names) => if names.len() <= 256 { 1 } else { 2 },
+            Type::Subrange { low, .. } => Type::of_value(&low.value).unwrap().size(),
+            // a length byte followed by up to 255 characters, as Turbo Pascal stores strings
+            Type::String => 256,
+            Type::Array { index, element, .. } => {
+                let (low, high) = index.bounds();
+                let length = high.ordinal().unwrap() as i64 - low.ordinal().unwrap() as i64 + 1;
+                length as usize * element.size()
+            }
+            Type::Set(_) => 32,
+            Type::Record { fields, .. } => {
+                fields.iter().map(|(_, type_node)| type_node.size()).sum()
+            }
+            // the address of what they refer to
+            Type::DynamicArray(_) | Type::Pointer(_) | Type::Routine { .. } => 8,
+            // the records Turbo Pascal keeps of an open file
+            Type::Text => 256,
+            Type::File(_) => 128,
+            Type::Named(name) => panic!("unresolved type {}", name),
+        }
+    }
+    /// The number of bytes a value of this type is stored in by a typed file,
+    /// or `None` when it cannot be stored, being or holding a reference
+    pub fn file_size(&self) -> Option<usize> {
+        let storable = match self {
+            Type::Array { element, .. } => element.file_size().is_some(),
+            Type::Record { fields, .. } => {
+                fields.iter().all(|(_, type_node)| type_node.file_size().is_some())
+            }
+            Type::DynamicArray(_) | Type::Pointer(_) | Type::Routine { .. } => false,
+            Type::Text | Type::File(_) => false,
+            _ => true,
+        };
+        storable.then(|| self.size())
+    }
+    /// Append the bytes a typed file stores a value of this type in
+    pub fn encode(&self, value: &Value, bytes: &mut Vec<u8>) -> Result<(), RuntimeError> {
+        match (self, value) {
+            (Type::Real, _) => bytes.extend(value.as_real()?.to_le_bytes()),
+            (Type::String, _) => {
+                let text = value.as_string()?;
+                let length = text.chars().count();
+                if length > 255 {
+                    return Err(RuntimeError::new(format!(
+                        "a string of {} characters is too long to be stored in a file",
+                        length
+                    )));
+                }
+                bytes.push(length as u8);
+                for ch in text.chars() {
+                    let byte = u8::try_from(ch).map_err(|_| {
+                        let message = format!("the character {:?} cannot be stored in a file", ch);
+                        RuntimeError::new(message)
+                    })?;
+                    bytes.push(byte);
+                }
+                bytes.resize(bytes.len() + 255 - length, 0);
+            }
+            (Type::Array { element, .. }, Value::Array { elements, .. }) => {
+                for value in elements {
+                    element.encode(value, bytes)?;
+                }
+            }
+            (Type::Record { fields, .. }, Value::Record(values)) => {
+                for ((_, type_node), (_, value)) in fields.iter().zip(values) {
+                    type_node.encode(value, bytes)?;
+                }
+            }
+            (Type::Set(_), Value::Set(set)) => bytes.extend(set.to_bytes()),
+            _ => {
+                let size = self.file_size().unwrap();
+                let ordinal = value.ordinal()?;
+                if size < 4 && !(0..1 << (8 * size)).contains(&ordinal) {
+                    return Err(RuntimeError::new(format!(
+                        "{} cannot be stored in {} bytes of a file",
+                        value, size
+                    )));
+                }
+                bytes.extend(&ordinal.to_le_bytes()[..size]);
+            }
+        }
+        Ok(())
+    }
+    /// The value of this type a typed file stores in `bytes`
+    pub fn decode(&self, bytes: &[u8]) -> Result<Value, RuntimeError> {
+        Ok(match self {
+            Type::Real => Value::Real(f64::from_le_bytes(bytes[..8].try_into().unwrap())),
+            Type::String => {
+                let length = bytes[0] as usize;
+                Value::String(bytes[1..=length].iter().map(|&byte| byte as char).collect())
+            }
+            Type::Array { index, element, .. } => Value::Array {
+                low: index.bounds().0.ordinal()?,
+                elements: bytes
+                    .chunks(element.file_size().unwrap())
+                    .map(|chunk| element.decode(chunk))
+                    .collect::<Result<_, _>>()?,
+            },
+            Type::Record { fields, .. } => {
+                let mut offset = 0;
+                let mut values = Vec::new();
+                for (name, type_node) in fields {
+                    let size = type_node.file_size().unwrap();
+                    values.push((name.clone(), type_node.decode(&bytes[offset..offset + size])?));
+                    offset += size;
+                }
+                Value::Record(values)
+            }
+            Type::Set(element) => Value::Set(Set::from_bytes(bytes, element.bounds().0)),
+            _ => {
+                let size = self.file_size().unwrap();
+                let mut ordinal = [0; 4];
+                ordinal[..size].copy_from_slice(&bytes[..size]);
+                self.bounds().0.with_ordinal(i32::from_le_bytes(ordinal))?
+            }
+        })
+    }
+    /// The value a typed constant of this type starts out with
+    pub fn initial_value(&self, initializer: &Initializer) -> Result<Value, RuntimeError> {
+        Ok(match (self, initializer) {
+            (Type::Array { element, .. }, Initializer::List(items)) => {
+                let Value::Array { low, elements } = self.default_value()? else {
+                    unreachable!()
+                };
+                if items.len() != elements.len() {
+                    return Err(RuntimeError::new(format!(
+                        "expected {} elements in an array constant but found {}",
+                        elements.len(), items.len()
+                    )));
+                }
+                let elements = items
+                    .iter()
+                    .map(|item| element.initial_value(item))
+                    .collect::<Result<_, _>>()?;
+                Value::Array { low, elements }
+            }
+            (Type::DynamicArray(element), Initializer::List(items)) => Value::Array {
+                low: 0,
+                elements: items
+                    .iter()
+                    .map(|item| element.initial_value(item))
+                    .collect::<Result<_, _>>()?,
+            },
+            (Type::Record { fields, variant_parts }, Initializer::Fields(items)) => {
+                // the fields are given in the order they are declared, leaving
+                // out only those of the variants which are not chosen
+                let mut items = items.iter().peekable();
+                let mut values = Vec::new();
+                for (name, type_node) in fields {
+                    let value = match items.peek() {
+                        Some((field, item)) if same_name(field, name) => {
+                            items.next();
+                            type_node.initial_value(item)?
+                        }
+                        _ if variant_parts.iter().any(|part| part.variant_of(name).is_some()) => {
+                            type_node.default_value()?
+                        }
+                        _ => {
+                            return Err(RuntimeError::new(format!(
+                                "expected field {} in a record constant",
+                                name
+                            )));
+                        }
+                    };
+                    values.push((name.clone(), value));
+                }
+                if let Some((field, _)) = items.next() {
+                    return Err(RuntimeError::new(format!(
+                        "unexpected field {} in a record constant",
+                        field
+                    )));
+                }
+                Value::Record(values)
+            }
+            (_, Initializer::Value(constant)) => {
+                let value = self.converted(constant.value.clone(), &self.default_value()?)?;
+                self.check_range(&value)?;
+                value
+            }
+            (Type::Array { .. } | Type::DynamicArray(_), _) => {
+                return Err(RuntimeError::new("expected a list of elements for an array constant"));
+            }
+            (Type::Record { .. }, _) => {
+                return Err(RuntimeError::new("expected a list of fields for a record constant"));
+            }
+            _ => {
+                return Err(RuntimeError::new(
+                    "expected a single value for a constant of a simple type",
+                ));
+            }
+        })
+    }
+    /// Convert a value for storing in a variable of this type which currently
+    /// holds `target`.
+    ///
+    /// Besides what `Value::converted_like` allows, a packed array of
+    /// characters takes a string of exactly its length and a dynamic array
+    /// takes an array of any length.
+    pub fn converted(&self, value: Value, target: &Value) -> Result<Value, RuntimeError> {
+        match (self, target) {
+            (Type::DynamicArray(element), _) => match value {
+                Value::Array { elements, .. } => {
+                    let default = element.default_value()?;
+                    Ok(Value::Array {
+                        low: 0,
+                        elements: elements
+                            .into_iter()
+                            .map(|value| element.converted(value, &default))
+                            .collect::<Result<_, _>>()?,
+                    })
+                }
+                _ => Err(RuntimeError::new(format!(
+                    "cannot assign a value of type {} to a dynamic array",
+                    value.type_name()
+                ))),
+            },
+            (Type::Array { element, packed: true, .. }, Value::Array { low, elements })
+                if **element == Type::Char && value.is_text() =>
+            {
+                let text = value.as_string()?;
+                if text.chars().count() != elements.len() {
+                    return Err(RuntimeError::new(format!(
+                        "cannot assign a string of length {} to a packed array of {} characters",
+                        text.chars().count(), elements.len()
+                    )));
+                }
+                Ok(Value::Array { low: *low, elements: text.chars().map(Value::Char).collect() })
+            }
+            _ => value.converted_like(target),
+        }
+    }
+    /// Reject accessing a field of a record which belongs to a variant other
+    /// than the one the tag of the record selects
+    pub fn check_variant(&self, record: &Value, field: &str) -> Result<(), RuntimeError> {
+        let Type::Record { variant_parts, .. } = self else { return Ok(()) };
+        for part in variant_parts {
+            let Some(tag) = &part.tag else { continue };
+            if let Some(variant) = part.variant_of(field)
+                && part.selected_by(record.select(&Selector::Field(tag.clone()))?) != Some(variant)
+            {
+                return Err(RuntimeError::new(format!(
+                    "field {} is not in the active variant of the record",
+                    field
+                )));
+            }
+        }
+        Ok(())
+    }
+    /// Make active the variant a store to a field of a record selects, being
+    /// the one holding the field or, for a tag, the one its new value selects.
+    ///
+    /// The fields of the other variants of the part are reset, as they share
+    /// their storage with the fields of the variant being made active.
+    pub fn switch_variant(
+        &self,
+        record: &mut Value,
+        field: &str,
+        value: &Value,
+    ) -> Result<(), RuntimeError> {
+        let Type::Record { variant_parts, .. } = self else { return Ok(()) };
+        for part in variant_parts {
+            let active = if part.tag.as_deref().is_some_and(|tag| same_name(tag, field)) {
+                let current = record.select(&Selector::Field(String::from(field)))?;
+                let selected = part.selected_by(value);
+                if part.selected_by(current) == selected {
+                    continue;
+                }
+                selected
+            } else if part.tag.is_none() && part.variant_of(field).is_some() {
+                part.variant_of(field)
+            } else {
+                continue;
+            };
+            for (i, (_, names)) in part.variants.iter().enumerate() {
+                if Some(i) == active {
+                    continue;
+                }
+                for name in names {
+                    let selector = Selector::Field(name.clone());
+                    *record.select_mut(&selector)? = self.select(&selector).default_value()?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Reject a value lying outside of a subrange, including inside an array
+    /// or a record, telling which value and which range
+    pub fn check_range(&self, value: &Value) -> Result<(), RuntimeError> {
+        match (self, value) {
+            (Type::Subrange { low, high }, _)
+                if value.ordinal()? < low.value.ordinal()?
+                    || value.ordinal()? > high.value.ordinal()? =>
+            {
+                return Err(RuntimeError::from(RangeError {
+                    value: value.clone(),
+                    low: low.value.clone(),
+                    high: high.value.clone(),
+                    position: None,
+                }));
+            }
+            (
+                Type::Array { element, .. } | Type::DynamicArray(element),
+                Value::Array { elements, .. },
+            ) => {
+                for value in elements {
+                    element.check_range(value)?;
+                }
+            }
+            (Type::Record { fields, .. }, Value::Record(values)) => {
+                for ((_, type_node), (_, value)) in fields.iter().zip(values) {
+                    type_node.check_range(value)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::error::RuntimeError;
+use crate::names::same_name;
+use crate::set::Set;
+
+/// A runtime value
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Value {
+    Integer(i32),
+    Real(f64),
+    Boolean(bool),
+    Char(char),
+    /// The value at position `ordinal` of an enumerated type with the given identifiers
+    Enum { ordinal: i32, names: Rc<[String]> },
+    String(String),
+    /// The elements of an array along with the ordinal of its first index
+    Array { low: i32, elements: Vec<Value> },
+    Set(Set),
+    /// the fields of a record in the order they are declared
+    Record(Vec<(String, Value)>),
+    /// the address of a heap cell, or `None` for NIL
+    Pointer(Option<usize>),
+    /// the name of a procedure or function along with the index of the frame
+    /// declaring it, which the call stack keeps for as long as the value may
+    /// be called, or `None` when no routine is set
+    Routine(Option<(String, usize)>),
+    /// the handle of the file assigned to a file variable, or `None` before
+    /// ASSIGN gives it one
+    File(Option<usize>),
+}
+
+/// One step from a value to a part of it
+#[derive(Clone)]
+pub enum Selector {
+    Index(i32),
+    Field(String),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "INTEGER",
+            Value::Real(_) => "REAL",
+            Value::Boolean(_) => "BOOLEAN",
+            Value::Char(_) => "CHAR",
+            Value::Enum { .. } => "ENUMERATION",
+            Value::String(_) => "STRING",
+            Value::Array { .. } => "ARRAY",
+            Value::Set(_) => "SET",
+            Value::Record(_) => "RECORD",
+            Value::Pointer(_) => "POINTER",
+            Value::Routine(_) => "ROUTINE",
+            Value::File(_) => "FILE",
+        }
+    }
+    /// The handle of the file assigned to a file variable
+    /// The error of finding this value where a value of another type is expected
+    pub fn mismatch(&self, expected: &'static str) -> RuntimeError {
+        RuntimeError::TypeMismatch { expected, found: self.type_name() }
+    }
+    /// The handle of the file assigned to a file variable
+    pub fn as_file(&self) -> Result<usize, RuntimeError> {
+        match self {
+            Value::File(Some(handle)) => Ok(*handle),
+            Value::File(None) => Err(RuntimeError::new("file used before ASSIGN gave it a name")),
+            _ => Err(self.mismatch("a FILE value")),
+        }
+    }
+    pub fn as_integer(&self) -> Result<i32, RuntimeError> {
+        match self {
+            Value::Integer(i) => Ok(*i),
+            _ => Err(self.mismatch("an INTEGER value")),
+        }
+    }
+    /// Integers are promoted to reals wherever a real is expected
+    pub fn as_real(&self) -> Result<f64, RuntimeError> {
+        match self {
+            Value::Integer(i) => Ok(*i as f64),
+            Value::Real(r) => Ok(*r),
+            _ => Err(self.mismatch("a REAL value")),
+        }
+    }
+    pub fn as_boolean(&self) -> Result<bool, RuntimeError> {
+        match self {
+            Value::Boolean(b) => Ok(*b),
+            _ => Err(self.mismatch("a BOOLEAN value")),
+        }
+    }
+    /// The position of an ordinal value in its type, used by FOR and CASE
+    pub fn ordinal(&self) -> Result<i32, RuntimeError> {
+        match self {
+            Value::Integer(i) => Ok(*i),
+            Value::Boolean(b) => Ok(*b as i32),
+            Value::Char(c) => Ok(*c as i32),
+            Value::Enum { ordinal, .. } => Ok(*ordinal),
+            _ => Err(self.mismatch("an ordinal value")),
+        }
+    }
+    /// The value of the same ordinal type at position `ordinal`
+    pub fn with_ordinal(&self, ordinal: i32) -> Result<Value, RuntimeError> {
+        match self.try_with_ordinal(ordinal) {
+            Some(value) => Ok(value),
+            None if self.ordinal().is_err() => Err(self.mismatch("an ordinal value")),
+            None => Err(RuntimeError::new(format!(
+                "{} is not a valid {} ordinal",
+                ordinal, self.type_name()
+            ))),
+        }
+    }
+    /// Like `with_ordinal`, but `None` when no value of the type has that
+    /// position, or the value is not an ordinal one
+    pub fn try_with_ordinal(&self, ordinal: i32) -> Option<Value> {
+        match self {
+            Value::Integer(_) => Some(Value::Integer(ordinal)),
+            Value::Boolean(_) => match ordinal {
+                0 | 1 => Some(Value::Boolean(ordinal == 1)),
+                _ => None,
+            },
+            // CHAR has the 256 characters from '\0' to '\u{ff}', as its bounds say
+            Value::Char(_) => u8::try_from(ordinal).ok().map(|byte| Value::Char(char::from(byte))),
+            Value::Enum { names, .. } => usize::try_from(ordinal)
+                .is_ok_and(|i| i < names.len())
+                .then(|| Value::Enum { ordinal, names: names.clone() }),
+            _ => None,
+        }
+    }
+    pub fn is_text(&self) -> bool {
+        matches!(self, Value::String(_) | Value::Char(_))
+    }
+    /// The text of a string, where a character counts as a string of length one
+    pub fn as_string(&self) -> Result<String, RuntimeError> {
+        match self {
+            Value::String(s) => Ok(s.clone()),
+            Value::Char(c) => Ok(String::from(*c)),
+            _ => Err(self.mismatch("a STRING value")),
+        }
+    }
+    pub fn as_pointer(&self) -> Result<Option<usize>, RuntimeError> {
+        match self {
+            Value::Pointer(address) => Ok(*address),
+            _ => Err(self.mismatch("a POINTER value")),
+        }
+    }
+    /// The element of an array or the field of a record
+    pub fn select(&self, selector: &Selector) -> Result<&Value, RuntimeError> {
+        match (self, selector) {
+            (Value::Array { low, elements }, Selector::Index(index)) => {
+                Ok(&elements[array_offset(*low, elements.len(), *index)?])
+            }
+            (Value::Record(fields), Selector::Field(name)) => {
+                match fields.iter().find(|(field, _)| same_name(field, name)) {
+                    Some((_, value)) => Ok(value),
+                    None => Err(RuntimeError::new(format!("no field {} in record", name))),
+                }
+            }
+            (_, Selector::Index(_)) => Err(self.mismatch("an ARRAY value")),
+            (_, Selector::Field(_)) => Err(self.mismatch("a RECORD value")),
+        }
+    }
+    pub fn select_mut(&mut self, selector: &Selector) -> Result<&mut Value, RuntimeError> {
+        match (self, selector) {
+            (Value::Array { low, elements }, Selector::Index(index)) => {
+                let offset = array_offset(*low, elements.len(), *index)?;
+                Ok(&mut elements[offset])
+            }
+            (Value::Record(fields), Selector::Field(name)) => {
+                match fields.iter_mut().find(|(field, _)| same_name(field, name)) {
+                    Some((_, value)) => Ok(value),
+                    None => Err(RuntimeError::new(format!("no field {} in record", name))),
+                }
+            }
+            (value, Selector::Index(_)) => Err(value.mismatch("an ARRAY value")),
+            (value, Selector::Field(_)) => Err(value.mismatch("a RECORD value")),
+        }
+    }
+    /// Order two values, promoting an integer compared with a real.
+    ///
+    /// Gives `None` for values which cannot be ordered, like a NaN real.
+    pub fn compare(&self, other: &Value) -> Result<Option<Ordering>, RuntimeError> {
+        Ok(match (self, other) {
+            (Value::Integer(left), Value::Integer(right)) => Some(left.cmp(right)),
+            (Value::Boolean(left), Value::Boolean(right)) => Some(left.cmp(right)),
+            (Value::Char(left), Value::Char(right)) => Some(left.cmp(right)),
+            (Value::Enum { ordinal: left, .. }, Value::Enum { ordinal: right, .. }) => {
+                Some(left.cmp(right))
+            }
+            // pointers are only ever equal or not
+            (Value::Pointer(left), Value::Pointer(right)) => {
+                (left == right).then_some(Ordering::Equal)
+            }
+            // strings compare lexicographically, a character being a string of length one
+            _ if self.is_text() && other.is_text() => {
+                Some(self.as_string()?.cmp(&other.as_string()?))
+            }
+            _ if self.is_text() || other.is_text() => {
+                return Err(RuntimeError::new(format!(
+                    "cannot compare {} with {}",
+                    self.type_name(), other.type_name()
+                )));
+            }
+            _ => self.as_real()?.partial_cmp(&other.as_real()?),
+        })
+    }
+    /// Convert the value for storing in a variable which currently holds `target`.
+    ///
+    /// A variable never changes the kind of value it holds, so `target` tells
+    /// the declared type of the variable.
+    pub fn converted_like(self, target: &Value) -> Result<Value, RuntimeError> {
+        Ok(match (target, &self) {
+            (Value::Real(_), Value::Integer(i)) => Value::Real(*i as f64),
+            (Value::String(_), Value::Char(c)) => Value::String(String::from(*c)),
+            (Value::Char(_), Value::String(s)) if s.chars().count() == 1 => {
+                Value::Char(s.chars().next().unwrap())
+            }
+            // arrays must have the same bounds, their elements are converted one by one
+            (Value::Array { low, elements }, Value::Array { low: value_low, elements: values })
+                if low == value_low && elements.len() == values.len() => Value::Array {
+                low: *low,
+                elements: values
+                    .iter()
+                    .zip(elements)
+                    .map(|(value, element)| value.clone().converted_like(element))
+                    .collect::<Result<_, _>>()?,
+            },
+            (Value::Set(target), Value::Set(set)) => {
+                Value::Set(set.clone().converted_like(target)?)
+            }
+            // records must have the same fields, converted one by one
+            (Value::Record(fields), Value::Record(values))
+                if fields.len() == values.len()
+                    && fields
+                        .iter()
+                        .zip(values)
+                        .all(|((field, _), (name, _))| same_name(field, name)) =>
+            {
+                Value::Record(
+                    values
+                        .iter()
+                        .zip(fields)
+                        .map(|((name, value), (_, field))| {
+                            Ok((name.clone(), value.clone().converted_like(field)?))
+                        })
+                        .collect::<Result<_, RuntimeError>>()?,
+                )
+            }
+            (Value::Record(_), _) => {
+                return Err(RuntimeError::new(format!(
+                    "cannot assign a value of type {} to a record of different fields",
+                    self.type_name()
+                )));
+            }
+            (Value::Enum { names, .. }, Value::Enum { names: value_names, .. })
+                if names != value_names =>
+            {
+                return Err(RuntimeError::new(format!(
+                    "cannot assign {} to a variable of a different enumerated type",
+                    self
+                )));
+            }
+            (Value::Array { .. }, _) => {
+                return Err(RuntimeError::new(format!(
+                    "cannot assign a value of type {} to an array of different bounds",
+                    self.type_name()
+                )));
+            }
+            _ if std::mem::discriminant(target) == std::mem::discriminant(&self) => self,
+            _ => {
+                return Err(RuntimeError::new(format!(
+                    "cannot assign a value of type {} to a variable of type {}",
+                    self.type_name(), target.type_name()
+                )));
+            }
+        })
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Real(r) => write!(f, "{:?}", r),
+            Value::Boolean(b) => write!(f, "{}", if *b { "TRUE" } else { "FALSE" }),
+            Value::Char(c) => write!(f, "{}", c),
+            Value::Enum { ordinal, names } => write!(f, "{}", names[*ordinal as usize]),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Array { elements, .. } => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+            Value::Set(set) => write!(f, "{}", set),
+            Value::Record(fields) => {
+                write!(f, "(")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}: {}", name, value)?;
+                }
+                write!(f, ")")
+            }
+            Value::Pointer(None) => write!(f, "NIL"),
+            Value::Pointer(Some(address)) => write!(f, "^{}", address),
+            Value::Routine(None) => write!(f, "NIL"),
+            Value::Routine(Some((name, _))) => write!(f, "{}", name),
+            Value::File(None) => write!(f, "unassigned file"),
+            Value::File(Some(handle)) => write!(f, "file #{}", handle),
+        }
+    }
+}
+
+/// The position in the elements of an array of the element at the given index ordinal
+fn array_offset(low: i32, len: usize, index: i32) -> Result<usize, RuntimeError> {
+    match usize::try_from(index as i64 - low as i64) {
+        Ok(offset) if offset < len => Ok(offset),
+        _ => Err(RuntimeError::new(format!(
+            "array index {} out of range {}..{}",
+            index, low, low + len as i32 - 1
+        ))),
+    }
+}
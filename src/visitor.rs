@@ -0,0 +1,115 @@
+use std::rc::Rc;
+
+use crate::ast::{
+    Assign, BinOp, Block, Case, Compound, ConstDecl, Decl, Deref, Exit, Expr, Field, For,
+    FormattedParam, ForwardDecl, FunctionCall, FunctionDecl, Goto, If, Index, LabelDecl, Labeled,
+    Literal, ProcedureCall, ProcedureDecl, Program, Raise, Repeat, SetConstructor, Stmt,
+    TryExcept, TryFinally, TypeDecl, TypedConstDecl, UnaryOp, Var, VarDecl, While, With,
+};
+use crate::token::Span;
+
+/// A pass over the syntax tree, with a method for each kind of node.
+///
+/// `dispatch_stmt`, `dispatch_expr` and `dispatch_decl` hand a node to the
+/// method of its kind. A pass goes down into the children of a node itself,
+/// through the dispatchers, so it chooses whether, when and how often each
+/// child is visited, like the interpreter running the body of a loop.
+pub trait Visitor {
+    /// what visiting a statement, a block or a program gives
+    type StmtOutput;
+    /// what visiting an expression gives, like its value or its type
+    type ExprOutput;
+    /// what visiting a declaration gives
+    type DeclOutput;
+
+    fn visit_program(&mut self, node: &Program) -> Self::StmtOutput;
+    fn visit_block(&mut self, node: &Block) -> Self::StmtOutput;
+
+    fn dispatch_stmt(&mut self, node: &Stmt) -> Self::StmtOutput {
+        match node {
+            Stmt::Compound(node) => self.visit_compound(node),
+            Stmt::Assign(node) => self.visit_assign(node),
+            Stmt::ProcedureCall(node) => self.visit_procedure_call(node),
+            Stmt::If(node) => self.visit_if(node),
+            Stmt::While(node) => self.visit_while(node),
+            Stmt::For(node) => self.visit_for(node),
+            Stmt::Repeat(node) => self.visit_repeat(node),
+            Stmt::Case(node) => self.visit_case(node),
+            Stmt::With(node) => self.visit_with(node),
+            Stmt::Break(span) => self.visit_break(span),
+            Stmt::Continue(span) => self.visit_continue(span),
+            Stmt::Exit(node) => self.visit_exit(node),
+            Stmt::Goto(node) => self.visit_goto(node),
+            Stmt::Labeled(node) => self.visit_labeled(node),
+            Stmt::TryExcept(node) => self.visit_try_except(node),
+            Stmt::TryFinally(node) => self.visit_try_finally(node),
+            Stmt::Raise(node) => self.visit_raise(node),
+            Stmt::NoOp(span) => self.visit_no_op(span),
+        }
+    }
+    fn visit_compound(&mut self, node: &Compound) -> Self::StmtOutput;
+    fn visit_assign(&mut self, node: &Assign) -> Self::StmtOutput;
+    fn visit_procedure_call(&mut self, node: &ProcedureCall) -> Self::StmtOutput;
+    fn visit_if(&mut self, node: &If) -> Self::StmtOutput;
+    fn visit_while(&mut self, node: &While) -> Self::StmtOutput;
+    fn visit_for(&mut self, node: &For) -> Self::StmtOutput;
+    fn visit_repeat(&mut self, node: &Repeat) -> Self::StmtOutput;
+    fn visit_case(&mut self, node: &Case) -> Self::StmtOutput;
+    fn visit_with(&mut self, node: &With) -> Self::StmtOutput;
+    fn visit_break(&mut self, span: &Span) -> Self::StmtOutput;
+    fn visit_continue(&mut self, span: &Span) -> Self::StmtOutput;
+    fn visit_exit(&mut self, node: &Exit) -> Self::StmtOutput;
+    fn visit_goto(&mut self, node: &Goto) -> Self::StmtOutput;
+    fn visit_labeled(&mut self, node: &Labeled) -> Self::StmtOutput;
+    fn visit_try_except(&mut self, node: &TryExcept) -> Self::StmtOutput;
+    fn visit_try_finally(&mut self, node: &TryFinally) -> Self::StmtOutput;
+    fn visit_raise(&mut self, node: &Raise) -> Self::StmtOutput;
+    fn visit_no_op(&mut self, span: &Span) -> Self::StmtOutput;
+
+    fn dispatch_expr(&mut self, node: &Expr) -> Self::ExprOutput {
+        match node {
+            Expr::BinOp(node) => self.visit_bin_op(node),
+            Expr::Literal(node) => self.visit_literal(node),
+            Expr::UnaryOp(node) => self.visit_unary_op(node),
+            Expr::Var(node) => self.visit_var(node),
+            Expr::Index(node) => self.visit_index(node),
+            Expr::Field(node) => self.visit_field(node),
+            Expr::Deref(node) => self.visit_deref(node),
+            Expr::SetConstructor(node) => self.visit_set_constructor(node),
+            Expr::FunctionCall(node) => self.visit_function_call(node),
+            Expr::FormattedParam(node) => self.visit_formatted_param(node),
+        }
+    }
+    fn visit_bin_op(&mut self, node: &BinOp) -> Self::ExprOutput;
+    fn visit_literal(&mut self, node: &Literal) -> Self::ExprOutput;
+    fn visit_unary_op(&mut self, node: &UnaryOp) -> Self::ExprOutput;
+    fn visit_var(&mut self, node: &Var) -> Self::ExprOutput;
+    fn visit_index(&mut self, node: &Index) -> Self::ExprOutput;
+    fn visit_field(&mut self, node: &Field) -> Self::ExprOutput;
+    fn visit_deref(&mut self, node: &Deref) -> Self::ExprOutput;
+    fn visit_set_constructor(&mut self, node: &SetConstructor) -> Self::ExprOutput;
+    fn visit_function_call(&mut self, node: &FunctionCall) -> Self::ExprOutput;
+    fn visit_formatted_param(&mut self, node: &FormattedParam) -> Self::ExprOutput;
+
+    fn dispatch_decl(&mut self, node: &Decl) -> Self::DeclOutput {
+        match node {
+            Decl::Label(node) => self.visit_label_decl(node),
+            Decl::Const(node) => self.visit_const_decl(node),
+            Decl::TypedConst(node) => self.visit_typed_const_decl(node),
+            Decl::Type(node) => self.visit_type_decl(node),
+            Decl::Var(node) => self.visit_var_decl(node),
+            Decl::Forward(node) => self.visit_forward_decl(node),
+            Decl::Procedure(node) => self.visit_procedure_decl(node),
+            Decl::Function(node) => self.visit_function_decl(node),
+        }
+    }
+    fn visit_label_decl(&mut self, node: &LabelDecl) -> Self::DeclOutput;
+    fn visit_const_decl(&mut self, node: &ConstDecl) -> Self::DeclOutput;
+    fn visit_typed_const_decl(&mut self, node: &TypedConstDecl) -> Self::DeclOutput;
+    fn visit_type_decl(&mut self, node: &TypeDecl) -> Self::DeclOutput;
+    fn visit_var_decl(&mut self, node: &VarDecl) -> Self::DeclOutput;
+    fn visit_forward_decl(&mut self, node: &ForwardDecl) -> Self::DeclOutput;
+    /// the routine is shared, for a pass to keep it past the visit
+    fn visit_procedure_decl(&mut self, node: &Rc<ProcedureDecl>) -> Self::DeclOutput;
+    fn visit_function_decl(&mut self, node: &Rc<FunctionDecl>) -> Self::DeclOutput;
+}